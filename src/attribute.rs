@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+
+use crate::cmn;
+use crate::error::{I3sError, Result};
+use crate::io::BufferReader;
+
+/*
+Values of a feature attribute or of a point cloud attribute, the latter with
+valuesPerElement consecutive values per point.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeColumn {
+    Int8(Vec<i8>),
+    UInt8(Vec<u8>),
+    Int16(Vec<i16>),
+    UInt16(Vec<u16>),
+    Int32(Vec<i32>),
+    UInt32(Vec<u32>),
+    Int64(Vec<i64>),
+    UInt64(Vec<u64>),
+    Float32(Vec<f32>),
+    Float64(Vec<f64>),
+    Oid32(Vec<u32>),
+    Oid64(Vec<u64>),
+    String(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue<'a> {
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(&'a str),
+}
+
+impl AttributeValue<'_> {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            AttributeValue::Int(value) => Some(*value as f64),
+            AttributeValue::UInt(value) => Some(*value as f64),
+            AttributeValue::Float(value) => Some(*value),
+            AttributeValue::String(_) => None,
+        }
+    }
+}
+
+impl AttributeColumn {
+    pub fn len(&self) -> usize {
+        match self {
+            AttributeColumn::Int8(values) => values.len(),
+            AttributeColumn::UInt8(values) => values.len(),
+            AttributeColumn::Int16(values) => values.len(),
+            AttributeColumn::UInt16(values) => values.len(),
+            AttributeColumn::Int32(values) => values.len(),
+            AttributeColumn::UInt32(values) => values.len(),
+            AttributeColumn::Int64(values) => values.len(),
+            AttributeColumn::UInt64(values) => values.len(),
+            AttributeColumn::Float32(values) => values.len(),
+            AttributeColumn::Float64(values) => values.len(),
+            AttributeColumn::Oid32(values) => values.len(),
+            AttributeColumn::Oid64(values) => values.len(),
+            AttributeColumn::String(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<AttributeValue<'_>> {
+        match self {
+            AttributeColumn::Int8(values) => {
+                values.get(index).map(|v| AttributeValue::Int(*v as i64))
+            }
+            AttributeColumn::UInt8(values) => {
+                values.get(index).map(|v| AttributeValue::UInt(*v as u64))
+            }
+            AttributeColumn::Int16(values) => {
+                values.get(index).map(|v| AttributeValue::Int(*v as i64))
+            }
+            AttributeColumn::UInt16(values) => {
+                values.get(index).map(|v| AttributeValue::UInt(*v as u64))
+            }
+            AttributeColumn::Int32(values) => {
+                values.get(index).map(|v| AttributeValue::Int(*v as i64))
+            }
+            AttributeColumn::UInt32(values) | AttributeColumn::Oid32(values) => {
+                values.get(index).map(|v| AttributeValue::UInt(*v as u64))
+            }
+            AttributeColumn::Int64(values) => values.get(index).map(|v| AttributeValue::Int(*v)),
+            AttributeColumn::UInt64(values) | AttributeColumn::Oid64(values) => {
+                values.get(index).map(|v| AttributeValue::UInt(*v))
+            }
+            AttributeColumn::Float32(values) => {
+                values.get(index).map(|v| AttributeValue::Float(*v as f64))
+            }
+            AttributeColumn::Float64(values) => {
+                values.get(index).map(|v| AttributeValue::Float(*v))
+            }
+            AttributeColumn::String(values) => values.get(index).map(|v| AttributeValue::String(v)),
+        }
+    }
+
+    // None for strings
+    pub fn get_f64(&self, index: usize) -> Option<f64> {
+        self.get(index)?.as_f64()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Attribute {
+    pub key: String,
+    pub name: String,
+    pub values: AttributeColumn,
+}
+
+/*
+Attribute values are stored per feature in the same order as the feature ids
+of the node's geometry, so row i of every column belongs to feature_ids[i].
+*/
+#[derive(Debug, Clone, Default)]
+pub struct AttributeTable {
+    pub feature_ids: Vec<u64>,
+    pub attributes: Vec<Attribute>,
+}
+
+impl AttributeTable {
+    pub fn new(feature_ids: Vec<u64>, attributes: Vec<Attribute>) -> Result<Self> {
+        if let Some(attribute) = attributes
+            .iter()
+            .find(|attribute| attribute.values.len() != feature_ids.len())
+        {
+            return Err(I3sError::MalformedBuffer(format!(
+                "attribute {} has {} values for {} features",
+                attribute.name,
+                attribute.values.len(),
+                feature_ids.len()
+            )));
+        }
+        Ok(Self {
+            feature_ids,
+            attributes,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.feature_ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.feature_ids.is_empty()
+    }
+
+    pub fn column(&self, name: &str) -> Option<&AttributeColumn> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.name == name)
+            .map(|attribute| &attribute.values)
+    }
+
+    pub fn row(&self, feature_id: u64) -> Option<usize> {
+        self.feature_ids.iter().position(|id| *id == feature_id)
+    }
+
+    pub fn value(&self, feature_id: u64, name: &str) -> Option<AttributeValue<'_>> {
+        let row = self.row(feature_id)?;
+        self.column(name)?.get(row)
+    }
+}
+
+fn read_scalar(reader: &mut BufferReader, value_type: &str) -> Result<u64> {
+    match value_type.to_ascii_lowercase().as_str() {
+        "uint8" => Ok(reader.read_u8()? as u64),
+        "uint16" => Ok(reader.read_u16()? as u64),
+        "uint32" => Ok(reader.read_u32()? as u64),
+        "uint64" => Ok(reader.read_u64()?),
+        _ => Err(I3sError::MalformedBuffer(format!(
+            "unsupported header value type: {}",
+            value_type
+        ))),
+    }
+}
+
+macro_rules! read_column {
+    ($reader:expr, $count:expr, $size:expr, $read:ident) => {{
+        $reader.align($size)?;
+        let mut values = Vec::with_capacity($count.min($reader.remaining() / $size));
+        for _ in 0..$count {
+            values.push($reader.$read()?);
+        }
+        values
+    }};
+}
+
+pub(crate) fn value_size(value_type: &str) -> Option<usize> {
+    match value_type.to_ascii_lowercase().as_str() {
+        "int8" | "uint8" => Some(1),
+        "int16" | "uint16" => Some(2),
+        "int32" | "uint32" | "float32" | "oid32" => Some(4),
+        "int64" | "uint64" | "float64" | "oid64" => Some(8),
+        _ => None,
+    }
+}
+
+/*
+Numeric arrays are aligned to the size of their value type, so e.g. Float64
+values start after 4 bytes of padding following a single UInt32 count.
+*/
+pub(crate) fn read_values(
+    reader: &mut BufferReader,
+    value_type: &str,
+    count: usize,
+) -> Result<AttributeColumn> {
+    Ok(match value_type.to_ascii_lowercase().as_str() {
+        "int8" => AttributeColumn::Int8(read_column!(reader, count, 1, read_i8)),
+        "uint8" => AttributeColumn::UInt8(read_column!(reader, count, 1, read_u8)),
+        "int16" => AttributeColumn::Int16(read_column!(reader, count, 2, read_i16)),
+        "uint16" => AttributeColumn::UInt16(read_column!(reader, count, 2, read_u16)),
+        "int32" => AttributeColumn::Int32(read_column!(reader, count, 4, read_i32)),
+        "uint32" => AttributeColumn::UInt32(read_column!(reader, count, 4, read_u32)),
+        "int64" => AttributeColumn::Int64(read_column!(reader, count, 8, read_i64)),
+        "uint64" => AttributeColumn::UInt64(read_column!(reader, count, 8, read_u64)),
+        "float32" => AttributeColumn::Float32(read_column!(reader, count, 4, read_f32)),
+        "float64" => AttributeColumn::Float64(read_column!(reader, count, 8, read_f64)),
+        "oid32" => AttributeColumn::Oid32(read_column!(reader, count, 4, read_u32)),
+        "oid64" => AttributeColumn::Oid64(read_column!(reader, count, 8, read_u64)),
+        _ => {
+            return Err(I3sError::MalformedBuffer(format!(
+                "unsupported attribute value type: {}",
+                value_type
+            )))
+        }
+    })
+}
+
+fn read_strings(reader: &mut BufferReader, byte_counts: &[u64]) -> Result<AttributeColumn> {
+    let mut values = Vec::with_capacity(byte_counts.len());
+    for count in byte_counts {
+        let bytes = reader.read_bytes(*count as usize)?;
+        // byte counts include the null terminator
+        let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+        let value = std::str::from_utf8(bytes).map_err(|err| {
+            I3sError::MalformedBuffer(format!("invalid attribute string: {}", err))
+        })?;
+        values.push(value.to_string());
+    }
+    Ok(AttributeColumn::String(values))
+}
+
+pub fn decode_attribute_buffer(
+    info: &cmn::AttributeStorageInfo,
+    buffer: &[u8],
+) -> Result<AttributeColumn> {
+    let mut reader = BufferReader::new(buffer);
+    let mut header = HashMap::new();
+    for value in info.header.iter() {
+        header.insert(
+            value.property.as_str(),
+            read_scalar(&mut reader, &value.value_type)?,
+        );
+    }
+    let count = header.get("count").copied().ok_or_else(|| {
+        I3sError::MalformedBuffer(format!("attribute {} has no count header", info.key))
+    })? as usize;
+
+    let default_ordering = if info.object_ids.is_some() {
+        vec!["ObjectIds".to_string()]
+    } else if info.attribute_byte_counts.is_some() {
+        vec![
+            "attributeByteCounts".to_string(),
+            "attributeValues".to_string(),
+        ]
+    } else {
+        vec!["attributeValues".to_string()]
+    };
+    let mut byte_counts = None;
+    let mut column = None;
+    for array in info.ordering.as_ref().unwrap_or(&default_ordering) {
+        match array.as_str() {
+            "attributeByteCounts" => {
+                let value_type = info
+                    .attribute_byte_counts
+                    .as_ref()
+                    .map_or("UInt32", |value| value.value_type.as_str());
+                let counts = (0..count)
+                    .map(|_| read_scalar(&mut reader, value_type))
+                    .collect::<Result<Vec<u64>>>()?;
+                byte_counts = Some(counts);
+            }
+            "attributeValues" => {
+                let value_type = info
+                    .attribute_values
+                    .as_ref()
+                    .map_or("", |value| value.value_type.as_str());
+                column = Some(if value_type.eq_ignore_ascii_case("String") {
+                    let counts = byte_counts.as_deref().ok_or_else(|| {
+                        I3sError::MalformedBuffer(format!(
+                            "attribute {} has strings without byte counts",
+                            info.key
+                        ))
+                    })?;
+                    read_strings(&mut reader, counts)?
+                } else {
+                    read_values(&mut reader, value_type, count)?
+                });
+            }
+            "ObjectIds" | "objectIds" => {
+                let value_type = info
+                    .object_ids
+                    .as_ref()
+                    .map_or("Oid32", |value| value.value_type.as_str());
+                column = Some(read_values(&mut reader, value_type, count)?);
+            }
+            array => {
+                return Err(I3sError::MalformedBuffer(format!(
+                    "unknown attribute array: {}",
+                    array
+                )))
+            }
+        }
+    }
+    column.ok_or_else(|| I3sError::MalformedBuffer(format!("attribute {} has no values", info.key)))
+}
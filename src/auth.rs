@@ -0,0 +1,139 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+use crate::error::Result;
+
+pub type TokenFuture = Pin<Box<dyn Future<Output = Result<String>> + Send>>;
+
+type TokenProvider = Arc<dyn Fn() -> TokenFuture + Send + Sync>;
+
+/*
+ArcGIS accepts tokens and API keys alike in the X-Esri-Authorization header
+as "Bearer {token}", so by default both are sent that way rather than as the
+token query parameter, which would leak into logs and error messages with the
+url. Headers such as Authorization or Referer are sent with every request.
+*/
+#[derive(Clone, Default)]
+pub struct Authentication {
+    token: Option<String>,
+    headers: Vec<(String, String)>,
+    provider: Option<TokenProvider>,
+    token_in_query: bool,
+}
+
+impl Authentication {
+    pub fn token<S: Into<String>>(token: S) -> Self {
+        Self {
+            token: Some(token.into()),
+            ..Default::default()
+        }
+    }
+
+    pub fn api_key<S: Into<String>>(api_key: S) -> Self {
+        Self::token(api_key)
+    }
+
+    /*
+    The provider is asked for a token before the first request and again
+    whenever the service rejects the current one as invalid (498) or
+    missing (499), e.g. because it expired.
+    */
+    pub fn token_provider<F, Fut>(provider: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String>> + Send + 'static,
+    {
+        Self {
+            provider: Some(Arc::new(move || Box::pin(provider()) as TokenFuture)),
+            ..Default::default()
+        }
+    }
+
+    pub fn header<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /*
+    Sends the token as ?token= for servers and proxies that drop the header.
+    Errors then leave out the url, but the token still shows in server logs.
+    */
+    pub fn token_in_query(mut self) -> Self {
+        self.token_in_query = true;
+        self
+    }
+}
+
+// tokens are secrets and are kept out of debug output
+impl fmt::Debug for Authentication {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Authentication")
+            .field("token", &self.token.as_ref().map(|_| "***"))
+            .field(
+                "headers",
+                &self
+                    .headers
+                    .iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>(),
+            )
+            .field("provider", &self.provider.is_some())
+            .field("token_in_query", &self.token_in_query)
+            .finish()
+    }
+}
+
+/*
+Holds the token currently in use. Concurrent requests rejected with the same
+token only refresh it once, the others retry with the token the first one
+obtained.
+*/
+#[derive(Debug)]
+pub(crate) struct Authenticator {
+    authentication: Authentication,
+    current: Mutex<Option<String>>,
+}
+
+impl Authenticator {
+    pub(crate) fn new(authentication: Authentication) -> Self {
+        Self {
+            current: Mutex::new(authentication.token.clone()),
+            authentication,
+        }
+    }
+
+    pub(crate) fn headers(&self) -> &[(String, String)] {
+        &self.authentication.headers
+    }
+
+    pub(crate) fn token_in_query(&self) -> bool {
+        self.authentication.token_in_query
+    }
+
+    pub(crate) fn can_refresh(&self) -> bool {
+        self.authentication.provider.is_some()
+    }
+
+    pub(crate) async fn token(&self) -> Result<Option<String>> {
+        let mut current = self.current.lock().await;
+        if let (None, Some(provider)) = (current.as_ref(), &self.authentication.provider) {
+            *current = Some(provider().await?);
+        }
+        Ok(current.clone())
+    }
+
+    pub(crate) async fn refresh(&self, rejected: Option<&str>) -> Result<()> {
+        let Some(provider) = &self.authentication.provider else {
+            return Ok(());
+        };
+        let mut current = self.current.lock().await;
+        if current.as_deref() == rejected {
+            *current = Some(provider().await?);
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use crate::cmn;
 use serde::Deserialize;
 
-#[derive(Default, Debug, Deserialize)]
+#[derive(Default, Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SubLayer {
     pub id: usize,
@@ -44,6 +44,12 @@ pub struct AttributeStats {
 #[derive(Debug, Deserialize)]
 pub struct LayerType(String);
 
+impl From<LayerType> for String {
+    fn from(layer_type: LayerType) -> Self {
+        layer_type.0
+    }
+}
+
 impl Default for LayerType {
     fn default() -> Self {
         Self("Building".to_string())
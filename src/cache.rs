@@ -0,0 +1,138 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+
+/*
+Responses are stored per layer as {md5 of url}.bin next to a .json sidecar
+with the url and the validators of the response. Each layer has its own
+directory, so a layer is invalidated by removing it.
+*/
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    root: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Sidecar {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CachedResponse {
+    pub data: Vec<u8>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+const LAST_UPDATE_FILE: &str = "last_update";
+
+fn key(url: &str) -> String {
+    Md5::digest(url.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/*
+Files are written under a name unique to the writer and then renamed into
+place, so readers and concurrent writers, also in other processes, never see
+a partially written file.
+*/
+fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    static WRITES: AtomicUsize = AtomicUsize::new(0);
+    let temporary = path.with_extension(format!(
+        "{}.{}.tmp",
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = fs::write(&temporary, data).and_then(|_| fs::rename(&temporary, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    Ok(result?)
+}
+
+impl DiskCache {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn layer_directory(&self, layer_url: &str) -> PathBuf {
+        self.root.join(key(layer_url))
+    }
+
+    pub fn read(&self, layer_url: &str, url: &str) -> Option<CachedResponse> {
+        let path = self.layer_directory(layer_url).join(key(url));
+        let sidecar = fs::read(path.with_extension("json")).ok()?;
+        let sidecar = serde_json::from_slice::<Sidecar>(&sidecar).ok()?;
+        // guards against md5 collisions and half written entries
+        if sidecar.url != url {
+            return None;
+        }
+        Some(CachedResponse {
+            data: fs::read(path.with_extension("bin")).ok()?,
+            etag: sidecar.etag,
+            last_modified: sidecar.last_modified,
+        })
+    }
+
+    pub fn write(&self, layer_url: &str, url: &str, response: &CachedResponse) -> Result<()> {
+        let directory = self.layer_directory(layer_url);
+        fs::create_dir_all(&directory)?;
+        let path = directory.join(key(url));
+        let sidecar = Sidecar {
+            url: url.to_string(),
+            etag: response.etag.clone(),
+            last_modified: response.last_modified.clone(),
+        };
+        /*
+        The old sidecar is removed first and the new one renamed in last, so an
+        entry is only visible once complete and never pairs the validators of
+        one response with the data of another.
+        */
+        match fs::remove_file(path.with_extension("json")) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+        write_atomic(&path.with_extension("bin"), &response.data)?;
+        write_atomic(
+            &path.with_extension("json"),
+            &serde_json::to_vec(&sidecar).unwrap_or_default(),
+        )
+    }
+
+    pub fn last_update(&self, layer_url: &str) -> Option<u64> {
+        let path = self.layer_directory(layer_url).join(LAST_UPDATE_FILE);
+        fs::read_to_string(path).ok()?.trim().parse().ok()
+    }
+
+    pub fn set_last_update(&self, layer_url: &str, last_update: u64) -> Result<()> {
+        let directory = self.layer_directory(layer_url);
+        fs::create_dir_all(&directory)?;
+        write_atomic(
+            &directory.join(LAST_UPDATE_FILE),
+            last_update.to_string().as_bytes(),
+        )
+    }
+
+    pub fn invalidate(&self, layer_url: &str) -> Result<()> {
+        match fs::remove_dir_all(self.layer_directory(layer_url)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
@@ -1,6 +1,7 @@
-use std::error::Error;
+use std::collections::HashMap;
 
-use crate::{io, Service};
+use crate::error;
+use crate::{io, I3SFormat, Service};
 
 use serde::Deserialize;
 use serde_json;
@@ -16,6 +17,7 @@ pub struct SceneLayerInformation {
     pub layer_type: String,
     pub capabilities: Vec<String>,
     pub store: Store,
+    #[serde(default)]
     pub geometry_definitions: Vec<GeometryDefinition>,
     #[serde(default)]
     pub node_pages: NodePageDefinition,
@@ -43,15 +45,34 @@ pub struct SceneLayerInformation {
 }
 
 impl SceneLayerInformation {
-    fn rest_path() -> String {
-        format!("layers/{}", 0)
+    pub async fn from_rest(stream: &Service) -> error::Result<Self> {
+        stream.get_json::<Self>("").await
     }
+}
 
-    pub async fn from_rest(stream: &Service) -> Result<Self, Box<dyn Error>> {
-        let bytes = stream.get(Self::rest_path().as_str()).await?;
-        let result = serde_json::from_slice::<Self>(&bytes)?;
-        Ok(result)
-    }
+/*
+The SceneServer root document. Its layers are full layer documents, but only
+what is needed to find a layer is parsed here, as layers of different types
+can be listed side by side.
+*/
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SceneServiceInformation {
+    pub service_name: Option<String>,
+    pub service_version: Option<String>,
+    #[serde(default)]
+    pub layers: Vec<ServiceLayer>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceLayer {
+    pub id: usize,
+    #[serde(default)]
+    pub name: String,
+    pub alias: Option<String>,
+    pub layer_type: Option<String>,
+    pub href: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, Deserialize)]
@@ -382,6 +403,29 @@ impl Default for GeometryColor {
     }
 }
 
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeometryUVRegion {
+    #[serde(rename = "type")]
+    pub dtype: String,
+    pub component: i32,
+}
+
+impl GeometryUVRegion {
+    pub fn new() -> Self {
+        Self {
+            dtype: "UInt16".to_string(),
+            component: 4,
+        }
+    }
+}
+
+impl Default for GeometryUVRegion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GeometryFeatureID {
@@ -445,18 +489,14 @@ impl Default for GeometryFaceRange {
 pub struct GeometryBuffer {
     #[serde(default)]
     pub offset: i32,
-    #[serde(default)]
-    pub position: GeometryPosition,
-    #[serde(default)]
-    pub normal: GeometryNormal,
-    #[serde(default)]
-    pub uv0: GeometryUV,
-    #[serde(default)]
-    pub feature_id: GeometryFeatureID,
-    #[serde(default)]
-    pub face_range: GeometryFaceRange,
-    #[serde(default)]
-    pub compressed_attributes: CompressedAttributes,
+    pub position: Option<GeometryPosition>,
+    pub normal: Option<GeometryNormal>,
+    pub uv0: Option<GeometryUV>,
+    pub color: Option<GeometryColor>,
+    pub uv_region: Option<GeometryUVRegion>,
+    pub feature_id: Option<GeometryFeatureID>,
+    pub face_range: Option<GeometryFaceRange>,
+    pub compressed_attributes: Option<CompressedAttributes>,
 }
 
 fn default_compressed_attributes_encoding() -> String {
@@ -645,6 +685,7 @@ pub struct MaterialDefinition {
 #[derive(Default, Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MaterialDefinitionInfo {
+    #[serde(default)]
     pub name: String,
     #[serde(rename = "type", default)]
     pub dtype: String,
@@ -656,6 +697,7 @@ pub struct MaterialDefinitionInfo {
 #[derive(Default, Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MaterialParams {
+    #[serde(default)]
     pub render_mode: String,
     #[serde(default)]
     pub vertex_colors: bool,
@@ -663,11 +705,11 @@ pub struct MaterialParams {
     pub vertex_regions: bool,
     #[serde(default)]
     pub use_vertex_color_alpha: bool,
-    pub transparency: Option<i32>,
-    pub reflectivity: Option<i32>,
-    pub shininess: Option<i32>,
-    pub ambient: Option<Vec<i32>>,
-    pub diffuse: Option<Vec<i32>>,
+    pub transparency: Option<f32>,
+    pub reflectivity: Option<f32>,
+    pub shininess: Option<f32>,
+    pub ambient: Option<Vec<f32>>,
+    pub diffuse: Option<Vec<f32>>,
     pub specular: Option<Vec<f32>>,
     pub cast_shadows: Option<bool>,
     pub receive_shadows: Option<bool>,
@@ -700,8 +742,8 @@ pub struct DefaultGeometrySchema {
 #[derive(Default, Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FeatureAttribute {
-    pub id: Option<Value>,
-    pub face_range: Option<Value>,
+    pub id: Option<GeometryAttribute>,
+    pub face_range: Option<GeometryAttribute>,
 }
 
 #[derive(Default, Debug, Clone, Deserialize)]
@@ -783,10 +825,6 @@ pub struct Node {
     pub parent: Option<usize>,
     pub lod_threshold: Option<f32>,
     pub mesh: Option<Mesh>,
-    #[serde(skip)]
-    geometry: Vec<u8>,
-    #[serde(skip)]
-    texture: Vec<u8>,
 }
 
 impl Node {
@@ -797,22 +835,6 @@ impl Node {
     pub fn is_leaf(&self) -> bool {
         self.children.is_empty()
     }
-
-    pub fn geometry(&self) -> &Vec<u8> {
-        if self.geometry.is_empty() && !self.is_root() {
-            todo!()
-        } else {
-            &self.geometry
-        }
-    }
-
-    pub fn texture(&self) -> &Vec<u8> {
-        if self.texture.is_empty() && !self.is_root() {
-            todo!()
-        } else {
-            &self.texture
-        }
-    }
 }
 
 #[derive(Default, Debug, Clone, Deserialize)]
@@ -974,6 +996,14 @@ impl MeshAttribute {
         }
         Some(format!("nodes/{}/attributes", self.resource))
     }
+
+    pub fn attribute_resource_path(&self, key: &str, local: bool) -> Option<String> {
+        let mut path = format!("{}/{}/0", self.resource_path()?, key);
+        if local {
+            path.push_str(".bin.gz");
+        }
+        Some(path)
+    }
 }
 
 #[derive(Default, Debug, Clone, Deserialize)]
@@ -985,16 +1015,44 @@ pub struct Metadata {
     pub folder_pattern: Option<String>,
 }
 
+/*
+The feature document of a node before 1.7, e.g. nodes/0/features/0. Features
+reference their triangles in the node's geometry buffer, which is described
+by the entries of geometryData.
+*/
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeatureDocument {
+    #[serde(default)]
+    pub feature_data: Vec<FeatureData>,
+    #[serde(default)]
+    pub geometry_data: Vec<Geometry>,
+}
+
+impl FeatureDocument {
+    // hrefs such as /geometryData/1 name the id of the geometry
+    pub fn geometry(&self, href: &str) -> Option<&Geometry> {
+        let id = href.rsplit('/').next()?.parse::<usize>().ok()?;
+        self.geometry_data.iter().find(|geometry| geometry.id == id)
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FeatureData {
-    pub id: usize,
+    pub id: u64,
+    #[serde(default)]
     pub position: Vec<f64>,
+    #[serde(default)]
     pub pivot_offset: [f64; 3],
+    #[serde(default)]
     pub mbb: [f64; 6],
+    #[serde(default)]
     pub layer: String,
+    #[serde(default)]
     pub attributes: FeatureAttribute,
-    pub geometries: Geometry,
+    #[serde(default)]
+    pub geometries: Vec<Geometry>,
 }
 
 impl Default for FeatureData {
@@ -1006,17 +1064,24 @@ impl Default for FeatureData {
             mbb: [0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
             layer: String::new(),
             attributes: FeatureAttribute::default(),
-            geometries: Geometry::default(),
+            geometries: vec![],
         }
     }
 }
 
+fn identity_transformation() -> [f64; 16] {
+    [
+        1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+    ]
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Geometry {
     pub id: usize,
     #[serde(rename = "type")]
     pub geometry_type: String,
+    #[serde(default = "identity_transformation")]
     pub transformation: [f64; 16],
     pub params: GeometryParams,
 }
@@ -1026,14 +1091,15 @@ impl Default for Geometry {
         Self {
             id: 0,
             geometry_type: String::new(),
-            transformation: [0.0; 16],
+            transformation: identity_transformation(),
             params: GeometryParams::default(),
         }
     }
 }
 
+// the params have no tag, they are told apart by their required properties
 #[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(untagged)]
 pub enum GeometryParams {
     Reference(GeometryReferenceParams),
     Vested(VestedGeometryParams),
@@ -1059,10 +1125,13 @@ pub struct GeometryReferenceParams {
 #[derive(Default, Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VestedGeometryParams {
+    #[serde(rename = "type", default)]
     pub geometry_type: String,
     pub topology: String,
     pub vertex_attributes: VertexAttribute,
-    pub faces: GeometryAttribute,
+    pub faces: Option<GeometryAttribute>,
+    #[serde(default)]
+    pub components: Vec<SingleComponentParams>,
 }
 
 #[derive(Default, Debug, Clone, Deserialize)]
@@ -1073,8 +1142,11 @@ pub struct SingleComponentParams {
     pub material: String,
     #[serde(default)]
     pub texture: String,
+    #[serde(rename = "materialID")]
     pub material_id: Option<usize>,
+    #[serde(rename = "textureID")]
     pub texture_id: Option<[usize; 1]>,
+    #[serde(rename = "regionID")]
     pub region_id: Option<[usize; 1]>,
 }
 
@@ -1118,10 +1190,175 @@ pub struct ValueCount {
     pub count: usize,
 }
 
+/*
+Layers before 1.7 have no node pages. Every node has an index document at
+nodes/{id} instead, which references its parent, children and neighbors and
+lists its resources. All hrefs are relative to the node.
+*/
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeIndexDocument {
+    pub id: String,
+    #[serde(default)]
+    pub level: u32,
+    pub version: Option<String>,
+    pub mbs: Option<[f64; 4]>,
+    pub obb: Option<OBB>,
+    pub created: Option<String>,
+    pub expires: Option<String>,
+    pub transform: Option<Vec<f64>>,
+    pub parent_node: Option<NodeReference>,
+    #[serde(default)]
+    pub children: Vec<NodeReference>,
+    #[serde(default)]
+    pub neighbors: Vec<NodeReference>,
+    pub shared_resource: Option<Resource>,
+    #[serde(default)]
+    pub feature_data: Vec<Resource>,
+    #[serde(default)]
+    pub geometry_data: Vec<Resource>,
+    #[serde(default)]
+    pub texture_data: Vec<Resource>,
+    #[serde(default)]
+    pub attribute_data: Vec<Resource>,
+    #[serde(default)]
+    pub lod_selection: Vec<LodSelection>,
+}
+
+impl NodeIndexDocument {
+    pub fn is_root(&self) -> bool {
+        self.parent_node.is_none()
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    pub fn path(&self) -> String {
+        node_index_path(&self.id)
+    }
+
+    // the layer relative path of a resource or node the document references
+    pub fn resolve(&self, href: &str) -> String {
+        resolve_href(&self.path(), href)
+    }
+
+    pub fn reference_path(&self, reference: &NodeReference) -> String {
+        match &reference.href {
+            Some(href) => self.resolve(href),
+            None => node_index_path(&reference.id),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeReference {
+    pub id: String,
+    pub href: Option<String>,
+    pub version: Option<String>,
+    pub mbs: Option<[f64; 4]>,
+    pub obb: Option<OBB>,
+    pub feature_count: Option<usize>,
+}
+
 #[derive(Default, Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct NodeIndexDocument {}
+pub struct Resource {
+    pub href: String,
+    pub layer_content: Option<Vec<String>>,
+    pub feature_range: Option<[usize; 2]>,
+    pub multi_texture_bundle: Option<bool>,
+    pub vertex_elements: Option<[usize; 2]>,
+    pub face_elements: Option<[usize; 2]>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LodSelection {
+    pub metric_type: String,
+    pub max_error: Option<f64>,
+    pub avg_error: Option<f64>,
+}
+
+pub fn node_index_path(id: &str) -> String {
+    format!("nodes/{}", id)
+}
+
+/*
+Joins an href such as ../1 or ./geometries/0 onto a layer relative path,
+e.g. nodes/0, and removes the dot segments.
+*/
+pub fn resolve_href(base: &str, href: &str) -> String {
+    let mut segments: Vec<&str> = base
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    for segment in href.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/*
+Materials and textures of a node before 1.7, e.g. nodes/0/shared. Both are
+keyed by the ids that geometry components reference, such as
+/materialDefinitions/Mat0 or /textureDefinitions/0.
+*/
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedResource {
+    #[serde(default)]
+    pub material_definitions: HashMap<String, MaterialDefinitionInfo>,
+    #[serde(default)]
+    pub texture_definitions: HashMap<String, TextureDefinition>,
+}
+
+fn reference_key(reference: &str) -> &str {
+    reference.rsplit('/').next().unwrap_or(reference)
+}
+
+impl SharedResource {
+    pub fn material(&self, reference: &str) -> Option<&MaterialDefinitionInfo> {
+        self.material_definitions.get(reference_key(reference))
+    }
+
+    pub fn texture(&self, reference: &str) -> Option<&TextureDefinition> {
+        self.texture_definitions.get(reference_key(reference))
+    }
+}
+
+// the encodings of a texture line up with the hrefs of each of its images
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextureDefinition {
+    #[serde(default)]
+    pub encoding: Vec<String>,
+    pub wrap: Option<Vec<String>>,
+    #[serde(default)]
+    pub atlas: bool,
+    #[serde(default)]
+    pub uv_set: String,
+    pub channels: Option<String>,
+    #[serde(default)]
+    pub images: Vec<TextureImage>,
+}
 
 #[derive(Default, Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct SharedResource {}
+pub struct TextureImage {
+    #[serde(default)]
+    pub id: String,
+    pub size: Option<u32>,
+    pub pixel_in_world_units: Option<f64>,
+    #[serde(default)]
+    pub href: Vec<String>,
+    pub byte_offset: Option<Vec<u64>>,
+    pub length: Option<Vec<u64>>,
+}
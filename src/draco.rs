@@ -0,0 +1,2222 @@
+use std::collections::HashMap;
+
+use crate::error::{I3sError, Result};
+use crate::geometry::MeshData;
+use crate::io::BufferReader;
+
+/*
+Decoder for the Draco meshes stored in geometries/1, bitstream 2.2. Faces are
+either stored sequentially as (optionally entropy coded) index deltas or with
+edgebreaker, in which case attribute values follow a traversal of the mesh and
+can be predicted from their neighbours. Encodings this decoder does not know,
+e.g. the texture coordinate or geometric normal predictions, are reported as
+unsupported rather than decoded incorrectly.
+*/
+
+const TRIANGULAR_MESH: u8 = 1;
+const SEQUENTIAL_ENCODING: u8 = 0;
+const EDGEBREAKER_ENCODING: u8 = 1;
+const METADATA_FLAG: u16 = 0x8000;
+
+const STANDARD_TRAVERSAL: u8 = 0;
+const PREDICTIVE_TRAVERSAL: u8 = 1;
+const VALENCE_TRAVERSAL: u8 = 2;
+
+const TOPOLOGY_C: u32 = 0;
+const TOPOLOGY_S: u32 = 1;
+const TOPOLOGY_L: u32 = 3;
+const TOPOLOGY_R: u32 = 5;
+const TOPOLOGY_E: u32 = 7;
+const VALENCE_SYMBOLS: [u32; 5] = [TOPOLOGY_C, TOPOLOGY_S, TOPOLOGY_L, TOPOLOGY_R, TOPOLOGY_E];
+const MIN_VALENCE: u32 = 2;
+const MAX_VALENCE: u32 = 7;
+
+const VERTEX_ATTRIBUTE: u8 = 0;
+const CORNER_ATTRIBUTE: u8 = 1;
+const DEPTH_FIRST_TRAVERSAL: u8 = 0;
+const PREDICTION_DEGREE_TRAVERSAL: u8 = 1;
+
+const POSITION: u8 = 0;
+const NORMAL: u8 = 1;
+const COLOR: u8 = 2;
+const TEX_COORD: u8 = 3;
+const GENERIC: u8 = 4;
+
+const PREDICTION_NONE: i8 = -2;
+const PREDICTION_DIFFERENCE: i8 = 0;
+const PREDICTION_PARALLELOGRAM: i8 = 1;
+const PREDICTION_CONSTRAINED_MULTI_PARALLELOGRAM: i8 = 4;
+const PREDICTION_GEOMETRIC_NORMAL: i8 = 6;
+const MAX_PARALLELOGRAMS: usize = 4;
+
+const TRANSFORM_NONE: i8 = -1;
+const TRANSFORM_DELTA: i8 = 0;
+const TRANSFORM_WRAP: i8 = 1;
+const TRANSFORM_NORMAL_OCTAHEDRON: i8 = 2;
+const TRANSFORM_NORMAL_OCTAHEDRON_CANONICALIZED: i8 = 3;
+
+const DECODER_GENERIC: u8 = 0;
+const DECODER_INTEGER: u8 = 1;
+const DECODER_QUANTIZATION: u8 = 2;
+const DECODER_NORMALS: u8 = 3;
+
+const I3S_ATTRIBUTE_TYPE: &str = "i3s-attribute-type";
+const I3S_FEATURE_IDS: &str = "i3s-feature-ids";
+
+fn malformed(reason: &str) -> I3sError {
+    I3sError::MalformedBuffer(format!("draco: {}", reason))
+}
+
+fn unsupported(reason: &str) -> I3sError {
+    I3sError::UnsupportedEncoding(format!("draco: {}", reason))
+}
+
+fn read_varint(reader: &mut BufferReader) -> Result<u64> {
+    let mut value: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = reader.read_u8()?;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(malformed("varint is too long"))
+}
+
+fn read_varint_u32(reader: &mut BufferReader) -> Result<u32> {
+    u32::try_from(read_varint(reader)?).map_err(|_| malformed("varint does not fit in 32 bits"))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Header {
+    major: u8,
+    minor: u8,
+    encoder_type: u8,
+    encoder_method: u8,
+    flags: u16,
+}
+
+fn read_header(reader: &mut BufferReader) -> Result<Header> {
+    if reader.read_bytes(5)? != b"DRACO" {
+        return Err(malformed("missing DRACO magic"));
+    }
+    Ok(Header {
+        major: reader.read_u8()?,
+        minor: reader.read_u8()?,
+        encoder_type: reader.read_u8()?,
+        encoder_method: reader.read_u8()?,
+        flags: reader.read_u16()?,
+    })
+}
+
+#[derive(Debug, Default)]
+struct Metadata {
+    entries: HashMap<String, Vec<u8>>,
+}
+
+impl Metadata {
+    fn string(&self, key: &str) -> Option<&str> {
+        self.entries
+            .get(key)
+            .and_then(|value| std::str::from_utf8(value).ok())
+            .map(|value| value.trim_end_matches('\0'))
+    }
+
+    fn int_array(&self, key: &str) -> Option<Vec<i32>> {
+        self.entries.get(key).map(|value| {
+            value
+                .chunks_exact(4)
+                .map(|bytes| i32::from_le_bytes(bytes.try_into().unwrap()))
+                .collect()
+        })
+    }
+}
+
+fn read_name(reader: &mut BufferReader) -> Result<String> {
+    let length = reader.read_u8()? as usize;
+    Ok(String::from_utf8_lossy(reader.read_bytes(length)?).into_owned())
+}
+
+fn read_metadata(reader: &mut BufferReader, metadata: &mut Metadata) -> Result<()> {
+    let entry_count = read_varint_u32(reader)?;
+    for _ in 0..entry_count {
+        let key = read_name(reader)?;
+        let size = read_varint_u32(reader)? as usize;
+        let value = reader.read_bytes(size)?.to_vec();
+        metadata.entries.insert(key, value);
+    }
+    // I3S does not use nested metadata, so sub metadata is parsed and dropped
+    let sub_metadata_count = read_varint_u32(reader)?;
+    for _ in 0..sub_metadata_count {
+        read_name(reader)?;
+        read_metadata(reader, &mut Metadata::default())?;
+    }
+    Ok(())
+}
+
+fn read_attribute_metadata(reader: &mut BufferReader) -> Result<HashMap<u32, Metadata>> {
+    let mut attribute_metadata = HashMap::new();
+    let count = read_varint_u32(reader)?;
+    for _ in 0..count {
+        let unique_id = read_varint_u32(reader)?;
+        let mut metadata = Metadata::default();
+        read_metadata(reader, &mut metadata)?;
+        attribute_metadata.insert(unique_id, metadata);
+    }
+    // file level metadata is not needed
+    read_metadata(reader, &mut Metadata::default())?;
+    Ok(attribute_metadata)
+}
+
+/*
+rANS decoder as used by Draco's symbol coding. The precision depends on the
+largest symbol bit length, and the state is read backwards from the end of
+the encoded block.
+*/
+struct RansDecoder<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+    state: u32,
+    precision: u32,
+    base: u32,
+    probabilities: Vec<(u32, u32)>,
+    lookup: Vec<u32>,
+}
+
+fn rans_precision_bits(symbol_bit_length: u32) -> u32 {
+    ((3 * symbol_bit_length) / 2).clamp(12, 20)
+}
+
+impl<'a> RansDecoder<'a> {
+    fn create(reader: &mut BufferReader<'a>, symbol_bit_length: u32) -> Result<Self> {
+        let precision = 1u32 << rans_precision_bits(symbol_bit_length);
+        let symbol_count = read_varint_u32(reader)? as usize;
+        if symbol_count / 64 > reader.remaining() {
+            return Err(malformed("too many rANS symbols"));
+        }
+        // the table grows with the bytes actually read instead of the declared count
+        let mut table = Vec::new();
+        while table.len() < symbol_count {
+            let data = reader.read_u8()?;
+            let token = data & 3;
+            if token == 3 {
+                // run of symbols with zero probability
+                let run = (data >> 2) as usize;
+                if table.len() + run >= symbol_count {
+                    return Err(malformed("rANS zero run is out of range"));
+                }
+                table.resize(table.len() + run + 1, 0);
+                continue;
+            }
+            let mut probability = (data >> 2) as u32;
+            for b in 0..token as u32 {
+                probability |= (reader.read_u8()? as u32) << (8 * (b + 1) - 2);
+            }
+            table.push(probability);
+        }
+
+        let mut probabilities = Vec::with_capacity(symbol_count);
+        let mut lookup = vec![0u32; precision as usize];
+        let mut cumulative = 0u32;
+        for (symbol, probability) in table.iter().enumerate() {
+            let start = cumulative;
+            cumulative += probability;
+            if cumulative > precision {
+                return Err(malformed("rANS probabilities exceed precision"));
+            }
+            probabilities.push((*probability, start));
+            lookup[start as usize..cumulative as usize].fill(symbol as u32);
+        }
+        if symbol_count > 0 && cumulative != precision {
+            return Err(malformed("rANS probabilities do not sum to precision"));
+        }
+
+        Ok(Self {
+            buffer: &[],
+            offset: 0,
+            state: 0,
+            precision,
+            base: precision * 4,
+            probabilities,
+            lookup,
+        })
+    }
+
+    fn symbol_count(&self) -> usize {
+        self.probabilities.len()
+    }
+
+    fn start(&mut self, reader: &mut BufferReader<'a>) -> Result<()> {
+        let size = read_varint(reader)? as usize;
+        if size > reader.remaining() {
+            return Err(malformed("rANS block exceeds buffer"));
+        }
+        let buffer = reader.read_bytes(size)?;
+        let Some(last) = buffer.last() else {
+            return Err(malformed("empty rANS block"));
+        };
+        let (length, mask) = match last >> 6 {
+            0 => (1, 0x3f),
+            1 => (2, 0x3fff),
+            2 => (3, 0x3f_ffff),
+            _ => (4, 0x3fff_ffff),
+        };
+        if size < length {
+            return Err(malformed("rANS block is too short"));
+        }
+        let offset = size - length;
+        let mut state = 0u32;
+        for (i, byte) in buffer[offset..].iter().enumerate() {
+            state |= (*byte as u32) << (8 * i);
+        }
+        self.state = (state & mask) + self.base;
+        if self.state as u64 >= self.base as u64 * 256 {
+            return Err(malformed("invalid rANS state"));
+        }
+        self.buffer = buffer;
+        self.offset = offset;
+        Ok(())
+    }
+
+    /*
+    The encoder never lets the state drop below the base, so running out of
+    bytes before it is back above the base means the declared symbol count
+    exceeds what the block holds.
+    */
+    fn read(&mut self) -> Result<u32> {
+        while self.state < self.base && self.offset > 0 {
+            self.offset -= 1;
+            self.state = self.state * 256 + self.buffer[self.offset] as u32;
+        }
+        if self.state < self.base {
+            return Err(malformed("rANS block is exhausted"));
+        }
+        let quotient = self.state / self.precision;
+        let remainder = self.state % self.precision;
+        let symbol = self.lookup[remainder as usize];
+        let (probability, cumulative) = self.probabilities[symbol as usize];
+        self.state = quotient * probability + remainder - cumulative;
+        Ok(symbol)
+    }
+}
+
+struct BitReader<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn read_bits(&mut self, count: u32) -> u32 {
+        let mut value = 0;
+        for bit in 0..count {
+            let byte = self.buffer.get(self.offset >> 3).copied().unwrap_or(0);
+            value |= (((byte >> (self.offset & 7)) & 1) as u32) << bit;
+            self.offset += 1;
+        }
+        value
+    }
+
+    fn bytes_read(&self) -> usize {
+        self.offset.div_ceil(8)
+    }
+}
+
+const RABS_BASE: u32 = 4096;
+
+/*
+Binary rANS decoder for flags, e.g. the seams and start faces of the
+edgebreaker traversal. Every flag is coded with the same 8 bit probability of
+being zero, and like the symbol rANS the state is read from the end.
+*/
+struct RabsDecoder<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+    state: u32,
+    probability_zero: u32,
+}
+
+impl<'a> RabsDecoder<'a> {
+    fn start(reader: &mut BufferReader<'a>) -> Result<Self> {
+        let probability_zero = reader.read_u8()? as u32;
+        let size = read_varint_u32(reader)? as usize;
+        if size > reader.remaining() {
+            return Err(malformed("rABS block exceeds buffer"));
+        }
+        let buffer = reader.read_bytes(size)?;
+        let Some(last) = buffer.last() else {
+            return Err(malformed("empty rABS block"));
+        };
+        let length = match last >> 6 {
+            0 => 1,
+            1 => 2,
+            2 => 3,
+            _ => return Err(malformed("invalid rABS state")),
+        };
+        if size < length {
+            return Err(malformed("rABS block is too short"));
+        }
+        let offset = size - length;
+        let mut state = 0u32;
+        for (i, byte) in buffer[offset..].iter().enumerate() {
+            state |= (*byte as u32) << (8 * i);
+        }
+        let state = (state & ((1 << (8 * length - 2)) - 1)) + RABS_BASE;
+        if state >= RABS_BASE * 256 {
+            return Err(malformed("invalid rABS state"));
+        }
+        Ok(Self {
+            buffer,
+            offset,
+            state,
+            probability_zero,
+        })
+    }
+
+    fn read(&mut self) -> bool {
+        if self.state < RABS_BASE && self.offset > 0 {
+            self.offset -= 1;
+            self.state = self.state * 256 + self.buffer[self.offset] as u32;
+        }
+        let probability = 256 - self.probability_zero;
+        let quotient = self.state / 256;
+        let remainder = self.state % 256;
+        if remainder < probability {
+            self.state = quotient * probability + remainder;
+            true
+        } else {
+            self.state -= quotient * probability + probability;
+            false
+        }
+    }
+}
+
+fn decode_tagged_symbols(
+    reader: &mut BufferReader,
+    count: usize,
+    components: usize,
+    values: &mut Vec<u32>,
+) -> Result<()> {
+    let mut tags = RansDecoder::create(reader, 5)?;
+    tags.start(reader)?;
+    if tags.symbol_count() == 0 {
+        return Err(malformed("tagged symbols without tags"));
+    }
+    let mut bits = BitReader {
+        buffer: reader.remaining_bytes(),
+        offset: 0,
+    };
+    while values.len() < count {
+        let bit_length = tags.read()?;
+        if bit_length > 32 {
+            return Err(malformed("symbol bit length exceeds 32"));
+        }
+        for _ in 0..components {
+            values.push(bits.read_bits(bit_length));
+        }
+    }
+    values.truncate(count);
+    reader.skip(bits.bytes_read().min(reader.remaining()))
+}
+
+fn decode_raw_symbols(
+    reader: &mut BufferReader,
+    count: usize,
+    values: &mut Vec<u32>,
+) -> Result<()> {
+    let max_bit_length = reader.read_u8()? as u32;
+    if !(1..=18).contains(&max_bit_length) {
+        return Err(malformed("invalid raw symbol bit length"));
+    }
+    let mut decoder = RansDecoder::create(reader, max_bit_length)?;
+    if count > 0 && decoder.symbol_count() == 0 {
+        return Err(malformed("raw symbols without alphabet"));
+    }
+    decoder.start(reader)?;
+    for _ in 0..count {
+        values.push(decoder.read()?);
+    }
+    Ok(())
+}
+
+fn decode_symbols(reader: &mut BufferReader, count: usize, components: usize) -> Result<Vec<u32>> {
+    let mut values = Vec::with_capacity(count.min(reader.remaining()));
+    if count == 0 {
+        return Ok(values);
+    }
+    match reader.read_u8()? {
+        0 => decode_tagged_symbols(reader, count, components.max(1), &mut values)?,
+        1 => decode_raw_symbols(reader, count, &mut values)?,
+        scheme => return Err(malformed(&format!("unknown symbol coding {}", scheme))),
+    }
+    Ok(values)
+}
+
+fn symbol_to_signed(value: u32) -> i32 {
+    if value & 1 == 0 {
+        (value >> 1) as i32
+    } else {
+        -((value >> 1) as i32) - 1
+    }
+}
+
+#[derive(Debug)]
+enum Values {
+    Float(Vec<f32>),
+    Integer(Vec<i64>),
+}
+
+#[derive(Debug)]
+struct Attribute {
+    attribute_type: u8,
+    data_type: u8,
+    components: usize,
+    unique_id: u32,
+    decoder: u8,
+    portable: Vec<i32>,
+    values: Values,
+}
+
+fn data_type_size(data_type: u8) -> Result<usize> {
+    match data_type {
+        1 | 2 | 11 => Ok(1),
+        3 | 4 => Ok(2),
+        5 | 6 | 9 => Ok(4),
+        7 | 8 | 10 => Ok(8),
+        _ => Err(malformed(&format!("invalid data type {}", data_type))),
+    }
+}
+
+fn is_float(data_type: u8) -> bool {
+    data_type == 9 || data_type == 10
+}
+
+/*
+Counts are derived from the point and face counts in the buffer, so they are
+checked against the bytes that are left before anything is allocated.
+*/
+fn check_length(reader: &BufferReader, count: usize, size: usize) -> Result<()> {
+    match count.checked_mul(size) {
+        Some(length) if length <= reader.remaining() => Ok(()),
+        _ => Err(malformed(&format!(
+            "{} values of {} bytes exceed the {} bytes left",
+            count,
+            size,
+            reader.remaining()
+        ))),
+    }
+}
+
+fn read_raw_values(reader: &mut BufferReader, data_type: u8, count: usize) -> Result<Values> {
+    check_length(reader, count, data_type_size(data_type)?)?;
+    if is_float(data_type) {
+        let mut values = Vec::with_capacity(count);
+        for _ in 0..count {
+            values.push(match data_type {
+                9 => reader.read_f32()?,
+                _ => reader.read_f64()? as f32,
+            });
+        }
+        return Ok(Values::Float(values));
+    }
+    let mut values = Vec::with_capacity(count);
+    for _ in 0..count {
+        values.push(match data_type {
+            1 => reader.read_i8()? as i64,
+            2 | 11 => reader.read_u8()? as i64,
+            3 => reader.read_i16()? as i64,
+            4 => reader.read_u16()? as i64,
+            5 => reader.read_i32()? as i64,
+            6 => reader.read_u32()? as i64,
+            _ => reader.read_i64()?,
+        });
+    }
+    Ok(Values::Integer(values))
+}
+
+/*
+Transforms applied on top of the predicted values. Wrap keeps corrections
+within the range of the original values, the octahedron transforms wrap
+corrections around the octahedral normal map.
+*/
+enum Transform {
+    Delta,
+    Wrap {
+        min: i32,
+        max: i32,
+    },
+    Octahedron {
+        max_quantized: i32,
+        canonicalized: bool,
+    },
+}
+
+fn read_transform(reader: &mut BufferReader, transform_type: i8) -> Result<Transform> {
+    match transform_type {
+        TRANSFORM_NONE | TRANSFORM_DELTA => Ok(Transform::Delta),
+        TRANSFORM_WRAP => {
+            let min = reader.read_i32()?;
+            let max = reader.read_i32()?;
+            if min > max || (max as i64 - min as i64) >= i32::MAX as i64 {
+                return Err(malformed("invalid wrap transform bounds"));
+            }
+            Ok(Transform::Wrap { min, max })
+        }
+        TRANSFORM_NORMAL_OCTAHEDRON | TRANSFORM_NORMAL_OCTAHEDRON_CANONICALIZED => {
+            let max_quantized = reader.read_i32()?;
+            let canonicalized = transform_type == TRANSFORM_NORMAL_OCTAHEDRON_CANONICALIZED;
+            if canonicalized {
+                // center value, derived from the quantization instead
+                reader.read_i32()?;
+            }
+            if max_quantized % 2 == 0 || !(3..=i32::MAX / 2).contains(&max_quantized) {
+                return Err(malformed("invalid octahedron transform"));
+            }
+            Ok(Transform::Octahedron {
+                max_quantized,
+                canonicalized,
+            })
+        }
+        transform => Err(unsupported(&format!("prediction transform {}", transform))),
+    }
+}
+
+struct Octahedron {
+    max_quantized: i32,
+    center: i32,
+}
+
+impl Octahedron {
+    fn from_bits(bits: u32) -> Self {
+        let max_quantized = (1i32 << bits) - 1;
+        Self {
+            max_quantized,
+            center: (max_quantized - 1) / 2,
+        }
+    }
+
+    fn is_in_diamond(&self, s: i32, t: i32) -> bool {
+        s.abs() + t.abs() <= self.center
+    }
+
+    fn invert_diamond(&self, s: &mut i32, t: &mut i32) {
+        let (sign_s, sign_t) = if *s >= 0 && *t >= 0 {
+            (1, 1)
+        } else if *s <= 0 && *t <= 0 {
+            (-1, -1)
+        } else {
+            (if *s > 0 { 1 } else { -1 }, if *t > 0 { 1 } else { -1 })
+        };
+        let corner_s = sign_s * self.center;
+        let corner_t = sign_t * self.center;
+        let mut ss = 2 * *s - corner_s;
+        let mut tt = 2 * *t - corner_t;
+        if sign_s * sign_t >= 0 {
+            (ss, tt) = (-tt, -ss);
+        } else {
+            std::mem::swap(&mut ss, &mut tt);
+        }
+        *s = (ss + corner_s) / 2;
+        *t = (tt + corner_t) / 2;
+    }
+
+    fn mod_max(&self, value: i32) -> i32 {
+        if value > self.center {
+            value - self.max_quantized
+        } else if value < -self.center {
+            value + self.max_quantized
+        } else {
+            value
+        }
+    }
+
+    fn rotation_count(s: i32, t: i32) -> u32 {
+        match (s.signum(), t) {
+            (0, 0) => 0,
+            (0, t) if t > 0 => 3,
+            (0, _) => 1,
+            (1, t) if t >= 0 => 2,
+            (1, _) => 1,
+            (_, t) if t <= 0 => 0,
+            _ => 3,
+        }
+    }
+
+    fn rotate(s: i32, t: i32, count: u32) -> (i32, i32) {
+        match count {
+            1 => (t, -s),
+            2 => (-s, -t),
+            3 => (-t, s),
+            _ => (s, t),
+        }
+    }
+
+    fn original(&self, predicted: [i32; 2], correction: [i32; 2], canonicalized: bool) -> [i32; 2] {
+        let (mut s, mut t) = (predicted[0] - self.center, predicted[1] - self.center);
+        let in_diamond = self.is_in_diamond(s, t);
+        if !in_diamond {
+            self.invert_diamond(&mut s, &mut t);
+        }
+        let in_bottom_left = (s == 0 && t == 0) || (s < 0 && t <= 0);
+        let rotation = Self::rotation_count(s, t);
+        if canonicalized && !in_bottom_left {
+            (s, t) = Self::rotate(s, t, rotation);
+        }
+        s = self.mod_max(s + correction[0]);
+        t = self.mod_max(t + correction[1]);
+        if canonicalized && !in_bottom_left {
+            (s, t) = Self::rotate(s, t, (4 - rotation) % 4);
+        }
+        if !in_diamond {
+            self.invert_diamond(&mut s, &mut t);
+        }
+        [s + self.center, t + self.center]
+    }
+
+    fn unit_vector(&self, s: i32, t: i32) -> [f32; 3] {
+        let scale = 2.0 / (self.max_quantized - 1) as f32;
+        let mut y = s as f32 * scale - 1.0;
+        let mut z = t as f32 * scale - 1.0;
+        let x = 1.0 - y.abs() - z.abs();
+        let offset = (-x).max(0.0);
+        y += if y < 0.0 { offset } else { -offset };
+        z += if z < 0.0 { offset } else { -offset };
+        let norm = x * x + y * y + z * z;
+        if norm < 1e-6 {
+            return [0.0, 0.0, 0.0];
+        }
+        let d = 1.0 / norm.sqrt();
+        [x * d, y * d, z * d]
+    }
+}
+
+impl Transform {
+    fn corrections_positive(&self) -> bool {
+        matches!(self, Transform::Octahedron { .. })
+    }
+
+    fn apply(&self, predicted: &[i32], correction: &[i32], output: &mut [i32]) {
+        match self {
+            Transform::Delta => {
+                for i in 0..output.len() {
+                    output[i] = predicted[i].wrapping_add(correction[i]);
+                }
+            }
+            Transform::Wrap { min, max } => {
+                let range = max - min + 1;
+                for i in 0..output.len() {
+                    let mut value = predicted[i].clamp(*min, *max).wrapping_add(correction[i]);
+                    if value > *max {
+                        value = value.wrapping_sub(range);
+                    } else if value < *min {
+                        value = value.wrapping_add(range);
+                    }
+                    output[i] = value;
+                }
+            }
+            Transform::Octahedron {
+                max_quantized,
+                canonicalized,
+            } => {
+                let octahedron = Octahedron {
+                    max_quantized: *max_quantized,
+                    center: (max_quantized - 1) / 2,
+                };
+                let value = octahedron.original(
+                    [predicted[0], predicted[1]],
+                    [correction[0], correction[1]],
+                    *canonicalized,
+                );
+                output[..2].copy_from_slice(&value);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Prediction<'a> {
+    Difference,
+    Parallelogram(&'a MeshPrediction<'a>),
+    ConstrainedMultiParallelogram(&'a MeshPrediction<'a>),
+}
+
+fn decode_portable_values(
+    reader: &mut BufferReader,
+    attribute: &Attribute,
+    entry_count: usize,
+    mesh: Option<&MeshPrediction>,
+) -> Result<Vec<i32>> {
+    let components = if attribute.decoder == DECODER_NORMALS {
+        2
+    } else {
+        attribute.components
+    };
+    let prediction = match (reader.read_i8()?, mesh) {
+        (PREDICTION_NONE, _) => None,
+        (PREDICTION_DIFFERENCE, _) => Some(Prediction::Difference),
+        // without connectivity Draco falls back to the difference prediction
+        (PREDICTION_PARALLELOGRAM..=PREDICTION_GEOMETRIC_NORMAL, None) => {
+            Some(Prediction::Difference)
+        }
+        (PREDICTION_PARALLELOGRAM, Some(mesh)) => Some(Prediction::Parallelogram(mesh)),
+        (PREDICTION_CONSTRAINED_MULTI_PARALLELOGRAM, Some(mesh)) => {
+            Some(Prediction::ConstrainedMultiParallelogram(mesh))
+        }
+        (method @ PREDICTION_PARALLELOGRAM..=PREDICTION_GEOMETRIC_NORMAL, Some(_)) => {
+            return Err(unsupported(&format!("prediction scheme {}", method)))
+        }
+        (method, _) => return Err(malformed(&format!("invalid prediction scheme {}", method))),
+    };
+    let transform_type = match prediction {
+        Some(_) => Some(reader.read_i8()?),
+        None => None,
+    };
+    let count = entry_count * components;
+    let mut values: Vec<i32> = if reader.read_u8()? > 0 {
+        decode_symbols(reader, count, components)?
+            .into_iter()
+            .map(|value| value as i32)
+            .collect()
+    } else {
+        let size = reader.read_u8()? as usize;
+        if !(1..=4).contains(&size) {
+            return Err(malformed("invalid integer value size"));
+        }
+        check_length(reader, count, size)?;
+        let mut values = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut bytes = [0u8; 4];
+            bytes[..size].copy_from_slice(reader.read_bytes(size)?);
+            values.push(i32::from_le_bytes(bytes));
+        }
+        values
+    };
+
+    let positive = matches!(
+        transform_type,
+        Some(TRANSFORM_NORMAL_OCTAHEDRON | TRANSFORM_NORMAL_OCTAHEDRON_CANONICALIZED)
+    );
+    if !positive {
+        for value in values.iter_mut() {
+            *value = symbol_to_signed(*value as u32);
+        }
+    }
+    if let (Some(prediction), Some(transform_type)) = (prediction, transform_type) {
+        let creases = match prediction {
+            Prediction::ConstrainedMultiParallelogram(mesh) => {
+                Some(decode_creases(reader, mesh.table.corner_count())?)
+            }
+            _ => None,
+        };
+        let transform = read_transform(reader, transform_type)?;
+        if transform.corrections_positive() && components != 2 {
+            return Err(malformed("octahedron transform on a non normal attribute"));
+        }
+        match prediction {
+            Prediction::Difference => {
+                // every entry is predicted by the previous one
+                let mut predicted = vec![0i32; components];
+                let mut decoded = vec![0i32; components];
+                for chunk in values.chunks_exact_mut(components) {
+                    transform.apply(&predicted, chunk, &mut decoded);
+                    chunk.copy_from_slice(&decoded);
+                    predicted.copy_from_slice(&decoded);
+                }
+            }
+            Prediction::Parallelogram(mesh) | Prediction::ConstrainedMultiParallelogram(mesh) => {
+                mesh.restore(&mut values, components, &transform, creases.as_ref())?
+            }
+        }
+    }
+    Ok(values)
+}
+
+/*
+Whether each parallelogram of the constrained multi parallelogram prediction
+crosses a crease and is left out, with one flag stream per number of
+parallelograms available at a vertex.
+*/
+fn decode_creases(
+    reader: &mut BufferReader,
+    corner_count: usize,
+) -> Result<[Vec<bool>; MAX_PARALLELOGRAMS]> {
+    let mut creases: [Vec<bool>; MAX_PARALLELOGRAMS] = Default::default();
+    for flags in creases.iter_mut() {
+        let count = read_varint_u32(reader)? as usize;
+        if count > corner_count {
+            return Err(malformed("more crease flags than corners"));
+        }
+        if count > 0 {
+            let mut decoder = RabsDecoder::start(reader)?;
+            *flags = (0..count).map(|_| decoder.read()).collect();
+        }
+    }
+    Ok(creases)
+}
+
+/*
+An attribute traversed over the edgebreaker connectivity. Its values are
+numbered in the order the traversal reached their vertices, and the
+parallelogram predictions complete the triangle across the edge opposite to
+the corner a value was reached at.
+*/
+struct MeshPrediction<'a> {
+    table: &'a CornerTable,
+    sequence: Sequence,
+}
+
+impl MeshPrediction<'_> {
+    fn entry(&self, corner: u32) -> usize {
+        let vertex = self.table.vertex(corner) as usize;
+        self.sequence
+            .vertex_entries
+            .get(vertex)
+            .map_or(usize::MAX, |entry| *entry as usize)
+    }
+
+    // the value index of every corner, face by face
+    fn faces(&self) -> Result<Vec<[u32; 3]>> {
+        self.table
+            .vertices
+            .chunks_exact(3)
+            .map(|corners| {
+                let mut face = [0u32; 3];
+                for (entry, vertex) in face.iter_mut().zip(corners) {
+                    *entry = self
+                        .sequence
+                        .vertex_entries
+                        .get(*vertex as usize)
+                        .copied()
+                        .filter(|entry| *entry != INVALID)
+                        .ok_or_else(|| malformed("corner without an attribute value"))?;
+                }
+                Ok(face)
+            })
+            .collect()
+    }
+
+    // only triangles whose values precede entry can be used
+    fn parallelogram(
+        &self,
+        entry: usize,
+        corner: u32,
+        values: &[i32],
+        components: usize,
+        prediction: &mut [i32],
+    ) -> bool {
+        let opposite = self.table.opposite(corner);
+        if opposite == INVALID {
+            return false;
+        }
+        let [tip, next, previous] = [
+            opposite,
+            self.table.next(opposite),
+            self.table.previous(opposite),
+        ]
+        .map(|corner| self.entry(corner));
+        if tip >= entry || next >= entry || previous >= entry {
+            return false;
+        }
+        for (i, value) in prediction.iter_mut().enumerate() {
+            *value = values[next * components + i]
+                .wrapping_add(values[previous * components + i])
+                .wrapping_sub(values[tip * components + i]);
+        }
+        true
+    }
+
+    /*
+    Collects up to MAX_PARALLELOGRAMS predictions around the vertex of start,
+    swinging left until a boundary and then right from start.
+    */
+    fn parallelograms(
+        &self,
+        entry: usize,
+        start: u32,
+        values: &[i32],
+        components: usize,
+        predictions: &mut [i32],
+    ) -> usize {
+        let mut count = 0;
+        let mut corner = start;
+        let mut left = true;
+        while corner != INVALID {
+            let prediction = &mut predictions[count * components..(count + 1) * components];
+            if self.parallelogram(entry, corner, values, components, prediction) {
+                count += 1;
+                if count == MAX_PARALLELOGRAMS {
+                    break;
+                }
+            }
+            corner = if left {
+                self.table.swing_left(corner)
+            } else {
+                self.table.swing_right(corner)
+            };
+            if corner == start {
+                break;
+            }
+            if corner == INVALID && left {
+                left = false;
+                corner = self.table.swing_right(start);
+            }
+        }
+        count
+    }
+
+    /*
+    Restores the values in traversal order. Without creases only the
+    parallelogram at the corner a value was reached at is used, with creases
+    the average of the parallelograms not flagged as crossing one. Values
+    without a usable parallelogram are predicted by the previous value.
+    */
+    fn restore(
+        &self,
+        values: &mut [i32],
+        components: usize,
+        transform: &Transform,
+        creases: Option<&[Vec<bool>; MAX_PARALLELOGRAMS]>,
+    ) -> Result<()> {
+        let mut predictions = vec![0i32; MAX_PARALLELOGRAMS * components];
+        let mut predicted = vec![0i32; components];
+        let mut decoded = vec![0i32; components];
+        let mut crease_positions = [0usize; MAX_PARALLELOGRAMS];
+        for (entry, corner) in self.sequence.entry_corners.iter().enumerate() {
+            let (restored, remaining) = values.split_at_mut(entry * components);
+            let correction = &mut remaining[..components];
+            let used = match creases {
+                None => usize::from(self.parallelogram(
+                    entry,
+                    *corner,
+                    restored,
+                    components,
+                    &mut predicted,
+                )),
+                Some(creases) => {
+                    let count =
+                        self.parallelograms(entry, *corner, restored, components, &mut predictions);
+                    let mut used = 0;
+                    predicted.fill(0);
+                    for prediction in predictions.chunks_exact(components).take(count) {
+                        let context = count - 1;
+                        let is_crease = *creases[context]
+                            .get(crease_positions[context])
+                            .ok_or_else(|| malformed("too few crease flags"))?;
+                        crease_positions[context] += 1;
+                        if !is_crease {
+                            used += 1;
+                            for (sum, value) in predicted.iter_mut().zip(prediction) {
+                                *sum = sum.wrapping_add(*value);
+                            }
+                        }
+                    }
+                    if used > 0 {
+                        for sum in predicted.iter_mut() {
+                            *sum /= used as i32;
+                        }
+                    }
+                    used
+                }
+            };
+            if used == 0 {
+                match entry.checked_sub(1) {
+                    Some(last) => predicted.copy_from_slice(&restored[last * components..]),
+                    None => predicted.fill(0),
+                }
+            }
+            transform.apply(&predicted, correction, &mut decoded);
+            correction.copy_from_slice(&decoded);
+        }
+        Ok(())
+    }
+}
+
+fn decode_transform(reader: &mut BufferReader, attribute: &mut Attribute) -> Result<()> {
+    match attribute.decoder {
+        DECODER_QUANTIZATION => {
+            let mut minimum = Vec::with_capacity(attribute.components);
+            for _ in 0..attribute.components {
+                minimum.push(reader.read_f32()?);
+            }
+            let range = reader.read_f32()?;
+            let bits = reader.read_u8()? as u32;
+            if !(1..=30).contains(&bits) {
+                return Err(malformed("invalid quantization bits"));
+            }
+            let delta = range / ((1u32 << bits) - 1) as f32;
+            let values = attribute
+                .portable
+                .chunks_exact(attribute.components)
+                .flat_map(|chunk| {
+                    chunk
+                        .iter()
+                        .zip(&minimum)
+                        .map(|(value, min)| *value as f32 * delta + min)
+                })
+                .collect();
+            attribute.values = Values::Float(values);
+        }
+        DECODER_NORMALS => {
+            let bits = reader.read_u8()? as u32;
+            if !(2..=30).contains(&bits) {
+                return Err(malformed("invalid normal quantization bits"));
+            }
+            let octahedron = Octahedron::from_bits(bits);
+            let values = attribute
+                .portable
+                .chunks_exact(2)
+                .flat_map(|chunk| octahedron.unit_vector(chunk[0], chunk[1]))
+                .collect();
+            attribute.values = Values::Float(values);
+        }
+        DECODER_INTEGER => {
+            let values = attribute
+                .portable
+                .iter()
+                .map(|value| match attribute.data_type {
+                    6 | 8 => *value as u32 as i64,
+                    _ => *value as i64,
+                })
+                .collect();
+            attribute.values = Values::Integer(values);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/*
+Sequential meshes index the values of every attribute by the decoded points.
+Edgebreaker meshes store the values of each attribute group in the order a
+traversal of its corner table reaches them, so the faces differ per group.
+*/
+enum Connectivity {
+    Sequential {
+        faces: Vec<[u32; 3]>,
+        point_count: usize,
+    },
+    Edgebreaker(Edgebreaker),
+}
+
+struct AttributeGroup {
+    attributes: Vec<Attribute>,
+    faces: Vec<[u32; 3]>,
+}
+
+fn decode_attributes(
+    reader: &mut BufferReader,
+    connectivity: &Connectivity,
+) -> Result<Vec<AttributeGroup>> {
+    let decoder_count = reader.read_u8()? as usize;
+    let mut sequencers = Vec::new();
+    if let Connectivity::Edgebreaker(edgebreaker) = connectivity {
+        for _ in 0..decoder_count {
+            sequencers.push(read_sequencer(reader, edgebreaker.seam_tables.len())?);
+        }
+    }
+    let mut groups: Vec<Vec<Attribute>> = Vec::with_capacity(decoder_count);
+    for _ in 0..decoder_count {
+        let count = read_varint_u32(reader)? as usize;
+        let mut group = Vec::with_capacity(count.min(reader.remaining()));
+        for _ in 0..count {
+            let attribute_type = reader.read_u8()?;
+            let data_type = reader.read_u8()?;
+            let components = reader.read_u8()? as usize;
+            let _normalized = reader.read_u8()?;
+            let unique_id = read_varint_u32(reader)?;
+            data_type_size(data_type)?;
+            if components == 0 {
+                return Err(malformed("attribute without components"));
+            }
+            group.push(Attribute {
+                attribute_type,
+                data_type,
+                components,
+                unique_id,
+                decoder: DECODER_GENERIC,
+                portable: vec![],
+                values: Values::Integer(vec![]),
+            });
+        }
+        for attribute in group.iter_mut() {
+            attribute.decoder = reader.read_u8()?;
+            if attribute.decoder > DECODER_NORMALS {
+                return Err(malformed(&format!(
+                    "unknown attribute decoder {}",
+                    attribute.decoder
+                )));
+            }
+        }
+        groups.push(group);
+    }
+
+    let mut decoded = Vec::with_capacity(groups.len());
+    for (index, mut attributes) in groups.into_iter().enumerate() {
+        let (faces, entry_count, mesh) = match connectivity {
+            Connectivity::Sequential { faces, point_count } => (faces.clone(), *point_count, None),
+            Connectivity::Edgebreaker(edgebreaker) => {
+                let mesh = edgebreaker.traverse(sequencers[index])?;
+                (mesh.faces()?, mesh.sequence.entry_corners.len(), Some(mesh))
+            }
+        };
+        for attribute in attributes.iter_mut() {
+            if attribute.decoder == DECODER_GENERIC {
+                attribute.values = read_raw_values(
+                    reader,
+                    attribute.data_type,
+                    entry_count * attribute.components,
+                )?;
+            } else {
+                attribute.portable =
+                    decode_portable_values(reader, attribute, entry_count, mesh.as_ref())?;
+            }
+        }
+        for attribute in attributes.iter_mut() {
+            decode_transform(reader, attribute)?;
+        }
+        decoded.push(AttributeGroup { attributes, faces });
+    }
+    Ok(decoded)
+}
+
+fn decode_sequential_connectivity(reader: &mut BufferReader) -> Result<(Vec<[u32; 3]>, usize)> {
+    let face_count = read_varint_u32(reader)? as usize;
+    let point_count = read_varint_u32(reader)? as usize;
+    let mut faces = Vec::with_capacity(face_count.min(reader.remaining()));
+    if reader.read_u8()? == 0 {
+        // indices are coded as signed differences to the previous index
+        let symbols = decode_symbols(reader, face_count * 3, 1)?;
+        let mut last = 0i64;
+        for face in symbols.chunks_exact(3) {
+            let mut indices = [0u32; 3];
+            for (index, symbol) in indices.iter_mut().zip(face) {
+                let difference = (symbol >> 1) as i64;
+                last += if symbol & 1 == 1 {
+                    -difference
+                } else {
+                    difference
+                };
+                *index = u32::try_from(last).map_err(|_| malformed("negative face index"))?;
+            }
+            faces.push(indices);
+        }
+    } else {
+        for _ in 0..face_count {
+            let mut indices = [0u32; 3];
+            for index in indices.iter_mut() {
+                *index = if point_count < 1 << 8 {
+                    reader.read_u8()? as u32
+                } else if point_count < 1 << 16 {
+                    reader.read_u16()? as u32
+                } else if point_count < 1 << 21 {
+                    read_varint_u32(reader)?
+                } else {
+                    reader.read_u32()?
+                };
+            }
+            faces.push(indices);
+        }
+    }
+    if faces
+        .iter()
+        .flatten()
+        .any(|index| *index as usize >= point_count)
+    {
+        return Err(malformed("face index out of range"));
+    }
+    Ok((faces, point_count))
+}
+
+const INVALID: u32 = u32::MAX;
+
+/*
+Corner table of a triangle mesh, corner 3 * f + i being the i-th corner of
+face f. The opposite of a corner is the corner across its opposite edge, and
+the left most corner of a vertex is where swinging left around it reaches a
+boundary. Lookups past the table return INVALID.
+*/
+#[derive(Debug, Clone)]
+struct CornerTable {
+    vertices: Vec<u32>,
+    opposites: Vec<u32>,
+    left_most: Vec<u32>,
+}
+
+impl CornerTable {
+    fn new(face_count: usize) -> Self {
+        Self {
+            vertices: vec![INVALID; 3 * face_count],
+            opposites: vec![INVALID; 3 * face_count],
+            left_most: vec![],
+        }
+    }
+
+    fn corner_count(&self) -> usize {
+        self.vertices.len()
+    }
+
+    fn face_count(&self) -> usize {
+        self.vertices.len() / 3
+    }
+
+    fn vertex_count(&self) -> usize {
+        self.left_most.len()
+    }
+
+    fn next(&self, corner: u32) -> u32 {
+        match corner {
+            INVALID => INVALID,
+            _ if corner % 3 == 2 => corner - 2,
+            _ => corner + 1,
+        }
+    }
+
+    fn previous(&self, corner: u32) -> u32 {
+        match corner {
+            INVALID => INVALID,
+            _ if corner.is_multiple_of(3) => corner + 2,
+            _ => corner - 1,
+        }
+    }
+
+    fn vertex(&self, corner: u32) -> u32 {
+        self.vertices
+            .get(corner as usize)
+            .copied()
+            .unwrap_or(INVALID)
+    }
+
+    fn opposite(&self, corner: u32) -> u32 {
+        self.opposites
+            .get(corner as usize)
+            .copied()
+            .unwrap_or(INVALID)
+    }
+
+    fn left_most(&self, vertex: u32) -> u32 {
+        self.left_most
+            .get(vertex as usize)
+            .copied()
+            .unwrap_or(INVALID)
+    }
+
+    fn swing_left(&self, corner: u32) -> u32 {
+        self.next(self.opposite(self.next(corner)))
+    }
+
+    fn swing_right(&self, corner: u32) -> u32 {
+        self.previous(self.opposite(self.previous(corner)))
+    }
+
+    fn left_corner(&self, corner: u32) -> u32 {
+        self.opposite(self.previous(corner))
+    }
+
+    fn right_corner(&self, corner: u32) -> u32 {
+        self.opposite(self.next(corner))
+    }
+
+    fn is_on_boundary(&self, vertex: u32) -> bool {
+        self.swing_left(self.left_most(vertex)) == INVALID
+    }
+
+    fn set_opposites(&mut self, a: u32, b: u32) {
+        self.opposites[a as usize] = b;
+        self.opposites[b as usize] = a;
+    }
+
+    fn map(&mut self, corner: u32, vertex: u32) {
+        self.vertices[corner as usize] = vertex;
+    }
+
+    fn add_vertex(&mut self) -> u32 {
+        self.left_most.push(INVALID);
+        (self.left_most.len() - 1) as u32
+    }
+
+    fn set_left_most(&mut self, vertex: u32, corner: u32) {
+        if let Some(left_most) = self.left_most.get_mut(vertex as usize) {
+            *left_most = corner;
+        }
+    }
+
+    /*
+    The table of an attribute whose values differ across the given seam
+    edges. Seams become boundaries, and every vertex is split into one vertex
+    per fan of corners between two seams.
+    */
+    fn with_seams(&self, seams: &[u32]) -> Result<CornerTable> {
+        let mut on_seam = vec![false; self.corner_count()];
+        let mut vertex_on_seam = vec![false; self.vertex_count()];
+        for seam in seams {
+            for corner in [*seam, self.opposite(*seam)] {
+                if corner == INVALID {
+                    continue;
+                }
+                on_seam[corner as usize] = true;
+                for vertex in [
+                    self.vertex(self.next(corner)),
+                    self.vertex(self.previous(corner)),
+                ] {
+                    if let Some(on_seam) = vertex_on_seam.get_mut(vertex as usize) {
+                        *on_seam = true;
+                    }
+                }
+            }
+        }
+        let mut table = CornerTable {
+            vertices: vec![INVALID; self.corner_count()],
+            opposites: self
+                .opposites
+                .iter()
+                .zip(&on_seam)
+                .map(|(opposite, on_seam)| if *on_seam { INVALID } else { *opposite })
+                .collect(),
+            left_most: vec![],
+        };
+        for (vertex, corner) in self.left_most.iter().enumerate() {
+            if *corner == INVALID {
+                continue;
+            }
+            let mut first = *corner;
+            if vertex_on_seam[vertex] {
+                let mut swung = table.swing_left(first);
+                while swung != INVALID {
+                    first = swung;
+                    swung = table.swing_left(swung);
+                    if swung == *corner {
+                        return Err(malformed("attribute seam does not reach a boundary"));
+                    }
+                }
+            }
+            let mut id = table.add_vertex();
+            table.map(first, id);
+            table.set_left_most(id, first);
+            let mut swung = self.swing_right(first);
+            while swung != INVALID && swung != first {
+                if on_seam[self.next(swung) as usize] {
+                    id = table.add_vertex();
+                    table.set_left_most(id, swung);
+                }
+                table.map(swung, id);
+                swung = self.swing_right(swung);
+            }
+        }
+        Ok(table)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TopologySplit {
+    source: u32,
+    split: u32,
+    right_edge: bool,
+}
+
+/*
+Split symbols join two parts of the traversal that the encoder visited
+separately. Each one names the later symbol whose active edge continues at
+the split, and on which side of it.
+*/
+fn decode_topology_splits(
+    reader: &mut BufferReader,
+    face_count: usize,
+) -> Result<Vec<TopologySplit>> {
+    let count = read_varint_u32(reader)? as usize;
+    if count > face_count {
+        return Err(malformed("more topology splits than faces"));
+    }
+    let mut splits = Vec::with_capacity(count.min(reader.remaining()));
+    let mut source = 0u32;
+    for _ in 0..count {
+        source = source
+            .checked_add(read_varint_u32(reader)?)
+            .ok_or_else(|| malformed("topology split source overflows"))?;
+        let delta = read_varint_u32(reader)?;
+        if delta > source {
+            return Err(malformed("topology split precedes the first symbol"));
+        }
+        splits.push(TopologySplit {
+            source,
+            split: source - delta,
+            right_edge: false,
+        });
+    }
+    if count > 0 {
+        let mut bits = BitReader {
+            buffer: reader.remaining_bytes(),
+            offset: 0,
+        };
+        for split in splits.iter_mut() {
+            split.right_edge = bits.read_bits(1) == 1;
+        }
+        reader.skip(bits.bytes_read().min(reader.remaining()))?;
+    }
+    Ok(splits)
+}
+
+/*
+The standard traversal stores every symbol as a prefix code, the valence
+traversal entropy codes them in contexts chosen by the valence of the vertex
+the next symbol starts at.
+*/
+enum Symbols<'a> {
+    Standard(BitReader<'a>),
+    Valence {
+        contexts: Vec<Vec<u32>>,
+        active: Option<usize>,
+        valences: Vec<u32>,
+    },
+}
+
+impl Symbols<'_> {
+    fn next(&mut self) -> Result<u32> {
+        match self {
+            Symbols::Standard(bits) => match bits.read_bits(1) {
+                0 => Ok(TOPOLOGY_C),
+                _ => Ok(1 | bits.read_bits(2) << 1),
+            },
+            Symbols::Valence { active: None, .. } => Ok(TOPOLOGY_E),
+            Symbols::Valence {
+                contexts,
+                active: Some(context),
+                ..
+            } => {
+                let symbol = contexts[*context]
+                    .pop()
+                    .ok_or_else(|| malformed("valence context is exhausted"))?;
+                VALENCE_SYMBOLS
+                    .get(symbol as usize)
+                    .copied()
+                    .ok_or_else(|| malformed("invalid valence symbol"))
+            }
+        }
+    }
+
+    fn valence(valences: &mut [u32], vertex: u32) -> Result<&mut u32> {
+        valences
+            .get_mut(vertex as usize)
+            .ok_or_else(|| malformed("vertex exceeds the encoded vertex count"))
+    }
+
+    fn new_active_corner(&mut self, table: &CornerTable, corner: u32, symbol: u32) -> Result<()> {
+        let Symbols::Valence {
+            active, valences, ..
+        } = self
+        else {
+            return Ok(());
+        };
+        let next = table.next(corner);
+        let previous = table.previous(corner);
+        let increments = match symbol {
+            TOPOLOGY_C | TOPOLOGY_S => [0, 1, 1],
+            TOPOLOGY_R => [1, 1, 2],
+            TOPOLOGY_L => [1, 2, 1],
+            _ => [2, 2, 2],
+        };
+        for (corner, increment) in [corner, next, previous].into_iter().zip(increments) {
+            *Self::valence(valences, table.vertex(corner))? += increment;
+        }
+        let valence = *Self::valence(valences, table.vertex(next))?;
+        *active = Some((valence.clamp(MIN_VALENCE, MAX_VALENCE) - MIN_VALENCE) as usize);
+        Ok(())
+    }
+
+    fn merge_vertices(&mut self, destination: u32, source: u32) -> Result<()> {
+        if let Symbols::Valence { valences, .. } = self {
+            let valence = *Self::valence(valences, source)?;
+            *Self::valence(valences, destination)? += valence;
+        }
+        Ok(())
+    }
+}
+
+/*
+Edgebreaker connectivity, plus one table per attribute that has seams of its
+own, e.g. texture coordinates that differ between the faces around a vertex.
+*/
+#[derive(Debug)]
+struct Edgebreaker {
+    table: CornerTable,
+    seam_tables: Vec<CornerTable>,
+}
+
+fn decode_edgebreaker_connectivity(reader: &mut BufferReader) -> Result<Edgebreaker> {
+    let traversal = reader.read_u8()?;
+    match traversal {
+        STANDARD_TRAVERSAL | VALENCE_TRAVERSAL => {}
+        PREDICTIVE_TRAVERSAL => return Err(unsupported("predictive edgebreaker traversal")),
+        _ => {
+            return Err(malformed(&format!(
+                "unknown edgebreaker traversal {}",
+                traversal
+            )))
+        }
+    }
+    let vertex_count = read_varint_u32(reader)? as usize;
+    let face_count = read_varint_u32(reader)? as usize;
+    let attribute_data_count = reader.read_u8()? as usize;
+    let symbol_count = read_varint_u32(reader)? as usize;
+    let split_symbol_count = read_varint_u32(reader)? as usize;
+    if face_count > (u32::MAX / 3) as usize {
+        return Err(malformed("too many faces"));
+    }
+    if face_count < symbol_count || face_count > symbol_count + symbol_count / 3 {
+        return Err(malformed(
+            "face count does not match the edgebreaker symbols",
+        ));
+    }
+    if split_symbol_count > symbol_count {
+        return Err(malformed("more split symbols than symbols"));
+    }
+    if vertex_count > 3 * face_count {
+        return Err(malformed("more vertices than corners"));
+    }
+    let mut splits = decode_topology_splits(reader, face_count)?;
+
+    let mut bits = None;
+    if traversal == STANDARD_TRAVERSAL {
+        let size = read_varint(reader)?;
+        if size > reader.remaining() as u64 {
+            return Err(malformed("edgebreaker symbols exceed buffer"));
+        }
+        let size = size as usize;
+        if symbol_count > 8 * size {
+            return Err(malformed("too few edgebreaker symbols"));
+        }
+        bits = Some(BitReader {
+            buffer: reader.read_bytes(size)?,
+            offset: 0,
+        });
+    }
+    let mut start_faces = RabsDecoder::start(reader)?;
+    let mut seam_decoders = (0..attribute_data_count)
+        .map(|_| RabsDecoder::start(reader))
+        .collect::<Result<Vec<_>>>()?;
+    let max_vertex_count = vertex_count + split_symbol_count;
+    let mut symbols = match bits {
+        Some(bits) => Symbols::Standard(bits),
+        None => {
+            let mut contexts = Vec::new();
+            let mut context_symbol_count = 0;
+            for _ in MIN_VALENCE..=MAX_VALENCE {
+                let count = read_varint_u32(reader)? as usize;
+                if count > face_count {
+                    return Err(malformed("valence context exceeds the face count"));
+                }
+                contexts.push(decode_symbols(reader, count, 1)?);
+                context_symbol_count += count;
+            }
+            if symbol_count > context_symbol_count + 1 {
+                return Err(malformed("too few valence symbols"));
+            }
+            Symbols::Valence {
+                contexts,
+                active: None,
+                valences: vec![0; max_vertex_count],
+            }
+        }
+    };
+
+    /*
+    The encoder traverses the mesh and the decoder replays the symbols in
+    reverse, growing the mesh from the last face the encoder visited. The
+    stack holds the corners opposite to the active edges, which the next
+    symbols attach their faces to.
+    */
+    let mut table = CornerTable::new(face_count);
+    let mut stack: Vec<u32> = Vec::new();
+    let mut split_corners: HashMap<usize, u32> = HashMap::new();
+    let empty_stack = || malformed("edgebreaker symbol without an active edge");
+    for symbol_id in 0..symbol_count {
+        let corner = 3 * symbol_id as u32;
+        let symbol = symbols.next()?;
+        let mut check_splits = false;
+        match symbol {
+            TOPOLOGY_C => {
+                let a = *stack.last().ok_or_else(empty_stack)?;
+                let x = table.vertex(table.next(a));
+                let b = table.next(table.left_most(x));
+                if b == INVALID
+                    || a == b
+                    || table.opposite(a) != INVALID
+                    || table.opposite(b) != INVALID
+                {
+                    return Err(malformed("invalid edgebreaker C symbol"));
+                }
+                table.set_opposites(a, corner + 1);
+                table.set_opposites(b, corner + 2);
+                let a_previous = table.vertex(table.previous(a));
+                let b_next = table.vertex(table.next(b));
+                if x == a_previous || x == b_next {
+                    return Err(malformed("invalid edgebreaker C symbol"));
+                }
+                table.map(corner, x);
+                table.map(corner + 1, b_next);
+                table.map(corner + 2, a_previous);
+                table.set_left_most(a_previous, corner + 2);
+                stack.pop();
+                stack.push(corner);
+            }
+            TOPOLOGY_R | TOPOLOGY_L => {
+                let a = *stack.last().ok_or_else(empty_stack)?;
+                if table.opposite(a) != INVALID {
+                    return Err(malformed("invalid edgebreaker R or L symbol"));
+                }
+                let (opposite, left, right) = if symbol == TOPOLOGY_R {
+                    (corner + 2, corner + 1, corner)
+                } else {
+                    (corner + 1, corner, corner + 2)
+                };
+                table.set_opposites(opposite, a);
+                let vertex = table.add_vertex();
+                if table.vertex_count() > max_vertex_count {
+                    return Err(malformed("more vertices than encoded"));
+                }
+                table.map(opposite, vertex);
+                table.set_left_most(vertex, opposite);
+                let right_vertex = table.vertex(table.previous(a));
+                table.map(right, right_vertex);
+                table.set_left_most(right_vertex, right);
+                table.map(left, table.vertex(table.next(a)));
+                stack.pop();
+                stack.push(corner);
+                check_splits = true;
+            }
+            TOPOLOGY_S => {
+                let b = stack.pop().ok_or_else(empty_stack)?;
+                if let Some(split_corner) = split_corners.get(&symbol_id) {
+                    stack.push(*split_corner);
+                }
+                let a = *stack.last().ok_or_else(empty_stack)?;
+                if a == b || table.opposite(a) != INVALID || table.opposite(b) != INVALID {
+                    return Err(malformed("invalid edgebreaker S symbol"));
+                }
+                table.set_opposites(a, corner + 2);
+                table.set_opposites(b, corner + 1);
+                let p = table.vertex(table.previous(a));
+                table.map(corner, p);
+                table.map(corner + 1, table.vertex(table.next(a)));
+                let b_previous = table.vertex(table.previous(b));
+                table.map(corner + 2, b_previous);
+                table.set_left_most(b_previous, corner + 2);
+                // the fan around the next vertex of b joins p
+                let first = table.next(b);
+                let n = table.vertex(first);
+                symbols.merge_vertices(p, n)?;
+                table.set_left_most(p, table.left_most(n));
+                let mut swung = first;
+                while swung != INVALID {
+                    table.map(swung, p);
+                    swung = table.swing_left(swung);
+                    if swung == first {
+                        return Err(malformed("invalid edgebreaker S symbol"));
+                    }
+                }
+                table.set_left_most(n, INVALID);
+                stack.pop();
+                stack.push(corner);
+            }
+            TOPOLOGY_E => {
+                for i in 0..3 {
+                    let vertex = table.add_vertex();
+                    table.map(corner + i, vertex);
+                    table.set_left_most(vertex, corner + i);
+                }
+                if table.vertex_count() > max_vertex_count {
+                    return Err(malformed("more vertices than encoded"));
+                }
+                stack.push(corner);
+                check_splits = true;
+            }
+            _ => return Err(malformed(&format!("invalid edgebreaker symbol {}", symbol))),
+        }
+        let top = *stack.last().ok_or_else(empty_stack)?;
+        symbols.new_active_corner(&table, top, symbol)?;
+        if check_splits {
+            let encoder_symbol_id = (symbol_count - symbol_id - 1) as u32;
+            while let Some(split) = splits.last().copied() {
+                if split.source > encoder_symbol_id {
+                    return Err(malformed("topology split was not reached"));
+                }
+                if split.source != encoder_symbol_id {
+                    break;
+                }
+                splits.pop();
+                let split_corner = if split.right_edge {
+                    table.next(top)
+                } else {
+                    table.previous(top)
+                };
+                split_corners.insert(symbol_count - split.split as usize - 1, split_corner);
+            }
+        }
+    }
+
+    // the remaining active edges either bound the mesh or close an interior face
+    let mut face = symbol_count;
+    while let Some(a) = stack.pop() {
+        if !start_faces.read() {
+            continue;
+        }
+        if face >= face_count {
+            return Err(malformed("more start faces than faces"));
+        }
+        let n = table.vertex(table.next(a));
+        let b = table.next(table.left_most(n));
+        let x = table.vertex(table.next(b));
+        let c = table.next(table.left_most(x));
+        if b == INVALID
+            || c == INVALID
+            || a == b
+            || a == c
+            || b == c
+            || table.opposite(a) != INVALID
+            || table.opposite(b) != INVALID
+            || table.opposite(c) != INVALID
+        {
+            return Err(malformed("invalid interior start face"));
+        }
+        let p = table.vertex(table.next(c));
+        let corner = 3 * face as u32;
+        face += 1;
+        table.set_opposites(corner, a);
+        table.set_opposites(corner + 1, b);
+        table.set_opposites(corner + 2, c);
+        table.map(corner, x);
+        table.map(corner + 1, p);
+        table.map(corner + 2, n);
+    }
+    if face != face_count {
+        return Err(malformed("edgebreaker faces are missing"));
+    }
+
+    // boundary edges are seams of every attribute, interior edges are flagged once
+    let mut seams = vec![Vec::new(); attribute_data_count];
+    if attribute_data_count > 0 {
+        for corner in 0..table.corner_count() as u32 {
+            let opposite = table.opposite(corner);
+            if opposite == INVALID {
+                for seam in seams.iter_mut() {
+                    seam.push(corner);
+                }
+            } else if opposite / 3 >= corner / 3 {
+                for (seam, decoder) in seams.iter_mut().zip(seam_decoders.iter_mut()) {
+                    if decoder.read() {
+                        seam.push(corner);
+                    }
+                }
+            }
+        }
+    }
+    let seam_tables = seams
+        .iter()
+        .map(|seams| table.with_seams(seams))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Edgebreaker { table, seam_tables })
+}
+
+/*
+How an attribute group is traversed, with the table of its seams for values
+stored per corner.
+*/
+#[derive(Debug, Clone, Copy)]
+struct Sequencer {
+    seams: Option<usize>,
+    traversal: u8,
+}
+
+fn read_sequencer(reader: &mut BufferReader, attribute_data_count: usize) -> Result<Sequencer> {
+    let data = reader.read_i8()?;
+    let decoder_type = reader.read_u8()?;
+    let traversal = reader.read_u8()?;
+    let data = match data {
+        -1 => None,
+        _ if data >= 0 && (data as usize) < attribute_data_count => Some(data as usize),
+        _ => return Err(malformed(&format!("invalid attribute data {}", data))),
+    };
+    if !matches!(
+        traversal,
+        DEPTH_FIRST_TRAVERSAL | PREDICTION_DEGREE_TRAVERSAL
+    ) {
+        return Err(malformed(&format!(
+            "unknown attribute traversal {}",
+            traversal
+        )));
+    }
+    match decoder_type {
+        VERTEX_ATTRIBUTE => Ok(Sequencer {
+            seams: None,
+            traversal,
+        }),
+        CORNER_ATTRIBUTE if data.is_some() && traversal == DEPTH_FIRST_TRAVERSAL => Ok(Sequencer {
+            seams: data,
+            traversal,
+        }),
+        CORNER_ATTRIBUTE => Err(malformed("invalid per corner attribute decoder")),
+        _ => Err(malformed(&format!(
+            "unknown attribute decoder type {}",
+            decoder_type
+        ))),
+    }
+}
+
+#[derive(Debug)]
+struct Sequence {
+    // the corner at which every value was reached
+    entry_corners: Vec<u32>,
+    // the value of every vertex, INVALID if it was not reached
+    vertex_entries: Vec<u32>,
+}
+
+// number of priorities of the prediction degree traversal
+const PRIORITIES: usize = 3;
+
+/*
+Visits the faces of a corner table from the given start corners and numbers
+the vertices in the order they are reached. Depth first follows the faces
+around a vertex, prediction degree prefers vertices whose values can be
+predicted from more of their neighbours.
+*/
+struct Traversal<'a> {
+    table: &'a CornerTable,
+    visited_faces: Vec<bool>,
+    visited_vertices: Vec<bool>,
+    degrees: Vec<u32>,
+    sequence: Sequence,
+}
+
+impl<'a> Traversal<'a> {
+    fn new(table: &'a CornerTable) -> Self {
+        Self {
+            table,
+            visited_faces: vec![false; table.face_count()],
+            visited_vertices: vec![false; table.vertex_count()],
+            degrees: vec![],
+            sequence: Sequence {
+                entry_corners: Vec::with_capacity(table.vertex_count()),
+                vertex_entries: vec![INVALID; table.vertex_count()],
+            },
+        }
+    }
+
+    fn is_face_visited(&self, corner: u32) -> bool {
+        self.visited_faces
+            .get((corner / 3) as usize)
+            .copied()
+            .unwrap_or(true)
+    }
+
+    fn visit_face(&mut self, corner: u32) -> Result<()> {
+        let visited = self
+            .visited_faces
+            .get_mut((corner / 3) as usize)
+            .ok_or_else(|| malformed("traversal left the mesh"))?;
+        *visited = true;
+        Ok(())
+    }
+
+    // returns whether the vertex of corner was reached for the first time
+    fn visit_vertex(&mut self, corner: u32) -> Result<bool> {
+        let vertex = self.table.vertex(corner) as usize;
+        let visited = self
+            .visited_vertices
+            .get_mut(vertex)
+            .ok_or_else(|| malformed("traversed corner without a vertex"))?;
+        if *visited {
+            return Ok(false);
+        }
+        *visited = true;
+        self.sequence.vertex_entries[vertex] = self.sequence.entry_corners.len() as u32;
+        self.sequence.entry_corners.push(corner);
+        Ok(true)
+    }
+
+    fn depth_first(&mut self, start: u32) -> Result<()> {
+        if self.is_face_visited(start) {
+            return Ok(());
+        }
+        self.visit_vertex(self.table.next(start))?;
+        self.visit_vertex(self.table.previous(start))?;
+        let mut stack = vec![start];
+        while let Some(top) = stack.last().copied() {
+            if self.is_face_visited(top) {
+                stack.pop();
+                continue;
+            }
+            let mut corner = top;
+            loop {
+                self.visit_face(corner)?;
+                if self.visit_vertex(corner)?
+                    && !self.table.is_on_boundary(self.table.vertex(corner))
+                {
+                    corner = self.table.right_corner(corner);
+                    continue;
+                }
+                let right = self.table.right_corner(corner);
+                let left = self.table.left_corner(corner);
+                match (self.is_face_visited(right), self.is_face_visited(left)) {
+                    (true, true) => {
+                        stack.pop();
+                        break;
+                    }
+                    (true, false) => corner = left,
+                    (false, true) => corner = right,
+                    (false, false) => {
+                        stack.pop();
+                        stack.push(left);
+                        stack.push(right);
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn priority(&mut self, corner: u32) -> usize {
+        let vertex = self.table.vertex(corner) as usize;
+        match self.visited_vertices.get(vertex) {
+            Some(false) => {
+                self.degrees[vertex] += 1;
+                if self.degrees[vertex] > 1 {
+                    1
+                } else {
+                    2
+                }
+            }
+            _ => 0,
+        }
+    }
+
+    fn prediction_degree(&mut self, start: u32) -> Result<()> {
+        if self.degrees.is_empty() {
+            self.degrees = vec![0; self.table.vertex_count()];
+        }
+        let mut stacks: [Vec<u32>; PRIORITIES] = Default::default();
+        let mut best = 0;
+        stacks[0].push(start);
+        self.visit_vertex(self.table.next(start))?;
+        self.visit_vertex(self.table.previous(start))?;
+        self.visit_vertex(start)?;
+        while let Some((priority, mut corner)) =
+            (best..PRIORITIES).find_map(|priority| Some((priority, stacks[priority].pop()?)))
+        {
+            best = priority;
+            if self.is_face_visited(corner) {
+                continue;
+            }
+            loop {
+                self.visit_face(corner)?;
+                self.visit_vertex(corner)?;
+                let right = self.table.right_corner(corner);
+                let left = self.table.left_corner(corner);
+                let right_visited = self.is_face_visited(right);
+                if !self.is_face_visited(left) {
+                    let priority = self.priority(left);
+                    if right_visited && priority <= best {
+                        corner = left;
+                        continue;
+                    }
+                    stacks[priority].push(left);
+                    best = best.min(priority);
+                }
+                if !right_visited {
+                    let priority = self.priority(right);
+                    if priority <= best {
+                        corner = right;
+                        continue;
+                    }
+                    stacks[priority].push(right);
+                }
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Edgebreaker {
+    fn traverse(&self, sequencer: Sequencer) -> Result<MeshPrediction<'_>> {
+        let table = match sequencer.seams {
+            Some(data) => &self.seam_tables[data],
+            None => &self.table,
+        };
+        let mut traversal = Traversal::new(table);
+        for face in 0..table.face_count() {
+            let corner = 3 * face as u32;
+            match sequencer.traversal {
+                DEPTH_FIRST_TRAVERSAL => traversal.depth_first(corner)?,
+                _ => traversal.prediction_degree(corner)?,
+            }
+        }
+        Ok(MeshPrediction {
+            table,
+            sequence: traversal.sequence,
+        })
+    }
+}
+
+fn float_values<const N: usize>(
+    attribute: &Attribute,
+    faces: &[[u32; 3]],
+) -> Result<Vec<[f32; N]>> {
+    let Values::Float(values) = &attribute.values else {
+        return Err(malformed("expected a floating point attribute"));
+    };
+    if attribute.components < N {
+        return Err(malformed("attribute has too few components"));
+    }
+    faces
+        .iter()
+        .flatten()
+        .map(|point| {
+            let values = point_values(values, *point, attribute.components)?;
+            Ok(std::array::from_fn(|i| values[i]))
+        })
+        .collect()
+}
+
+// the values of a point, which faces of a malformed mesh may point past
+fn point_values<T>(values: &[T], point: u32, components: usize) -> Result<&[T]> {
+    (point as usize)
+        .checked_mul(components)
+        .and_then(|start| values.get(start..start.checked_add(components)?))
+        .ok_or_else(|| malformed("point has no attribute value"))
+}
+
+fn integer_values(attribute: &Attribute, faces: &[[u32; 3]]) -> Result<Vec<Vec<i64>>> {
+    let values: Vec<i64> = match &attribute.values {
+        Values::Integer(values) => values.clone(),
+        Values::Float(values) => values.iter().map(|value| *value as i64).collect(),
+    };
+    faces
+        .iter()
+        .flatten()
+        .map(|point| point_values(&values, *point, attribute.components).map(<[i64]>::to_vec))
+        .collect()
+}
+
+/*
+I3S stores per vertex feature indices in a generic attribute and the actual
+feature ids in its metadata. Faces of a feature are contiguous, so the face
+ranges can be recovered from the index of the first vertex of every face.
+*/
+fn feature_ranges(feature_indices: &[i64], feature_count: usize) -> Vec<[u32; 2]> {
+    let mut ranges: Vec<Option<[u32; 2]>> = vec![None; feature_count];
+    for (face, corners) in feature_indices.chunks_exact(3).enumerate() {
+        let Some(range) = usize::try_from(corners[0])
+            .ok()
+            .and_then(|index| ranges.get_mut(index))
+        else {
+            continue;
+        };
+        let face = face as u32;
+        *range = Some(match range {
+            Some([first, last]) => [(*first).min(face), (*last).max(face)],
+            None => [face, face],
+        });
+    }
+    ranges
+        .into_iter()
+        .map(|range| range.unwrap_or_default())
+        .collect()
+}
+
+/*
+Decodes a Draco mesh into the same non indexed layout as the uncompressed
+geometry buffer, i.e. three vertices per face.
+*/
+pub fn decode_mesh(buffer: &[u8]) -> Result<MeshData> {
+    let mut reader = BufferReader::new(buffer);
+    let header = read_header(&mut reader)?;
+    if header.major != 2 || header.minor != 2 {
+        return Err(unsupported(&format!(
+            "bitstream version {}.{}",
+            header.major, header.minor
+        )));
+    }
+    if header.encoder_type != TRIANGULAR_MESH {
+        return Err(unsupported(&format!(
+            "geometry type {}",
+            header.encoder_type
+        )));
+    }
+    let metadata = if header.flags & METADATA_FLAG != 0 {
+        read_attribute_metadata(&mut reader)?
+    } else {
+        HashMap::new()
+    };
+    let connectivity = match header.encoder_method {
+        SEQUENTIAL_ENCODING => {
+            let (faces, point_count) = decode_sequential_connectivity(&mut reader)?;
+            Connectivity::Sequential { faces, point_count }
+        }
+        EDGEBREAKER_ENCODING => {
+            Connectivity::Edgebreaker(decode_edgebreaker_connectivity(&mut reader)?)
+        }
+        method => return Err(unsupported(&format!("encoding method {}", method))),
+    };
+    let groups = decode_attributes(&mut reader, &connectivity)?;
+
+    let mut mesh = MeshData::default();
+    for (attribute, faces) in groups.iter().flat_map(|group| {
+        group
+            .attributes
+            .iter()
+            .map(|attribute| (attribute, &group.faces))
+    }) {
+        let i3s_type = metadata
+            .get(&attribute.unique_id)
+            .and_then(|metadata| metadata.string(I3S_ATTRIBUTE_TYPE));
+        match (attribute.attribute_type, i3s_type) {
+            (POSITION, _) => mesh.positions = float_values(attribute, faces)?,
+            (NORMAL, _) => mesh.normals = float_values(attribute, faces)?,
+            (TEX_COORD, _) => mesh.uv0 = float_values(attribute, faces)?,
+            (COLOR, _) => {
+                mesh.colors = integer_values(attribute, faces)?
+                    .into_iter()
+                    .map(|color| {
+                        let channel = |i: usize| color.get(i).map_or(255, |value| *value as u8);
+                        [channel(0), channel(1), channel(2), channel(3)]
+                    })
+                    .collect()
+            }
+            (GENERIC, Some("uv-region")) => {
+                mesh.uv_regions = integer_values(attribute, faces)?
+                    .into_iter()
+                    .map(|region| {
+                        let value = |i: usize| region.get(i).map_or(0, |value| *value as u16);
+                        [value(0), value(1), value(2), value(3)]
+                    })
+                    .collect()
+            }
+            (GENERIC, Some("feature-index")) => {
+                let indices = integer_values(attribute, faces)?
+                    .into_iter()
+                    .map(|index| index.first().copied())
+                    .collect::<Option<Vec<i64>>>()
+                    .ok_or_else(|| malformed("feature index has no components"))?;
+                mesh.feature_ids = match metadata[&attribute.unique_id].int_array(I3S_FEATURE_IDS) {
+                    // i3s-feature-ids holds int32 values, negative ones are not valid ids
+                    Some(ids) => ids
+                        .into_iter()
+                        .map(|id| {
+                            u64::try_from(id)
+                                .map_err(|_| malformed(&format!("feature id {} is negative", id)))
+                        })
+                        .collect::<Result<_>>()?,
+                    None => {
+                        let count = indices.iter().max().map_or(0, |max| *max + 1);
+                        if count > indices.len() as i64 {
+                            return Err(malformed("feature index exceeds the vertex count"));
+                        }
+                        (0..count.max(0) as u64).collect()
+                    }
+                };
+                mesh.face_ranges = feature_ranges(&indices, mesh.feature_ids.len());
+            }
+            _ => {}
+        }
+    }
+    Ok(mesh)
+}
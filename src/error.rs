@@ -0,0 +1,124 @@
+use core::fmt;
+use std::error;
+use std::io;
+
+use zip::result::ZipError;
+
+pub type Result<T> = std::result::Result<T, I3sError>;
+
+#[derive(Debug)]
+pub enum I3sError {
+    ResourceNotFound(String),
+    HttpStatus {
+        path: String,
+        status: u16,
+    },
+    Gzip {
+        path: String,
+        source: io::Error,
+    },
+    Json {
+        path: String,
+        source: serde_json::Error,
+    },
+    UnsupportedLayerType(String),
+    MissingLayerType,
+    MalformedNodePage {
+        path: String,
+        reason: String,
+    },
+    MalformedBuffer(String),
+    UnsupportedEncoding(String),
+    InvalidCamera(String),
+    Request(reqwest::Error),
+    Url(url::ParseError),
+    Zip(ZipError),
+    IO(io::Error),
+}
+
+impl I3sError {
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, I3sError::ResourceNotFound(_))
+    }
+
+    pub(crate) fn from_zip(path: &str, err: ZipError) -> Self {
+        match err {
+            ZipError::FileNotFound => I3sError::ResourceNotFound(path.to_string()),
+            err => I3sError::Zip(err),
+        }
+    }
+}
+
+impl fmt::Display for I3sError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            I3sError::ResourceNotFound(path) => write!(f, "resource not found: {}", path),
+            I3sError::HttpStatus { path, status } => {
+                write!(f, "request for {} failed with status {}", path, status)
+            }
+            I3sError::Gzip { path, source } => {
+                write!(f, "cannot decompress {}: {}", path, source)
+            }
+            I3sError::Json { path, source } => write!(
+                f,
+                "cannot parse {} at line {}, column {}: {}",
+                path,
+                source.line(),
+                source.column(),
+                source
+            ),
+            I3sError::UnsupportedLayerType(layer_type) => {
+                write!(f, "layer type not supported: {}", layer_type)
+            }
+            I3sError::MissingLayerType => write!(f, "cannot parse layer type"),
+            I3sError::MalformedNodePage { path, reason } => {
+                write!(f, "malformed node page {}: {}", path, reason)
+            }
+            I3sError::MalformedBuffer(reason) => write!(f, "malformed buffer: {}", reason),
+            I3sError::UnsupportedEncoding(reason) => write!(f, "unsupported encoding: {}", reason),
+            I3sError::InvalidCamera(reason) => write!(f, "invalid camera: {}", reason),
+            I3sError::Request(err) => write!(f, "request failed: {}", err),
+            I3sError::Url(err) => write!(f, "invalid url: {}", err),
+            I3sError::Zip(err) => write!(f, "zip error: {}", err),
+            I3sError::IO(err) => write!(f, "io error: {}", err),
+        }
+    }
+}
+
+impl error::Error for I3sError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            I3sError::Gzip { source, .. } => Some(source),
+            I3sError::Json { source, .. } => Some(source),
+            I3sError::Request(err) => Some(err),
+            I3sError::Url(err) => Some(err),
+            I3sError::Zip(err) => Some(err),
+            I3sError::IO(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for I3sError {
+    fn from(err: io::Error) -> Self {
+        I3sError::IO(err)
+    }
+}
+
+impl From<ZipError> for I3sError {
+    fn from(err: ZipError) -> Self {
+        I3sError::Zip(err)
+    }
+}
+
+impl From<reqwest::Error> for I3sError {
+    fn from(err: reqwest::Error) -> Self {
+        I3sError::Request(err)
+    }
+}
+
+impl From<url::ParseError> for I3sError {
+    fn from(err: url::ParseError) -> Self {
+        I3sError::Url(err)
+    }
+}
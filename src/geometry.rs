@@ -0,0 +1,642 @@
+use crate::attribute::{self, AttributeColumn};
+use crate::cmn;
+use crate::error::{I3sError, Result};
+use crate::io::BufferReader;
+use crate::lepcc;
+use crate::pcl;
+
+#[derive(Default, Debug, Clone)]
+pub struct MeshData {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uv0: Vec<[f32; 2]>,
+    pub colors: Vec<[u8; 4]>,
+    pub uv_regions: Vec<[u16; 4]>,
+    pub feature_ids: Vec<u64>,
+    pub face_ranges: Vec<[u32; 2]>,
+}
+
+impl MeshData {
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn feature_count(&self) -> usize {
+        self.feature_ids.len()
+    }
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct PointData {
+    pub positions: Vec<[f64; 3]>,
+    pub colors: Vec<[u8; 3]>,
+    pub intensities: Vec<u16>,
+}
+
+impl PointData {
+    pub fn point_count(&self) -> usize {
+        self.positions.len()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PointAttribute {
+    pub values_per_element: usize,
+    pub values: AttributeColumn,
+}
+
+impl PointAttribute {
+    pub fn point_count(&self) -> usize {
+        self.values.len() / self.values_per_element.max(1)
+    }
+
+    pub fn value(&self, point: usize, component: usize) -> Option<f64> {
+        if component >= self.values_per_element {
+            return None;
+        }
+        self.values
+            .get_f64(point * self.values_per_element + component)
+    }
+
+    pub fn elevation(positions: &[[f64; 3]]) -> Self {
+        Self {
+            values_per_element: 1,
+            values: AttributeColumn::Float64(positions.iter().map(|p| p[2]).collect()),
+        }
+    }
+}
+
+/*
+Point cloud attributes are either LEPCC blobs or tightly packed little
+endian arrays of `valuesPerElement` values per point. Embedded elevation has
+no resource of its own, see PointAttribute::elevation.
+*/
+pub fn decode_point_attribute(
+    attribute: &pcl::AttributeInfo,
+    buffer: &[u8],
+) -> Result<PointAttribute> {
+    match attribute.encoding.as_deref() {
+        Some("lepcc-rgb") => {
+            return Ok(PointAttribute {
+                values_per_element: 3,
+                values: AttributeColumn::UInt8(
+                    lepcc::decode_rgb(buffer)?.into_iter().flatten().collect(),
+                ),
+            })
+        }
+        Some("lepcc-intensity") => {
+            return Ok(PointAttribute {
+                values_per_element: 1,
+                values: AttributeColumn::UInt16(lepcc::decode_intensity(buffer)?),
+            })
+        }
+        Some("embedded-elevation") => {
+            return Err(I3sError::MalformedBuffer(
+                "embedded elevation is part of the geometry".to_string(),
+            ))
+        }
+        _ => {}
+    }
+    let Some(layout) = &attribute.attribute_values else {
+        return Err(I3sError::MalformedBuffer(format!(
+            "attribute {} has no value type",
+            attribute.name
+        )));
+    };
+    let value_type = layout.value_type.as_str();
+    let size = attribute::value_size(value_type).ok_or_else(|| {
+        I3sError::MalformedBuffer(format!("unsupported attribute value type: {}", value_type))
+    })?;
+    if !buffer.len().is_multiple_of(size) {
+        return Err(I3sError::MalformedBuffer(format!(
+            "attribute buffer of {} bytes is not a multiple of {}",
+            buffer.len(),
+            size
+        )));
+    }
+    let values = attribute::read_values(
+        &mut BufferReader::new(buffer),
+        value_type,
+        buffer.len() / size,
+    )?;
+    let values_per_element = layout.values_per_element.max(1);
+    if !values.len().is_multiple_of(values_per_element) {
+        return Err(I3sError::MalformedBuffer(format!(
+            "attribute {} is not a multiple of {} values",
+            attribute.name, values_per_element
+        )));
+    }
+    Ok(PointAttribute {
+        values_per_element,
+        values,
+    })
+}
+
+fn unsupported(attribute: &str, dtype: &str, component: i32) -> I3sError {
+    I3sError::MalformedBuffer(format!(
+        "unsupported {} layout: {} x {}",
+        attribute, dtype, component
+    ))
+}
+
+/*
+Counts come from the buffer's own header, so they are checked against the
+bytes that are left before anything is allocated for them.
+*/
+fn check_length(
+    reader: &BufferReader,
+    count: usize,
+    element_size: usize,
+    attribute: &str,
+) -> Result<()> {
+    match count.checked_mul(element_size) {
+        Some(length) if length <= reader.remaining() => Ok(()),
+        _ => Err(I3sError::MalformedBuffer(format!(
+            "{}: {} elements of {} bytes exceed the {} bytes left in the buffer",
+            attribute,
+            count,
+            element_size,
+            reader.remaining()
+        ))),
+    }
+}
+
+fn read_f32_array<const N: usize>(
+    reader: &mut BufferReader,
+    count: usize,
+    attribute: &str,
+    dtype: &str,
+    component: i32,
+) -> Result<Vec<[f32; N]>> {
+    if !dtype.eq_ignore_ascii_case("Float32") || component as usize != N {
+        return Err(unsupported(attribute, dtype, component));
+    }
+    check_length(reader, count, 4 * N, attribute)?;
+    let mut values = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut value = [0.0; N];
+        for v in value.iter_mut() {
+            *v = reader.read_f32()?;
+        }
+        values.push(value);
+    }
+    Ok(values)
+}
+
+fn read_colors(
+    reader: &mut BufferReader,
+    count: usize,
+    dtype: &str,
+    component: i32,
+) -> Result<Vec<[u8; 4]>> {
+    if !dtype.eq_ignore_ascii_case("UInt8") || !(3..=4).contains(&component) {
+        return Err(unsupported("color", dtype, component));
+    }
+    check_length(reader, count, component as usize, "color")?;
+    let mut colors = Vec::with_capacity(count);
+    for _ in 0..count {
+        let bytes = reader.read_bytes(component as usize)?;
+        let alpha = if component == 4 { bytes[3] } else { 255 };
+        colors.push([bytes[0], bytes[1], bytes[2], alpha]);
+    }
+    Ok(colors)
+}
+
+fn read_uv_regions(
+    reader: &mut BufferReader,
+    count: usize,
+    dtype: &str,
+    component: i32,
+) -> Result<Vec<[u16; 4]>> {
+    if !dtype.eq_ignore_ascii_case("UInt16") || component != 4 {
+        return Err(unsupported("uvRegion", dtype, component));
+    }
+    check_length(reader, count, 8, "uvRegion")?;
+    let mut regions = Vec::with_capacity(count);
+    for _ in 0..count {
+        regions.push([
+            reader.read_u16()?,
+            reader.read_u16()?,
+            reader.read_u16()?,
+            reader.read_u16()?,
+        ]);
+    }
+    Ok(regions)
+}
+
+fn read_feature_ids(
+    reader: &mut BufferReader,
+    count: usize,
+    dtype: &str,
+    component: i32,
+) -> Result<Vec<u64>> {
+    let mut ids = Vec::new();
+    match (dtype.to_ascii_lowercase().as_str(), component) {
+        ("uint64", 1) => {
+            check_length(reader, count, 8, "featureId")?;
+            ids.reserve_exact(count);
+            for _ in 0..count {
+                ids.push(reader.read_u64()?);
+            }
+        }
+        ("uint32", 1) => {
+            check_length(reader, count, 4, "featureId")?;
+            ids.reserve_exact(count);
+            for _ in 0..count {
+                ids.push(reader.read_u32()? as u64);
+            }
+        }
+        _ => return Err(unsupported("featureId", dtype, component)),
+    }
+    Ok(ids)
+}
+
+fn read_face_ranges(
+    reader: &mut BufferReader,
+    count: usize,
+    dtype: &str,
+    component: i32,
+) -> Result<Vec<[u32; 2]>> {
+    if !dtype.eq_ignore_ascii_case("UInt32") || component != 2 {
+        return Err(unsupported("faceRange", dtype, component));
+    }
+    check_length(reader, count, 8, "faceRange")?;
+    let mut ranges = Vec::with_capacity(count);
+    for _ in 0..count {
+        ranges.push([reader.read_u32()?, reader.read_u32()?]);
+    }
+    Ok(ranges)
+}
+
+/*
+Uncompressed buffers store every vertex attribute as a contiguous array in a
+fixed order (position, normal, uv0, color, uvRegion) followed by the
+per-feature arrays (featureId, faceRange). Only attributes declared in the
+geometry buffer are present, and `offset` skips any leading header.
+*/
+pub fn decode_geometry_buffer(
+    buffer: &[u8],
+    definition: &cmn::GeometryBuffer,
+    vertex_count: usize,
+    feature_count: usize,
+) -> Result<MeshData> {
+    if definition.compressed_attributes.is_some() {
+        return Err(I3sError::MalformedBuffer(
+            "geometry buffer is compressed".to_string(),
+        ));
+    }
+    let mut reader = BufferReader::new(buffer);
+    reader.skip(definition.offset.max(0) as usize)?;
+
+    let mut mesh = MeshData::default();
+    if let Some(position) = &definition.position {
+        mesh.positions = read_f32_array(
+            &mut reader,
+            vertex_count,
+            "position",
+            &position.dtype,
+            position.component,
+        )?;
+    }
+    if let Some(normal) = &definition.normal {
+        mesh.normals = read_f32_array(
+            &mut reader,
+            vertex_count,
+            "normal",
+            &normal.dtype,
+            normal.component,
+        )?;
+    }
+    if let Some(uv0) = &definition.uv0 {
+        mesh.uv0 = read_f32_array(&mut reader, vertex_count, "uv0", &uv0.dtype, uv0.component)?;
+    }
+    if let Some(color) = &definition.color {
+        mesh.colors = read_colors(&mut reader, vertex_count, &color.dtype, color.component)?;
+    }
+    if let Some(uv_region) = &definition.uv_region {
+        mesh.uv_regions = read_uv_regions(
+            &mut reader,
+            vertex_count,
+            &uv_region.dtype,
+            uv_region.component,
+        )?;
+    }
+    if let Some(feature_id) = &definition.feature_id {
+        mesh.feature_ids = read_feature_ids(
+            &mut reader,
+            feature_count,
+            &feature_id.dtype,
+            feature_id.component,
+        )?;
+    }
+    if let Some(face_range) = &definition.face_range {
+        mesh.face_ranges = read_face_ranges(
+            &mut reader,
+            feature_count,
+            &face_range.dtype,
+            face_range.component,
+        )?;
+    }
+    Ok(mesh)
+}
+
+fn read_header_value(reader: &mut BufferReader, header: &cmn::HeaderAttribute) -> Result<u64> {
+    match header.dtype.to_ascii_lowercase().as_str() {
+        "uint8" => Ok(reader.read_u8()? as u64),
+        "uint16" => Ok(reader.read_u16()? as u64),
+        "uint32" => Ok(reader.read_u32()? as u64),
+        "uint64" => reader.read_u64(),
+        dtype => Err(I3sError::MalformedBuffer(format!(
+            "unsupported header {} type: {}",
+            header.property, dtype
+        ))),
+    }
+}
+
+fn schema_attribute<'a>(
+    attribute: Option<&'a cmn::GeometryAttribute>,
+    name: &str,
+) -> Result<&'a cmn::GeometryAttribute> {
+    attribute.ok_or_else(|| {
+        I3sError::MalformedBuffer(format!(
+            "geometry schema orders {} but does not define it",
+            name
+        ))
+    })
+}
+
+/*
+Geometry buffers of layers before 1.7 are described by the store's
+defaultGeometrySchema instead of geometry definitions. The header holds the
+vertex and feature counts, followed by the vertex attributes in `ordering`
+and the feature attributes in `featureAttributeOrder`.
+*/
+pub fn decode_legacy_geometry_buffer(
+    buffer: &[u8],
+    schema: &cmn::DefaultGeometrySchema,
+) -> Result<MeshData> {
+    let mut reader = BufferReader::new(buffer);
+    let mut vertex_count = 0;
+    let mut feature_count = 0;
+    for header in schema.header.iter() {
+        let value = read_header_value(&mut reader, header)? as usize;
+        match header.property.as_str() {
+            "vertexCount" => vertex_count = value,
+            "featureCount" => feature_count = value,
+            _ => {}
+        }
+    }
+
+    let mut mesh = MeshData::default();
+    let vertex_attributes = &schema.vertex_attributes;
+    for name in schema.ordering.iter() {
+        match name.as_str() {
+            "position" => {
+                let position = schema_attribute(vertex_attributes.position.as_ref(), name)?;
+                mesh.positions = read_f32_array(
+                    &mut reader,
+                    vertex_count,
+                    name,
+                    &position.value_type,
+                    position.values_per_element,
+                )?;
+            }
+            "normal" => {
+                let normal = schema_attribute(vertex_attributes.normal.as_ref(), name)?;
+                mesh.normals = read_f32_array(
+                    &mut reader,
+                    vertex_count,
+                    name,
+                    &normal.value_type,
+                    normal.values_per_element,
+                )?;
+            }
+            "uv0" => {
+                let uv0 = schema_attribute(vertex_attributes.uv0.as_ref(), name)?;
+                mesh.uv0 = read_f32_array(
+                    &mut reader,
+                    vertex_count,
+                    name,
+                    &uv0.value_type,
+                    uv0.values_per_element,
+                )?;
+            }
+            "color" => {
+                let color = schema_attribute(vertex_attributes.color.as_ref(), name)?;
+                mesh.colors = read_colors(
+                    &mut reader,
+                    vertex_count,
+                    &color.value_type,
+                    color.values_per_element,
+                )?;
+            }
+            "region" => {
+                let region = schema_attribute(vertex_attributes.region.as_ref(), name)?;
+                mesh.uv_regions = read_uv_regions(
+                    &mut reader,
+                    vertex_count,
+                    &region.value_type,
+                    region.values_per_element,
+                )?;
+            }
+            name => {
+                return Err(I3sError::MalformedBuffer(format!(
+                    "unsupported vertex attribute: {}",
+                    name
+                )))
+            }
+        }
+    }
+
+    let feature_attributes = &schema.feature_attributes;
+    for name in schema.feature_attribute_order.iter() {
+        match name.as_str() {
+            "id" => {
+                let id = schema_attribute(feature_attributes.id.as_ref(), name)?;
+                mesh.feature_ids = read_feature_ids(
+                    &mut reader,
+                    feature_count,
+                    &id.value_type,
+                    id.values_per_element,
+                )?;
+            }
+            "faceRange" => {
+                let face_range = schema_attribute(feature_attributes.face_range.as_ref(), name)?;
+                mesh.face_ranges = read_face_ranges(
+                    &mut reader,
+                    feature_count,
+                    &face_range.value_type,
+                    face_range.values_per_element,
+                )?;
+            }
+            name => {
+                return Err(I3sError::MalformedBuffer(format!(
+                    "unsupported feature attribute: {}",
+                    name
+                )))
+            }
+        }
+    }
+    Ok(mesh)
+}
+
+fn slice_vertices<T: Clone>(values: &[T], vertices: &std::ops::Range<usize>) -> Vec<T> {
+    values
+        .get(vertices.clone())
+        .map(<[T]>::to_vec)
+        .unwrap_or_default()
+}
+
+fn is_identity(matrix: &[f64; 16]) -> bool {
+    matrix
+        .iter()
+        .enumerate()
+        .all(|(i, value)| *value == if i % 5 == 0 { 1.0 } else { 0.0 })
+}
+
+// matrices are column major, so the translation is in the last four values
+fn transform_mesh(mesh: &mut MeshData, matrix: &[f64; 16]) {
+    if is_identity(matrix) {
+        return;
+    }
+    let m = matrix;
+    for position in mesh.positions.iter_mut() {
+        let [x, y, z] = position.map(f64::from);
+        *position = [
+            (m[0] * x + m[4] * y + m[8] * z + m[12]) as f32,
+            (m[1] * x + m[5] * y + m[9] * z + m[13]) as f32,
+            (m[2] * x + m[6] * y + m[10] * z + m[14]) as f32,
+        ];
+    }
+    for normal in mesh.normals.iter_mut() {
+        let [x, y, z] = normal.map(f64::from);
+        let transformed = [
+            m[0] * x + m[4] * y + m[8] * z,
+            m[1] * x + m[5] * y + m[9] * z,
+            m[2] * x + m[6] * y + m[10] * z,
+        ];
+        let length = transformed.iter().map(|v| v * v).sum::<f64>().sqrt();
+        if length > 0.0 {
+            *normal = transformed.map(|v| (v / length) as f32);
+        }
+    }
+}
+
+/*
+Features of layers before 1.7 reference an inclusive range of triangles of
+the node's geometry, e.g. {"href": "/geometryData/1", "faceRange": [0, 11]}.
+Every referenced range is cut out of the mesh as a MeshData of its own and
+transformed by the referenced geometry and then by the reference.
+*/
+pub fn split_legacy_features(
+    mesh: &MeshData,
+    features: &cmn::FeatureDocument,
+) -> Result<Vec<MeshData>> {
+    let mut meshes = Vec::new();
+    for feature in features.feature_data.iter() {
+        for geometry in feature.geometries.iter() {
+            let cmn::GeometryParams::Reference(reference) = &geometry.params else {
+                return Err(I3sError::MalformedBuffer(format!(
+                    "feature {} has no geometry reference",
+                    feature.id
+                )));
+            };
+            let referenced = features.geometry(&reference.href).ok_or_else(|| {
+                I3sError::MalformedBuffer(format!(
+                    "feature {} references missing geometry {}",
+                    feature.id, reference.href
+                ))
+            })?;
+            let triangles = match reference.face_range.as_deref() {
+                Some([first, last]) if 0 <= *first && first <= last => {
+                    *first as usize..*last as usize + 1
+                }
+                Some(face_range) => {
+                    return Err(I3sError::MalformedBuffer(format!(
+                        "feature {} has an invalid face range {:?}",
+                        feature.id, face_range
+                    )))
+                }
+                None => 0..mesh.vertex_count() / 3,
+            };
+            let vertices = triangles.start * 3..triangles.end * 3;
+            if vertices.end > mesh.vertex_count() {
+                return Err(I3sError::MalformedBuffer(format!(
+                    "face range of feature {} exceeds {} triangles",
+                    feature.id,
+                    mesh.vertex_count() / 3
+                )));
+            }
+            let mut feature_mesh = MeshData {
+                positions: slice_vertices(&mesh.positions, &vertices),
+                normals: slice_vertices(&mesh.normals, &vertices),
+                uv0: slice_vertices(&mesh.uv0, &vertices),
+                colors: slice_vertices(&mesh.colors, &vertices),
+                uv_regions: slice_vertices(&mesh.uv_regions, &vertices),
+                feature_ids: vec![feature.id],
+                face_ranges: match triangles.len() {
+                    0 => Vec::new(),
+                    count => vec![[0, count as u32 - 1]],
+                },
+            };
+            transform_mesh(&mut feature_mesh, &referenced.transformation);
+            transform_mesh(&mut feature_mesh, &geometry.transformation);
+            meshes.push(feature_mesh);
+        }
+    }
+    Ok(meshes)
+}
+
+fn geometry_definition<'a>(
+    geometry_definitions: &'a [cmn::GeometryDefinition],
+    geometry: &cmn::MeshGeometry,
+) -> Result<&'a cmn::GeometryDefinition> {
+    usize::try_from(geometry.definition)
+        .ok()
+        .and_then(|index| geometry_definitions.get(index))
+        .ok_or_else(|| {
+            I3sError::MalformedBuffer(format!(
+                "geometry definition {} does not exist",
+                geometry.definition
+            ))
+        })
+}
+
+pub fn decode_mesh_geometry(
+    buffer: &[u8],
+    geometry_definitions: &[cmn::GeometryDefinition],
+    geometry: &cmn::MeshGeometry,
+) -> Result<MeshData> {
+    let definition = geometry_definition(geometry_definitions, geometry)?
+        .geometry_buffers
+        .first()
+        .ok_or_else(|| I3sError::MalformedBuffer("geometry definition is empty".to_string()))?;
+    decode_geometry_buffer(
+        buffer,
+        definition,
+        geometry.vertex_count,
+        geometry.feature_count,
+    )
+}
+
+#[cfg(feature = "draco")]
+pub fn decode_compressed_mesh_geometry(
+    buffer: &[u8],
+    geometry_definitions: &[cmn::GeometryDefinition],
+    geometry: &cmn::MeshGeometry,
+) -> Result<MeshData> {
+    let compressed = geometry_definition(geometry_definitions, geometry)?
+        .geometry_buffers
+        .iter()
+        .find_map(|buffer| buffer.compressed_attributes.as_ref())
+        .ok_or_else(|| {
+            I3sError::MalformedBuffer("geometry definition has no compressed buffer".to_string())
+        })?;
+    if !compressed.encoding.eq_ignore_ascii_case("draco") {
+        return Err(I3sError::MalformedBuffer(format!(
+            "unsupported geometry encoding: {}",
+            compressed.encoding
+        )));
+    }
+    crate::draco::decode_mesh(buffer)
+}
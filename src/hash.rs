@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use md5::{Digest, Md5};
+
+use crate::error::{I3sError, Result};
+use crate::io;
+
+pub const HASH_TABLE_PATH: &str = "@specialIndexFileHASH128@";
+
+const ENTRY_SIZE: usize = 24;
+
+// the smallest central directory record, without name, extra field or comment
+const MIN_DIRECTORY_RECORD_SIZE: u64 = 46;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HashTableStatus {
+    Missing,
+    Valid { entries: usize },
+    Inconsistent(String),
+}
+
+// the form of a resource path that is hashed
+pub fn normalize(path: &str) -> String {
+    path.replace('\\', "/").to_lowercase()
+}
+
+/*
+The hash table is a flat array of 16 byte MD5 hashes of the lowercase
+resource path followed by the little endian offset of the entry's local file
+header in the package.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct HashTable {
+    offsets: HashMap<[u8; 16], u64>,
+    sorted_offsets: Vec<u64>,
+}
+
+impl HashTable {
+    pub fn parse(buffer: &[u8]) -> Result<Self> {
+        if !buffer.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(I3sError::MalformedBuffer(format!(
+                "hash table of {} bytes is not a multiple of {}",
+                buffer.len(),
+                ENTRY_SIZE
+            )));
+        }
+        let offsets: HashMap<[u8; 16], u64> = buffer
+            .chunks_exact(ENTRY_SIZE)
+            .map(|entry| {
+                (
+                    entry[..16].try_into().unwrap(),
+                    u64::from_le_bytes(entry[16..].try_into().unwrap()),
+                )
+            })
+            .collect();
+        let mut sorted_offsets: Vec<u64> = offsets.values().copied().collect();
+        sorted_offsets.sort_unstable();
+        Ok(Self {
+            offsets,
+            sorted_offsets,
+        })
+    }
+
+    /*
+    Packaging tools write the hash table as the last entry, right before the
+    central directory, so it can be found without reading the directory. It
+    has at most one 24 byte entry per directory record, and is preceded by its
+    local file header.
+    */
+    pub fn search_window(directory_size: u64) -> u64 {
+        directory_size / MIN_DIRECTORY_RECORD_SIZE * ENTRY_SIZE as u64
+            + io::LOCAL_HEADER_SIZE
+            + HASH_TABLE_PATH.len() as u64
+            + u16::MAX as u64
+    }
+
+    /*
+    Finds the hash table in the bytes before the central directory, see
+    search_window. Returns it with the offset of its local file header.
+    */
+    pub fn find_trailing(buffer: &[u8], buffer_start: u64) -> Option<Result<(Self, u64)>> {
+        let entry = io::find_trailing_entry(buffer, buffer_start, HASH_TABLE_PATH)?;
+        let start = (entry.header_start - buffer_start + entry.data_offset()) as usize;
+        let hash_table =
+            io::inflate_zip_entry(&entry, &buffer[start..]).and_then(|table| Self::parse(&table));
+        Some(hash_table.map(|hash_table| (hash_table, entry.header_start)))
+    }
+
+    pub fn hash(path: &str) -> [u8; 16] {
+        Md5::digest(normalize(path).as_bytes()).into()
+    }
+
+    pub fn offset(&self, path: &str) -> Option<u64> {
+        self.offsets.get(&Self::hash(path)).copied()
+    }
+
+    // the offset of the next entry in the package, which bounds the entry at offset
+    pub fn next_offset(&self, offset: u64) -> Option<u64> {
+        let index = self
+            .sorted_offsets
+            .partition_point(|other| *other <= offset);
+        self.sorted_offsets.get(index).copied()
+    }
+
+    // node pages are numbered from 0 without gaps, see i3s::sort_node_page_paths
+    pub fn node_page_paths(&self) -> Vec<String> {
+        (0..)
+            .map(|page| format!("nodepages/{}.json.gz", page))
+            .take_while(|path| self.offset(path).is_some())
+            .collect()
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&[u8; 16], u64)> + '_ {
+        self.offsets.iter().map(|(hash, offset)| (hash, *offset))
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+}
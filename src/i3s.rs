@@ -1,17 +1,31 @@
-use std::error::Error;
-use std::io::Read;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
+use tokio::sync::Semaphore;
 use url::Url;
 
+use crate::attribute;
+use crate::auth;
 use crate::bld;
+use crate::cache;
 use crate::cmn;
+use crate::error::{I3sError, Result};
+use crate::geometry;
+use crate::hash;
 use crate::io;
-use crate::io::ZipFileReadError;
+use crate::lepcc;
+use crate::lod;
 use crate::pcl;
 use crate::psl;
+use crate::texture;
 
 use serde::Deserialize;
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Deserialize, Debug)]
 pub enum I3SInfo {
     IntegratedMesh(cmn::SceneLayerInformation),
@@ -31,147 +45,2113 @@ impl I3SInfo {
             I3SInfo::PointCloud(_) => "PointCloud",
         }
     }
+
+    pub fn last_update(&self) -> Option<u64> {
+        let time_stamp = match self {
+            I3SInfo::IntegratedMesh(info) | I3SInfo::DDDObject(info) => {
+                info.service_update_time_stamp.as_ref()
+            }
+            I3SInfo::Point(info) => info.service_update_time_stamp.as_ref(),
+            I3SInfo::PointCloud(info) => info.service_update_time_stamp.as_ref(),
+            I3SInfo::Building(_) => None,
+        };
+        time_stamp.map(|time_stamp| time_stamp.last_update)
+    }
+}
+
+/*
+Every source of a scene layer implements I3SFormat, which only fetches
+resources, so the loaders and decoders of I3SFormatExt are written once.
+Paths are relative to the layer. Packages and extracted folders store
+resources with extensions and mostly gzipped, e.g. nodes/0/geometries/0.bin.gz,
+while services address the same resource as nodes/0/geometries/0, which is
+what is_local tells the path helpers.
+*/
+pub trait I3SFormat: Send + Sync {
+    fn get(&self, path: &str) -> impl Future<Output = Result<Vec<u8>>> + Send;
+
+    fn is_local(&self) -> bool;
+
+    /*
+    Fetches several resources concurrently. Results are in the order of the
+    paths and a failing path does not abort the others.
+    */
+    fn get_many(&self, paths: &[String]) -> impl Future<Output = Vec<Result<Vec<u8>>>> + Send {
+        futures_util::future::join_all(paths.iter().map(|path| self.get(path)))
+    }
+
+    fn exists(&self, path: &str) -> impl Future<Output = Result<bool>> + Send {
+        async move {
+            match self.get(path).await {
+                Ok(_) => Ok(true),
+                Err(err) if err.is_not_found() => Ok(false),
+                Err(err) => Err(err),
+            }
+        }
+    }
+
+    fn get_resource(&self, path: &str) -> impl Future<Output = Result<Vec<u8>>> + Send {
+        async move { io::decode_gzip_if_compressed(path, &self.get(path).await?) }
+    }
+
+    fn get_json<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+    ) -> impl Future<Output = Result<T>> + Send {
+        async move { io::parse_json::<T>(path, &self.get_resource(path).await?) }
+    }
+
+    /*
+    Returns the first of several candidate paths that exists, which is needed
+    where packages differ in how they name a resource.
+    */
+    fn get_first(&self, paths: &[String]) -> impl Future<Output = Result<Vec<u8>>> + Send {
+        async move {
+            for path in paths.iter() {
+                match self.get_resource(path).await {
+                    Ok(buffer) => return Ok(buffer),
+                    Err(err) if err.is_not_found() => continue,
+                    Err(err) => return Err(err),
+                }
+            }
+            Err(I3sError::ResourceNotFound(paths.join(", ")))
+        }
+    }
+
+    /*
+    The number of node pages, if the source can tell without reading them.
+    Otherwise pages are read until the first one that does not exist.
+    */
+    fn node_page_count(&self) -> impl Future<Output = Result<Option<usize>>> + Send {
+        async { Ok(None) }
+    }
 }
 
-pub trait I3SFormat {}
+/*
+Loaders and decoders on top of any I3SFormat. Services shadow
+scene_layer_information and profile with their own, see Service.
+*/
+pub trait I3SFormatExt: I3SFormat {
+    fn scene_layer_information(&self) -> impl Future<Output = Result<I3SInfo>> + Send {
+        async move {
+            let path = if self.is_local() {
+                "3dSceneLayer.json.gz"
+            } else {
+                ""
+            };
+            unpack_scene_layer_information(path, &self.get_resource(path).await?)
+        }
+    }
+
+    fn read_node_page<T: serde::de::DeserializeOwned>(
+        &self,
+        page: usize,
+    ) -> impl Future<Output = Result<Option<T>>> + Send {
+        async move {
+            let path = if self.is_local() {
+                format!("nodepages/{}.json.gz", page)
+            } else {
+                format!("nodepages/{}", page)
+            };
+            match self.get_json::<T>(&path).await {
+                Ok(node_page) => Ok(Some(node_page)),
+                Err(err) if err.is_not_found() => Ok(None),
+                Err(err) => Err(err),
+            }
+        }
+    }
+
+    fn node_pages(
+        &self,
+        definition: &cmn::NodePageDefinition,
+    ) -> impl Future<Output = Result<Vec<cmn::NodePage>>> + Send {
+        async move {
+            let nodes_per_page = definition.nodes_per_page as usize;
+            let count = self.node_page_count().await?;
+            let mut node_pages = Vec::new();
+            while count.is_none_or(|count| node_pages.len() < count) {
+                let page = node_pages.len();
+                let Some(node_page) = self.read_node_page::<cmn::NodePage>(page).await? else {
+                    if count.is_some() {
+                        return Err(I3sError::ResourceNotFound(format!("nodepages/{}", page)));
+                    }
+                    break;
+                };
+                check_node_page(page, &node_page, nodes_per_page)?;
+                node_pages.push(node_page);
+            }
+            Ok(node_pages)
+        }
+    }
+
+    fn node_index_document(
+        &self,
+        path: &str,
+    ) -> impl Future<Output = Result<cmn::NodeIndexDocument>> + Send {
+        async move {
+            if self.is_local() {
+                self.get_json(&format!("{}/3dNodeIndexDocument.json.gz", path))
+                    .await
+            } else {
+                self.get_json(path).await
+            }
+        }
+    }
+
+    /*
+    Walks the node index documents of a pre 1.7 layer depth first, starting at
+    the store's rootNode, e.g. ./nodes/root. The children of a node are only
+    visited if visit returns true for it, so a walk can stop at a level of
+    detail. A node that is reached twice makes the index a graph rather than a
+    tree, which would otherwise be walked forever.
+    */
+    fn walk_node_index<F>(
+        &self,
+        root_node: &str,
+        mut visit: F,
+    ) -> impl Future<Output = Result<()>> + Send
+    where
+        F: FnMut(&cmn::NodeIndexDocument) -> bool + Send,
+    {
+        async move {
+            let mut paths = vec![cmn::resolve_href("", root_node)];
+            let mut visited = HashSet::new();
+            while let Some(path) = paths.pop() {
+                if !visited.insert(path.clone()) {
+                    return Err(I3sError::MalformedNodePage {
+                        path,
+                        reason: "node is referenced more than once".to_string(),
+                    });
+                }
+                let node = self.node_index_document(&path).await?;
+                if visit(&node) {
+                    let children = node.children.iter().rev();
+                    paths.extend(children.map(|child| node.reference_path(child)));
+                }
+            }
+            Ok(())
+        }
+    }
+
+    // the ids of the nodes to render, see lod::classify_node_index
+    fn select_node_index(
+        &self,
+        root_node: &str,
+        camera: &lod::Camera,
+        quality: f64,
+        frame: lod::Frame,
+    ) -> impl Future<Output = Result<Vec<String>>> + Send {
+        async move {
+            let mut selected = Vec::new();
+            self.walk_node_index(root_node, |node| {
+                match lod::classify_node_index(camera, quality, frame, node) {
+                    lod::Selection::Culled => false,
+                    lod::Selection::Render => {
+                        selected.push(node.id.clone());
+                        false
+                    }
+                    lod::Selection::Refine => true,
+                }
+            })
+            .await?;
+            Ok(selected)
+        }
+    }
+
+    fn point_cloud_node_pages(
+        &self,
+        index: &pcl::Index,
+    ) -> impl Future<Output = Result<Vec<pcl::NodePage>>> + Send {
+        async move {
+            let count = self.node_page_count().await?;
+            let mut node_pages = Vec::new();
+            while count.is_none_or(|count| node_pages.len() < count) {
+                let page = node_pages.len();
+                let Some(node_page) = self.read_node_page::<pcl::NodePage>(page).await? else {
+                    if count.is_some() {
+                        return Err(I3sError::ResourceNotFound(format!("nodepages/{}", page)));
+                    }
+                    break;
+                };
+                if node_page.nodes.len() > index.nodes_per_page {
+                    return Err(I3sError::MalformedNodePage {
+                        path: format!("nodepages/{}", page),
+                        reason: format!(
+                            "{} nodes exceed nodesPerPage of {}",
+                            node_page.nodes.len(),
+                            index.nodes_per_page
+                        ),
+                    });
+                }
+                node_pages.push(node_page);
+            }
+            Ok(node_pages)
+        }
+    }
+
+    fn profile(&self, information: &I3SInfo) -> impl Future<Output = Result<Profile>> + Send {
+        async move {
+            if let Some(node_index) = NodeIndex::from_information(information) {
+                return Ok(Profile::NodeIndex(node_index));
+            }
+            match information {
+                I3SInfo::IntegratedMesh(info) => {
+                    let node_pages = self.node_pages(&info.node_pages).await?;
+                    Ok(Profile::IntegratedMesh(IntegratedMesh::new(
+                        node_pages,
+                        &info.node_pages,
+                    )))
+                }
+                I3SInfo::DDDObject(info) => {
+                    let node_pages = self.node_pages(&info.node_pages).await?;
+                    Ok(Profile::DDDObject(DDDObject::new(
+                        node_pages,
+                        &info.node_pages,
+                    )))
+                }
+                I3SInfo::Point(info) => {
+                    let definition = info.point_node_pages.clone().unwrap_or_default();
+                    let node_pages = self.node_pages(&definition).await?;
+                    Ok(Profile::Point(Point::new(node_pages, &definition)))
+                }
+                I3SInfo::PointCloud(info) => {
+                    let node_pages = self.point_cloud_node_pages(&info.store.index).await?;
+                    Ok(Profile::PointCloud(PointCloud::new(
+                        node_pages,
+                        &info.store.index,
+                    )))
+                }
+                I3SInfo::Building(_) => Ok(Profile::new(information)),
+            }
+        }
+    }
+
+    fn geometry(
+        &self,
+        information: &cmn::SceneLayerInformation,
+        node: &cmn::Node,
+    ) -> impl Future<Output = Result<Option<geometry::MeshData>>> + Send {
+        async move {
+            let Some(mesh_geometry) = node.mesh.as_ref().and_then(|mesh| mesh.geometry.as_ref())
+            else {
+                return Ok(None);
+            };
+            let Some(path) = mesh_geometry.resource_path(false, self.is_local()) else {
+                return Ok(None);
+            };
+            let buffer = self.get_resource(&path).await?;
+            geometry::decode_mesh_geometry(
+                &buffer,
+                &information.geometry_definitions,
+                mesh_geometry,
+            )
+            .map(Some)
+        }
+    }
+
+    // the geometry of a node of a layer before 1.7, see walk_node_index
+    fn legacy_geometry(
+        &self,
+        information: &cmn::SceneLayerInformation,
+        node: &cmn::NodeIndexDocument,
+    ) -> impl Future<Output = Result<Option<geometry::MeshData>>> + Send {
+        async move {
+            let (Some(schema), Some(resource)) = (
+                information.store.default_geometry_schema.as_ref(),
+                node.geometry_data.first(),
+            ) else {
+                return Ok(None);
+            };
+            let mut path = node.resolve(&resource.href);
+            if self.is_local() {
+                path.push_str(".bin.gz");
+            }
+            let buffer = self.get_resource(&path).await?;
+            geometry::decode_legacy_geometry_buffer(&buffer, schema).map(Some)
+        }
+    }
+
+    fn legacy_features(
+        &self,
+        node: &cmn::NodeIndexDocument,
+    ) -> impl Future<Output = Result<Option<cmn::FeatureDocument>>> + Send {
+        async move {
+            let Some(resource) = node.feature_data.first() else {
+                return Ok(None);
+            };
+            let mut path = node.resolve(&resource.href);
+            if self.is_local() {
+                path.push_str(".json.gz");
+            }
+            self.get_json(&path).await.map(Some)
+        }
+    }
+
+    // one mesh per geometry of every feature of the node, see split_legacy_features
+    fn legacy_feature_geometries(
+        &self,
+        information: &cmn::SceneLayerInformation,
+        node: &cmn::NodeIndexDocument,
+    ) -> impl Future<Output = Result<Vec<geometry::MeshData>>> + Send {
+        async move {
+            let Some(mesh) = self.legacy_geometry(information, node).await? else {
+                return Ok(Vec::new());
+            };
+            let Some(features) = self.legacy_features(node).await? else {
+                return Ok(Vec::new());
+            };
+            geometry::split_legacy_features(&mesh, &features)
+        }
+    }
+
+    fn shared_resource(
+        &self,
+        node: &cmn::NodeIndexDocument,
+    ) -> impl Future<Output = Result<Option<cmn::SharedResource>>> + Send {
+        async move {
+            let Some(resource) = node.shared_resource.as_ref() else {
+                return Ok(None);
+            };
+            let path = node.resolve(&resource.href);
+            if self.is_local() {
+                self.get_json(&format!("{}/sharedResource.json.gz", path))
+                    .await
+                    .map(Some)
+            } else {
+                self.get_json(&path).await.map(Some)
+            }
+        }
+    }
+
+    fn legacy_material(
+        &self,
+        node: &cmn::NodeIndexDocument,
+        component: &cmn::SingleComponentParams,
+        preferences: &[texture::TextureFormat],
+    ) -> impl Future<Output = Result<Option<texture::LegacyMaterial>>> + Send {
+        async move {
+            let (Some(resource), Some(shared)) = (
+                node.shared_resource.as_ref(),
+                self.shared_resource(node).await?,
+            ) else {
+                return Ok(None);
+            };
+            Ok(texture::resolve_legacy_material(
+                &shared,
+                &node.resolve(&resource.href),
+                component,
+                preferences,
+                self.is_local(),
+            ))
+        }
+    }
+
+    fn legacy_texture(
+        &self,
+        material: &texture::LegacyMaterial,
+    ) -> impl Future<Output = Result<Option<texture::Texture>>> + Send {
+        async move {
+            let Some(legacy) = material.texture.as_ref() else {
+                return Ok(None);
+            };
+            let data = self.get_first(&legacy.paths).await?;
+            texture::Texture::new(legacy.format, data).map(Some)
+        }
+    }
+
+    #[cfg(feature = "draco")]
+    fn compressed_geometry(
+        &self,
+        information: &cmn::SceneLayerInformation,
+        node: &cmn::Node,
+    ) -> impl Future<Output = Result<Option<geometry::MeshData>>> + Send {
+        async move {
+            let Some(mesh_geometry) = node.mesh.as_ref().and_then(|mesh| mesh.geometry.as_ref())
+            else {
+                return Ok(None);
+            };
+            let Some(path) = mesh_geometry.resource_path(true, self.is_local()) else {
+                return Ok(None);
+            };
+            let buffer = self.get_resource(&path).await?;
+            geometry::decode_compressed_mesh_geometry(
+                &buffer,
+                &information.geometry_definitions,
+                mesh_geometry,
+            )
+            .map(Some)
+        }
+    }
+
+    fn attributes(
+        &self,
+        information: &cmn::SceneLayerInformation,
+        node: &cmn::Node,
+        feature_ids: &[u64],
+    ) -> impl Future<Output = Result<Option<attribute::AttributeTable>>> + Send {
+        async move {
+            let (Some(mesh_attribute), Some(storage_info)) = (
+                node.mesh.as_ref().and_then(|mesh| mesh.attribute.as_ref()),
+                information.attribute_storage_info.as_ref(),
+            ) else {
+                return Ok(None);
+            };
+            let mut attributes = Vec::with_capacity(storage_info.len());
+            for info in storage_info.iter() {
+                let Some(path) = mesh_attribute.attribute_resource_path(&info.key, self.is_local())
+                else {
+                    return Ok(None);
+                };
+                let buffer = self.get_resource(&path).await?;
+                attributes.push(attribute::Attribute {
+                    key: info.key.clone(),
+                    name: info.name.clone(),
+                    values: attribute::decode_attribute_buffer(info, &buffer)?,
+                });
+            }
+            attribute::AttributeTable::new(feature_ids.to_vec(), attributes).map(Some)
+        }
+    }
+
+    fn texture(
+        &self,
+        information: &cmn::SceneLayerInformation,
+        node: &cmn::Node,
+        preferences: &[texture::TextureFormat],
+    ) -> impl Future<Output = Result<Option<texture::Texture>>> + Send {
+        async move {
+            let Some((format, paths)) =
+                texture::texture_paths(information, node, preferences, self.is_local())
+            else {
+                return Ok(None);
+            };
+            let data = self.get_first(&paths).await?;
+            texture::Texture::new(format, data).map(Some)
+        }
+    }
+
+    fn points(
+        &self,
+        information: &pcl::SceneLayerInformation,
+        node: &pcl::Node,
+    ) -> impl Future<Output = Result<geometry::PointData>> + Send {
+        async move {
+            let local = self.is_local();
+            let encoding = &information.store.default_geometry_schema.encoding;
+            let xyz = self
+                .get_resource(&node.geometry_resource_path(encoding, local))
+                .await?;
+            let (rgb, intensity) = lepcc_attributes(information);
+            let rgb = match rgb {
+                Some(attribute) => Some(
+                    self.get_resource(&node.attribute_resource_path(attribute, local))
+                        .await?,
+                ),
+                None => None,
+            };
+            let intensity = match intensity {
+                Some(attribute) => Some(
+                    self.get_resource(&node.attribute_resource_path(attribute, local))
+                        .await?,
+                ),
+                None => None,
+            };
+            lepcc::decode_points(&xyz, rgb.as_deref(), intensity.as_deref())
+        }
+    }
+
+    fn point_attributes(
+        &self,
+        information: &pcl::SceneLayerInformation,
+        node: &pcl::Node,
+    ) -> impl Future<Output = Result<HashMap<String, geometry::PointAttribute>>> + Send {
+        async move {
+            let local = self.is_local();
+            let encoding = &information.store.default_geometry_schema.encoding;
+            let mut attributes = HashMap::new();
+            for attribute in information.attribute_storage_info.iter() {
+                let decoded = if is_embedded_elevation(attribute) {
+                    let xyz = self
+                        .get_resource(&node.geometry_resource_path(encoding, local))
+                        .await?;
+                    geometry::PointAttribute::elevation(&lepcc::decode_xyz(&xyz)?)
+                } else {
+                    let buffer = self
+                        .get_resource(&node.attribute_resource_path(attribute, local))
+                        .await?;
+                    geometry::decode_point_attribute(attribute, &buffer)?
+                };
+                attributes.insert(attribute.name.clone(), decoded);
+            }
+            Ok(attributes)
+        }
+    }
+}
+
+impl<F: I3SFormat + ?Sized> I3SFormatExt for F {}
+
 pub trait I3SProfile {}
 
-fn unpack_scene_layer_information(buffer: &[u8]) -> Result<I3SInfo, Box<dyn Error>> {
-    let scene_layer_info = serde_json::from_slice::<serde_json::Value>(buffer)?;
-    let layer_type = get_layer_type(&scene_layer_info)?;
+fn unpack_scene_layer_information(path: &str, buffer: &[u8]) -> Result<I3SInfo> {
+    let scene_layer_info = io::parse_json::<serde_json::Value>(path, buffer)?;
+    let layer_type = get_layer_type(&scene_layer_info)?.to_string();
 
-    match layer_type {
+    match layer_type.as_str() {
         "IntegratedMesh" => {
-            let parsed = serde_json::from_value::<cmn::SceneLayerInformation>(scene_layer_info)?;
+            let parsed = io::parse_json::<cmn::SceneLayerInformation>(path, buffer)?;
             Ok(I3SInfo::IntegratedMesh(parsed))
         }
-        "Point" => {
-            let parsed = serde_json::from_value::<psl::SceneLayerInformation>(scene_layer_info)?;
-            Ok(I3SInfo::Point(parsed))
+        "Point" => {
+            let parsed = io::parse_json::<psl::SceneLayerInformation>(path, buffer)?;
+            Ok(I3SInfo::Point(parsed))
+        }
+        "Building" => {
+            let parsed = io::parse_json::<bld::SceneLayerInformation>(path, buffer)?;
+            Ok(I3SInfo::Building(parsed))
+        }
+        "PointCloud" => {
+            let parsed = io::parse_json::<pcl::SceneLayerInformation>(path, buffer)?;
+            Ok(I3SInfo::PointCloud(parsed))
+        }
+        "3DObject" => {
+            let parsed = io::parse_json::<cmn::SceneLayerInformation>(path, buffer)?;
+            Ok(I3SInfo::DDDObject(parsed))
+        }
+        _ => Err(I3sError::UnsupportedLayerType(layer_type)),
+    }
+}
+
+/*
+Layers without node pages, e.g. those before 1.7, have a nodesPerPage of 0,
+so their nodes have no page to be looked up on.
+*/
+fn get_node_page_index(node_index: &usize, nodes_per_page: &usize) -> Option<usize> {
+    node_index.checked_div(*nodes_per_page)
+}
+
+fn get_node_index_in_node_page(node_index: &usize, nodes_per_page: &usize) -> Option<usize> {
+    node_index.checked_rem(*nodes_per_page)
+}
+
+pub fn get_layer_type(scene_layer_info: &serde_json::Value) -> Result<&str> {
+    scene_layer_info
+        .get("layerType")
+        .and_then(|layer_type| layer_type.as_str())
+        .ok_or(I3sError::MissingLayerType)
+}
+#[derive(Debug)]
+pub struct Service {
+    pub base: Url,
+    pub layer: usize,
+    client: reqwest::Client,
+    node_pages: Mutex<HashMap<usize, Arc<cmn::NodePage>>>,
+    point_cloud_node_pages: Mutex<HashMap<usize, Arc<pcl::NodePage>>>,
+    node_page_definition: OnceLock<(usize, usize)>,
+    requests: Arc<Semaphore>,
+    timeout: Option<Duration>,
+    max_retries: u32,
+    backoff: Duration,
+    cache: Option<cache::DiskCache>,
+    cache_validated: AtomicBool,
+    authenticator: Arc<auth::Authenticator>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ServiceBuilder {
+    base: Url,
+    layer: usize,
+    client: Option<reqwest::Client>,
+    timeout: Option<Duration>,
+    max_concurrent_requests: usize,
+    max_retries: u32,
+    backoff: Duration,
+    cache: Option<cache::DiskCache>,
+    authentication: auth::Authentication,
+}
+
+impl ServiceBuilder {
+    pub fn new(mut base: Url) -> Self {
+        // paths are joined onto the base, which replaces its last segment without a trailing slash
+        if !base.path().ends_with('/') {
+            let path = format!("{}/", base.path());
+            base.set_path(&path);
+        }
+        Self {
+            base,
+            layer: 0,
+            client: None,
+            timeout: Some(Duration::from_secs(30)),
+            max_concurrent_requests: 8,
+            max_retries: 3,
+            backoff: Duration::from_millis(500),
+            cache: None,
+            authentication: auth::Authentication::default(),
+        }
+    }
+
+    pub fn layer(mut self, layer: usize) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn max_concurrent_requests(mut self, max_concurrent_requests: usize) -> Self {
+        self.max_concurrent_requests = max_concurrent_requests.max(1);
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    // the delay before the first retry, which doubles with every further retry
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn cache<P: AsRef<Path>>(mut self, directory: P) -> Self {
+        self.cache = Some(cache::DiskCache::new(directory));
+        self
+    }
+
+    pub fn authentication(mut self, authentication: auth::Authentication) -> Self {
+        self.authentication = authentication;
+        self
+    }
+
+    pub fn build(self) -> Service {
+        Service {
+            base: self.base,
+            layer: self.layer,
+            client: self.client.unwrap_or_default(),
+            node_pages: Mutex::default(),
+            point_cloud_node_pages: Mutex::default(),
+            node_page_definition: OnceLock::new(),
+            requests: Arc::new(Semaphore::new(self.max_concurrent_requests)),
+            timeout: self.timeout,
+            max_retries: self.max_retries,
+            backoff: self.backoff,
+            cache: self.cache,
+            cache_validated: AtomicBool::new(false),
+            authenticator: Arc::new(auth::Authenticator::new(self.authentication)),
+        }
+    }
+}
+
+impl I3SFormat for Service {
+    async fn get(&self, path: &str) -> Result<Vec<u8>> {
+        self.fetch(&self.layer_path(path)).await
+    }
+
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+impl Service {
+    pub fn connect(base: Url) -> Self {
+        ServiceBuilder::new(base).build()
+    }
+
+    pub fn builder(base: Url) -> ServiceBuilder {
+        ServiceBuilder::new(base)
+    }
+
+    /*
+    Every Service must have scene layer information at layers/{id}, so we return an error
+    if the file is not found. Something is either wrong with the service or the code.
+    */
+    pub async fn scene_layer_information(&self) -> Result<I3SInfo> {
+        let path = self.layer_path("");
+        let buffer = io::decode_gzip_if_compressed(&path, &self.fetch(&path).await?)?;
+        let information = unpack_scene_layer_information(&path, &buffer)?;
+        let definition = match &information {
+            I3SInfo::IntegratedMesh(info) | I3SInfo::DDDObject(info) => (
+                info.node_pages.nodes_per_page as usize,
+                info.node_pages.root_index,
+            ),
+            I3SInfo::Point(info) => info
+                .point_node_pages
+                .as_ref()
+                .map(|pages| (pages.nodes_per_page as usize, pages.root_index))
+                .unwrap_or_default(),
+            I3SInfo::PointCloud(info) => (info.store.index.nodes_per_page, 0),
+            I3SInfo::Building(_) => (0, 0),
+        };
+        let _ = self.node_page_definition.set(definition);
+        self.validate_cache(information.last_update())?;
+        Ok(information)
+    }
+
+    /*
+    Services can have millions of nodes, so nodes are fetched page by page
+    when they are accessed instead of up front.
+    */
+    pub async fn profile(&self, information: &I3SInfo) -> Result<Profile> {
+        Ok(Profile::new(information))
+    }
+
+    /*
+    Another layer of the same SceneServer, e.g. a building sublayer. It shares
+    the client, request limit, credentials and cache with this one.
+    */
+    pub fn with_layer(&self, layer: usize) -> Self {
+        Service {
+            base: self.base.clone(),
+            layer,
+            client: self.client.clone(),
+            node_pages: Mutex::default(),
+            point_cloud_node_pages: Mutex::default(),
+            node_page_definition: OnceLock::new(),
+            requests: self.requests.clone(),
+            timeout: self.timeout,
+            max_retries: self.max_retries,
+            backoff: self.backoff,
+            cache: self.cache.clone(),
+            cache_validated: AtomicBool::new(false),
+            authenticator: self.authenticator.clone(),
+        }
+    }
+
+    pub async fn service_information(&self) -> Result<cmn::SceneServiceInformation> {
+        let buffer = io::decode_gzip_if_compressed("", &self.fetch("").await?)?;
+        io::parse_json::<cmn::SceneServiceInformation>(self.base.as_str(), &buffer)
+    }
+
+    pub async fn layers(&self) -> Result<Vec<cmn::ServiceLayer>> {
+        Ok(self.service_information().await?.layers)
+    }
+
+    // layers are matched by id first and then by name or alias
+    pub async fn find_layer(&self, layer: &str) -> Result<cmn::ServiceLayer> {
+        let layers = self.layers().await?;
+        let found = match layer.parse::<usize>() {
+            Ok(id) => layers.iter().position(|candidate| candidate.id == id),
+            Err(_) => None,
+        }
+        .or_else(|| {
+            layers.iter().position(|candidate| {
+                candidate.name == layer || candidate.alias.as_deref() == Some(layer)
+            })
+        });
+        match found {
+            Some(index) => Ok(layers[index].clone()),
+            None => Err(I3sError::ResourceNotFound(format!(
+                "layer {} of {}",
+                layer, self.base
+            ))),
+        }
+    }
+
+    pub async fn open_layer(&self, layer: &str) -> Result<SceneLayer<Format, Profile>> {
+        let layer = self.find_layer(layer).await?;
+        open_format(Format::Service(self.with_layer(layer.id))).await
+    }
+
+    fn layer_path(&self, path: &str) -> String {
+        match path {
+            "" => format!("layers/{}", self.layer),
+            _ => format!("layers/{}/{}", self.layer, path),
+        }
+    }
+
+    async fn node_page_definition(&self) -> Result<(usize, usize)> {
+        if let Some(definition) = self.node_page_definition.get() {
+            return Ok(*definition);
+        }
+        self.scene_layer_information().await?;
+        Ok(self.node_page_definition.get().copied().unwrap_or_default())
+    }
+
+    // the node page of a node and its position on that page
+    async fn node_position(&self, index: &usize) -> Result<(usize, usize)> {
+        let (nodes_per_page, _) = self.node_page_definition().await?;
+        get_node_page_index(index, &nodes_per_page)
+            .zip(get_node_index_in_node_page(index, &nodes_per_page))
+            .ok_or_else(|| I3sError::MalformedNodePage {
+                path: self.layer_path("nodepages"),
+                reason: "layer does not define nodesPerPage".to_string(),
+            })
+    }
+
+    /*
+    Node pages are only requested the first time a node on them is accessed,
+    so services with millions of nodes can be walked without downloading
+    every page up front. Pages are checked like those of packages.
+    */
+    pub async fn node_page(&self, page: usize) -> Result<Arc<cmn::NodePage>> {
+        let cached = lock(&self.node_pages).get(&page).cloned();
+        if let Some(node_page) = cached {
+            return Ok(node_page);
+        }
+        let path = format!("nodepages/{}", page);
+        let node_page = self.get_json::<cmn::NodePage>(&path).await?;
+        self.insert_node_page(page, node_page).await
+    }
+
+    async fn insert_node_page(
+        &self,
+        page: usize,
+        node_page: cmn::NodePage,
+    ) -> Result<Arc<cmn::NodePage>> {
+        let (nodes_per_page, _) = self.node_page_definition().await?;
+        check_node_page(page, &node_page, nodes_per_page).map_err(|err| match err {
+            I3sError::MalformedNodePage { path, reason } => I3sError::MalformedNodePage {
+                path: self.layer_path(&path),
+                reason,
+            },
+            err => err,
+        })?;
+        let node_page = Arc::new(node_page);
+        lock(&self.node_pages).insert(page, node_page.clone());
+        Ok(node_page)
+    }
+
+    fn cached_node(&self, index: &usize, (page, position): (usize, usize)) -> Result<cmn::Node> {
+        lock(&self.node_pages)
+            .get(&page)
+            .and_then(|node_page| node_page.nodes.get(position))
+            .cloned()
+            .ok_or_else(|| I3sError::MalformedNodePage {
+                path: self.layer_path(&format!("nodepages/{}", page)),
+                reason: format!("node {} is not on its node page", index),
+            })
+    }
+
+    pub async fn node(&self, index: &usize) -> Result<cmn::Node> {
+        let (page, position) = self.node_position(index).await?;
+        self.node_page(page).await?;
+        self.cached_node(index, (page, position))
+    }
+
+    /*
+    Missing node pages are fetched concurrently, bounded by the maximum
+    number of concurrent requests of the service.
+    */
+    pub async fn nodes(&self, indices: &[usize]) -> Result<Vec<cmn::Node>> {
+        let mut positions = Vec::with_capacity(indices.len());
+        for index in indices {
+            positions.push(self.node_position(index).await?);
+        }
+        let mut pages: Vec<usize> = {
+            let node_pages = lock(&self.node_pages);
+            positions
+                .iter()
+                .map(|(page, _)| *page)
+                .filter(|page| !node_pages.contains_key(page))
+                .collect()
+        };
+        pages.sort_unstable();
+        pages.dedup();
+        let paths: Vec<String> = pages
+            .iter()
+            .map(|page| format!("nodepages/{}", page))
+            .collect();
+        let buffers = self.get_many(&paths).await;
+        for ((page, path), buffer) in pages.into_iter().zip(paths.iter()).zip(buffers) {
+            let buffer = io::decode_gzip_if_compressed(path, &buffer?)?;
+            let node_page = io::parse_json::<cmn::NodePage>(path, &buffer)?;
+            self.insert_node_page(page, node_page).await?;
+        }
+        indices
+            .iter()
+            .zip(positions)
+            .map(|(index, position)| self.cached_node(index, position))
+            .collect()
+    }
+
+    pub async fn root(&self) -> Result<cmn::Node> {
+        let (_, root_index) = self.node_page_definition().await?;
+        self.node(&root_index).await
+    }
+
+    pub async fn point_cloud_node_page(&self, page: usize) -> Result<Arc<pcl::NodePage>> {
+        let cached = lock(&self.point_cloud_node_pages).get(&page).cloned();
+        if let Some(node_page) = cached {
+            return Ok(node_page);
+        }
+        let path = format!("nodepages/{}", page);
+        let node_page = Arc::new(self.get_json::<pcl::NodePage>(&path).await?);
+        lock(&self.point_cloud_node_pages).insert(page, node_page.clone());
+        Ok(node_page)
+    }
+
+    pub async fn point_cloud_node(&self, index: &usize) -> Result<pcl::Node> {
+        let (page, position) = self.node_position(index).await?;
+        let path = self.layer_path(&format!("nodepages/{}", page));
+        self.point_cloud_node_page(page)
+            .await?
+            .nodes
+            .get(position)
+            .cloned()
+            .ok_or_else(|| I3sError::MalformedNodePage {
+                path,
+                reason: format!("node {} is not on its node page", index),
+            })
+    }
+
+    fn layer_url(&self) -> String {
+        self.base
+            .join(&self.layer_path(""))
+            .map_or_else(|_| self.base.to_string(), |url| url.to_string())
+    }
+
+    /*
+    Cached resources of a layer are only trusted without asking the server
+    once the layer's last update is known to be unchanged. A changed last
+    update drops everything cached for the layer.
+    */
+    fn validate_cache(&self, last_update: Option<u64>) -> Result<()> {
+        let (Some(cache), Some(last_update)) = (&self.cache, last_update) else {
+            return Ok(());
+        };
+        let layer_url = self.layer_url();
+        if cache.last_update(&layer_url) != Some(last_update) {
+            cache.invalidate(&layer_url)?;
+            cache.set_last_update(&layer_url, last_update)?;
+        }
+        self.cache_validated.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /*
+    Without a validated layer, cached responses are revalidated with their
+    ETag or Last-Modified, so an unchanged resource costs a 304 instead of a
+    download. The root and layer documents are always revalidated.
+    */
+    async fn fetch(&self, path: &str) -> Result<Vec<u8>> {
+        let url = match path {
+            "" => self.base.clone(),
+            _ => self.base.join(path)?,
+        };
+        let Some(cache) = &self.cache else {
+            let response = self.fetch_with_retries(&url, None).await?;
+            return Ok(response.unwrap_or_default().data);
+        };
+        let layer_url = self.layer_url();
+        let cached = cache.read(&layer_url, url.as_str());
+        let trusted = self.cache_validated.load(Ordering::Relaxed)
+            && !path.is_empty()
+            && path != self.layer_path("");
+        if let Some(cached) = cached.as_ref().filter(|_| trusted) {
+            return Ok(cached.data.clone());
+        }
+        match self.fetch_with_retries(&url, cached.as_ref()).await? {
+            Some(response) => {
+                // the cache is best effort, a full disk should not fail the request
+                let _ = cache.write(&layer_url, url.as_str(), &response);
+                Ok(response.data)
+            }
+            None => Ok(cached.unwrap_or_default().data),
+        }
+    }
+
+    async fn fetch_with_retries(
+        &self,
+        url: &Url,
+        cached: Option<&cache::CachedResponse>,
+    ) -> Result<Option<cache::CachedResponse>> {
+        self.send_with_retries(|token| self.fetch_once(url, cached, token))
+            .await
+    }
+
+    /*
+    Rate limited (429) and failed (5xx) requests as well as timeouts and
+    connection errors are retried with exponential backoff. Requests only
+    hold a permit while they are in flight, not while they wait for a retry.
+    */
+    async fn send_with_retries<T, F, Fut>(&self, send: F) -> Result<T>
+    where
+        F: Fn(Option<String>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        let mut refreshed = false;
+        loop {
+            let token = self.authenticator.token().await?;
+            let result = {
+                let _permit = self.requests.acquire().await.ok();
+                send(token.clone()).await
+            };
+            match result {
+                // an expired token is refreshed once, which does not count as a retry
+                Err(err)
+                    if !refreshed && self.authenticator.can_refresh() && is_token_error(&err) =>
+                {
+                    self.authenticator.refresh(token.as_deref()).await?;
+                    refreshed = true;
+                }
+                Err(err) if attempt < self.max_retries && is_retryable(&err) => {
+                    tokio::time::sleep(self.backoff * 2u32.saturating_pow(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    // a request with the timeout and authentication of the service
+    fn request(&self, url: &Url, token: Option<&str>) -> reqwest::RequestBuilder {
+        let mut request = self.client.get(url.clone());
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        if let Some(token) = token {
+            request = match self.authenticator.token_in_query() {
+                true => request.query(&[("token", token)]),
+                false => request.header("X-Esri-Authorization", format!("Bearer {}", token)),
+            };
+        }
+        for (name, value) in self.authenticator.headers() {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        request
+    }
+
+    // reqwest errors include the url, which holds the token if it is sent in the query
+    fn request_error(&self, err: reqwest::Error) -> I3sError {
+        match self.authenticator.token_in_query() {
+            true => I3sError::Request(err.without_url()),
+            false => I3sError::Request(err),
+        }
+    }
+
+    async fn fetch_once(
+        &self,
+        url: &Url,
+        cached: Option<&cache::CachedResponse>,
+        token: Option<String>,
+    ) -> Result<Option<cache::CachedResponse>> {
+        let mut request = self.request(url, token.as_deref());
+        if let Some(etag) = cached.and_then(|cached| cached.etag.as_ref()) {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = cached.and_then(|cached| cached.last_modified.as_ref()) {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+        let resp = request
+            .send()
+            .await
+            .map_err(|err| self.request_error(err))?;
+        let status = resp.status();
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok())
+                .map(|value| value.to_string())
+        };
+        let etag = header(reqwest::header::ETAG);
+        let last_modified = header(reqwest::header::LAST_MODIFIED);
+        if status == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Err(I3sError::ResourceNotFound(url.to_string()));
+        }
+        if !status.is_success() {
+            return Err(I3sError::HttpStatus {
+                path: url.to_string(),
+                status: status.as_u16(),
+            });
+        }
+        let data = resp
+            .bytes()
+            .await
+            .map_err(|err| self.request_error(err))?
+            .to_vec();
+        match service_error_code(&data) {
+            Some(404) => Err(I3sError::ResourceNotFound(url.to_string())),
+            Some(status) => Err(I3sError::HttpStatus {
+                path: url.to_string(),
+                status,
+            }),
+            None => Ok(Some(cache::CachedResponse {
+                data,
+                etag,
+                last_modified,
+            })),
+        }
+    }
+}
+
+// 498 is an invalid or expired token and 499 a missing one
+fn is_token_error(err: &I3sError) -> bool {
+    matches!(
+        err,
+        I3sError::HttpStatus {
+            status: 498 | 499,
+            ..
+        }
+    )
+}
+
+fn is_retryable(err: &I3sError) -> bool {
+    match err {
+        I3sError::HttpStatus { status, .. } => *status == 429 || (500..600).contains(status),
+        I3sError::Request(err) => err.is_timeout() || err.is_connect(),
+        _ => false,
+    }
+}
+
+/*
+ArcGIS services report some errors with a 200 status and a JSON body like
+{"error":{"code":404,"message":"..."}} instead of the resource.
+*/
+fn service_error_code(buffer: &[u8]) -> Option<u16> {
+    let start = buffer.iter().position(|byte| !byte.is_ascii_whitespace())?;
+    if !buffer[start..].starts_with(b"{\"error\"") {
+        return None;
+    }
+    let body = serde_json::from_slice::<serde_json::Value>(buffer).ok()?;
+    let code = body.get("error")?.get("code")?.as_u64()?;
+    u16::try_from(code).ok()
+}
+
+// poisoning is ignored, a panic in another thread must not make a store unusable
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/*
+Packages are usually files, but any seekable reader works, e.g. a
+Cursor<Vec<u8>> for packages that are kept in memory.
+*/
+#[derive(Debug)]
+pub struct SceneLayerPackage<R = std::fs::File> {
+    archive: Mutex<Archive<R>>,
+    size: u64,
+    hash_table: Option<hash::HashTable>,
+    hash_table_status: hash::HashTableStatus,
+    mismatched: AtomicUsize,
+    map: Option<memmap2::Mmap>,
+    mapped_entries: OnceLock<MappedEntries>,
+    entry_names: OnceLock<HashMap<String, usize>>,
+}
+
+/*
+Packages with a hash table are read straight from the package reader, and the
+zip central directory is only read once a resource has to be looked up there.
+From then on the reader belongs to the zip archive.
+*/
+#[derive(Debug)]
+enum Archive<R> {
+    Reader(R),
+    Zip(zip::ZipArchive<R>),
+    Unreadable,
+}
+
+/*
+Entry locations resolved once for a memory mapped package. Stored and
+deflated entries are read straight from the map without locking the archive,
+so many threads can read resources at the same time. Anything else, e.g.
+encrypted entries, goes through the zip reader.
+*/
+#[derive(Debug)]
+struct MappedEntries {
+    entries: Vec<Option<MappedEntry>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MappedEntry {
+    start: usize,
+    size: usize,
+    deflated: bool,
+}
+
+impl MappedEntries {
+    fn read(&self, map: &[u8], index: usize) -> Option<Result<Vec<u8>>> {
+        let entry = self.entries.get(index).copied().flatten()?;
+        let data = &map[entry.start..entry.start + entry.size];
+        if !entry.deflated {
+            return Some(Ok(data.to_vec()));
+        }
+        let mut buffer = Vec::new();
+        let result = flate2::read::DeflateDecoder::new(data).read_to_end(&mut buffer);
+        Some(result.map(|_| buffer).map_err(I3sError::from))
+    }
+}
+
+fn map_entries<R: Read + Seek>(
+    zip_archive: &mut zip::ZipArchive<R>,
+    map_size: usize,
+) -> Result<MappedEntries> {
+    let mut entries = Vec::with_capacity(zip_archive.len());
+    for index in 0..zip_archive.len() {
+        let zip_file = zip_archive.by_index_raw(index)?;
+        let deflated = match zip_file.compression() {
+            zip::CompressionMethod::Stored => false,
+            zip::CompressionMethod::Deflated => true,
+            _ => {
+                entries.push(None);
+                continue;
+            }
+        };
+        let start = zip_file.data_start() as usize;
+        let size = zip_file.compressed_size() as usize;
+        let in_bounds = start.checked_add(size).is_some_and(|end| end <= map_size);
+        entries.push((in_bounds && !zip_file.encrypted()).then_some(MappedEntry {
+            start,
+            size,
+            deflated,
+        }));
+    }
+    Ok(MappedEntries { entries })
+}
+
+/*
+Entry indices by the path the hash table hashes, so that resources are found
+regardless of case and separators whether or not the package has a hash table.
+The first of several entries with the same path wins.
+*/
+fn entry_names<R: Read + Seek>(zip_archive: &zip::ZipArchive<R>) -> HashMap<String, usize> {
+    let mut names = HashMap::with_capacity(zip_archive.len());
+    for (index, name) in zip_archive.file_names().enumerate() {
+        names.entry(hash::normalize(name)).or_insert(index);
+    }
+    names
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, start: u64, length: u64) -> Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(start))?;
+    let mut buffer = Vec::new();
+    reader.by_ref().take(length).read_to_end(&mut buffer)?;
+    if (buffer.len() as u64) < length {
+        return Err(I3sError::MalformedBuffer(format!(
+            "zip: package ends before byte {}",
+            start + length
+        )));
+    }
+    Ok(buffer)
+}
+
+/*
+Reads the hash table if it is the last entry of the package. Anything
+unexpected, including a package that is not a zip file, leaves it to the zip
+archive to report.
+*/
+fn read_trailing_hash_table<R: Read + Seek>(
+    reader: &mut R,
+    size: u64,
+) -> Option<Result<hash::HashTable>> {
+    let tail_start = size.saturating_sub(io::ZIP_TAIL_SIZE);
+    let tail = read_at(reader, tail_start, size - tail_start).ok()?;
+    let (offset, directory_size) = io::find_central_directory(&tail, tail_start).ok()?;
+    let window = hash::HashTable::search_window(directory_size).min(offset);
+    let buffer = read_at(reader, offset - window, window).ok()?;
+    hash::HashTable::find_trailing(&buffer, offset - window)
+        .map(|result| result.map(|(hash_table, _)| hash_table))
+}
+
+impl<R: Read + Seek + Send> I3SFormat for SceneLayerPackage<R> {
+    async fn get(&self, path: &str) -> Result<Vec<u8>> {
+        SceneLayerPackage::get(self, path)
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool> {
+        self.contains(path)
+    }
+
+    async fn node_page_count(&self) -> Result<Option<usize>> {
+        self.node_page_paths().map(|paths| Some(paths.len()))
+    }
+}
+
+impl SceneLayerPackage {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_reader(std::fs::File::open(path)?)
+    }
+
+    /*
+    The map is only valid as long as the package is not modified or truncated
+    by another process while it is open, which is the usual contract of memory
+    mapped files.
+    */
+    pub fn open_mapped<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        let mut package = Self::from_reader(file)?;
+        package.map = Some(map);
+        Ok(package)
+    }
+}
+
+impl SceneLayerPackage<Cursor<Vec<u8>>> {
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        Self::from_reader(Cursor::new(bytes))
+    }
+}
+
+impl<R: Read + Seek> SceneLayerPackage<R> {
+    pub fn from_reader(mut reader: R) -> Result<Self> {
+        let size = reader.seek(SeekFrom::End(0))?;
+        let (hash_table, hash_table_status) = match read_trailing_hash_table(&mut reader, size) {
+            Some(Ok(hash_table)) => {
+                let entries = hash_table.len();
+                (Some(hash_table), hash::HashTableStatus::Valid { entries })
+            }
+            Some(Err(err)) => (None, hash::HashTableStatus::Inconsistent(err.to_string())),
+            None => (None, hash::HashTableStatus::Missing),
+        };
+        // without a hash table every lookup needs the central directory
+        let archive = match hash_table {
+            Some(_) => Archive::Reader(reader),
+            None => Archive::Zip(zip::ZipArchive::new(reader)?),
+        };
+        let mut package = SceneLayerPackage {
+            archive: Mutex::new(archive),
+            size,
+            hash_table,
+            hash_table_status,
+            mismatched: AtomicUsize::new(0),
+            map: None,
+            mapped_entries: OnceLock::new(),
+            entry_names: OnceLock::new(),
+        };
+        if package.hash_table_status == hash::HashTableStatus::Missing {
+            package.load_hash_table()?;
+        }
+        Ok(package)
+    }
+
+    fn archive(&self) -> MutexGuard<'_, Archive<R>> {
+        lock(&self.archive)
+    }
+
+    fn with_zip_archive<T>(
+        &self,
+        f: impl FnOnce(&mut zip::ZipArchive<R>) -> Result<T>,
+    ) -> Result<T> {
+        let mut archive = self.archive();
+        if matches!(*archive, Archive::Reader(_)) {
+            if let Archive::Reader(reader) = std::mem::replace(&mut *archive, Archive::Unreadable) {
+                *archive = Archive::Zip(zip::ZipArchive::new(reader)?);
+            }
+        }
+        match &mut *archive {
+            Archive::Zip(zip_archive) => f(zip_archive),
+            _ => Err(I3sError::MalformedBuffer(
+                "zip: the central directory cannot be read".to_string(),
+            )),
+        }
+    }
+
+    /*
+    Reads length bytes at start from the map, or from the package reader as
+    long as it does not belong to the zip archive. Returns None otherwise.
+    */
+    fn with_bytes<T>(
+        &self,
+        start: u64,
+        length: u64,
+        f: impl FnOnce(&[u8]) -> Result<T>,
+    ) -> Result<Option<T>> {
+        let end = start
+            .checked_add(length)
+            .filter(|end| *end <= self.size)
+            .ok_or_else(|| {
+                I3sError::MalformedBuffer(format!("zip: package ends before byte {}", start))
+            })?;
+        if let Some(map) = &self.map {
+            return f(&map[start as usize..end as usize]).map(Some);
+        }
+        let buffer = match &mut *self.archive() {
+            Archive::Reader(reader) => read_at(reader, start, length)?,
+            _ => return Ok(None),
+        };
+        f(&buffer).map(Some)
+    }
+
+    fn mapped_entries(&self, map: &memmap2::Mmap) -> Result<&MappedEntries> {
+        if let Some(mapped_entries) = self.mapped_entries.get() {
+            return Ok(mapped_entries);
+        }
+        let mapped_entries =
+            self.with_zip_archive(|zip_archive| map_entries(zip_archive, map.len()))?;
+        Ok(self.mapped_entries.get_or_init(|| mapped_entries))
+    }
+
+    /*
+    Resources are looked up through the hash table when the package has one,
+    by reading the local file header it points at. Entries whose header names
+    another resource are counted as mismatches and, like resources the hash
+    table does not know, looked up in the central directory instead.
+    */
+    fn hashed_entry(&self, path: &str) -> Option<io::ZipEntry> {
+        let offset = self.hash_table.as_ref()?.offset(path)?;
+        let entry = self
+            .with_bytes(offset, io::LOCAL_HEADER_SIZE, io::local_header_size)
+            .and_then(|size| match size {
+                Some(size) => {
+                    self.with_bytes(offset, size, |header| io::read_local_entry(header, offset))
+                }
+                None => Ok(None),
+            });
+        match entry {
+            Ok(Some(Some(entry)))
+                if hash::HashTable::hash(&entry.name) == hash::HashTable::hash(path) =>
+            {
+                Some(entry).filter(|entry| entry.flags & 1 == 0 && matches!(entry.method, 0 | 8))
+            }
+            // the reader belongs to the zip archive, or the sizes follow the data
+            Ok(None) | Ok(Some(None)) => None,
+            _ => {
+                self.mismatched.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    // the index of a resource in the central directory, see entry_names
+    fn entry_index(&self, path: &str) -> Result<Option<usize>> {
+        let names = match self.entry_names.get() {
+            Some(names) => names,
+            None => {
+                let names = self.with_zip_archive(|zip_archive| Ok(entry_names(zip_archive)))?;
+                self.entry_names.get_or_init(|| names)
+            }
+        };
+        Ok(names.get(&hash::normalize(path)).copied())
+    }
+
+    fn contains(&self, path: &str) -> Result<bool> {
+        if self.hashed_entry(path).is_some() {
+            return Ok(true);
+        }
+        self.entry_index(path).map(|index| index.is_some())
+    }
+
+    pub fn get(&self, path: &str) -> Result<Vec<u8>> {
+        if let Some(entry) = self.hashed_entry(path) {
+            let start = entry.header_start + entry.data_offset();
+            let buffer = self.with_bytes(start, entry.compressed_size, |data| {
+                io::inflate_zip_entry(&entry, data)
+            })?;
+            if let Some(buffer) = buffer {
+                return Ok(buffer);
+            }
+        }
+        let index = self
+            .entry_index(path)?
+            .ok_or_else(|| I3sError::ResourceNotFound(path.to_string()))?;
+        if let Some(map) = &self.map {
+            if let Some(buffer) = self.mapped_entries(map)?.read(map, index) {
+                return buffer;
+            }
         }
-        "Building" => {
-            let parsed = serde_json::from_value::<bld::SceneLayerInformation>(scene_layer_info)?;
-            Ok(I3SInfo::Building(parsed))
+        self.with_zip_archive(|zip_archive| {
+            let mut zip_file = zip_archive
+                .by_index(index)
+                .map_err(|err| I3sError::from_zip(path, err))?;
+            let mut buffer = Vec::new();
+            zip_file.read_to_end(&mut buffer)?;
+            Ok(buffer)
+        })
+    }
+
+    /*
+    Hash table entries are only checked when they are looked up, so a table is
+    reported as valid until a lookup finds an entry it does not point at.
+    */
+    pub fn hash_table_status(&self) -> hash::HashTableStatus {
+        match (
+            &self.hash_table_status,
+            self.mismatched.load(Ordering::Relaxed),
+        ) {
+            (hash::HashTableStatus::Valid { entries }, mismatched) if mismatched > 0 => {
+                hash::HashTableStatus::Inconsistent(format!(
+                    "{} lookups in {} entries did not find the hashed file",
+                    mismatched, entries
+                ))
+            }
+            (status, _) => status.clone(),
         }
-        "PointCloud" => {
-            let parsed = serde_json::from_value::<pcl::SceneLayerInformation>(scene_layer_info)?;
-            Ok(I3SInfo::PointCloud(parsed))
+    }
+
+    // packages that do not store the hash table last, see read_trailing_hash_table
+    fn load_hash_table(&mut self) -> Result<()> {
+        let buffer = match self.get(hash::HASH_TABLE_PATH) {
+            Ok(buffer) => buffer,
+            Err(err) if err.is_not_found() => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        match hash::HashTable::parse(&buffer) {
+            Ok(hash_table) => {
+                self.hash_table_status = hash::HashTableStatus::Valid {
+                    entries: hash_table.len(),
+                };
+                self.hash_table = Some(hash_table);
+            }
+            Err(err) => {
+                self.hash_table_status = hash::HashTableStatus::Inconsistent(err.to_string());
+            }
         }
-        "3DObject" => {
-            let parsed = serde_json::from_value::<cmn::SceneLayerInformation>(scene_layer_info)?;
-            Ok(I3SInfo::DDDObject(parsed))
+        Ok(())
+    }
+
+    /*
+    metadata.json is unique to .slpk files, and is not required so we return
+    None if the file is not found.
+    */
+    pub fn metadata(&self) -> Option<cmn::Metadata> {
+        let buffer = self.get("metadata.json").ok()?;
+        serde_json::from_slice::<cmn::Metadata>(&buffer).ok()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        if self.hash_table.is_some() {
+            return false;
         }
-        _ => Err("Layer type not supported".into()),
+        self.with_zip_archive(|zip_archive| Ok(zip_archive.is_empty()))
+            .unwrap_or(true)
     }
-}
 
-fn get_node_page_index(node_index: &f32, nodes_per_page: &f32) -> usize {
-    (node_index / nodes_per_page).floor() as usize
+    fn node_page_paths(&self) -> Result<Vec<String>> {
+        if let Some(hash_table) = &self.hash_table {
+            return Ok(hash_table.node_page_paths());
+        }
+        self.with_zip_archive(|zip_archive| {
+            sort_node_page_paths(io::find_node_page_paths(zip_archive))
+        })
+    }
 }
 
-fn get_node_index_in_node_page(node_index: &f32, nodes_per_page: &f32) -> usize {
-    (node_index % nodes_per_page) as usize
+/*
+Node pages are stored as nodepages/{n}.json.gz. Neither the zip central
+directory nor a directory listing is ordered, so pages are sorted by n and
+must form a contiguous range starting at 0 for node index lookups to work.
+*/
+fn sort_node_page_paths(paths: Vec<String>) -> Result<Vec<String>> {
+    let mut pages = Vec::new();
+    for path in paths {
+        let Some(name) = path.strip_prefix("nodepages/") else {
+            continue;
+        };
+        let number = name
+            .split('.')
+            .next()
+            .and_then(|number| number.parse::<usize>().ok())
+            .ok_or_else(|| I3sError::MalformedNodePage {
+                path: path.clone(),
+                reason: "file name is not a node page number".to_string(),
+            })?;
+        pages.push((number, path));
+    }
+    pages.sort_by_key(|(number, _)| *number);
+    for (expected, (number, path)) in pages.iter().enumerate() {
+        if *number != expected {
+            return Err(I3sError::MalformedNodePage {
+                path: path.clone(),
+                reason: format!("node page {} is missing", expected),
+            });
+        }
+    }
+    Ok(pages.into_iter().map(|(_, path)| path).collect())
 }
 
-pub fn get_layer_type(scene_layer_info: &serde_json::Value) -> Result<&str, Box<dyn Error>> {
-    let layer_type = scene_layer_info
-        .get("layerType")
-        .ok_or("Cannot parse layer type")?
-        .as_str()
-        .ok_or("Cannot parse layer type")?;
-    Ok(layer_type)
+/*
+A .slpk on a plain HTTP server or object storage. The end of the package and
+then single entries are fetched with range requests, so only the resources
+that are used are downloaded. Entries are located through the hash table if
+the package stores it last, otherwise the central directory is read on first
+access.
+*/
+#[derive(Debug)]
+pub struct RemoteSceneLayerPackage {
+    pub url: Url,
+    service: Service,
+    tail: OnceLock<PackageTail>,
+    entries: OnceLock<HashMap<String, io::ZipEntry>>,
 }
+
+/*
+Servers that ignore the Range header answer the first request with the whole
+package, which is then kept as the tail and read from instead.
+*/
 #[derive(Debug)]
-pub struct Service {
-    pub base: Url,
-    client: reqwest::Client,
+struct PackageTail {
+    buffer: Vec<u8>,
+    start: u64,
+    complete: bool,
+    directory_offset: u64,
+    directory_size: u64,
+    // with the offset of its local file header
+    hash_table: Option<(hash::HashTable, u64)>,
 }
 
-impl I3SFormat for Service {}
+impl I3SFormat for RemoteSceneLayerPackage {
+    async fn get(&self, path: &str) -> Result<Vec<u8>> {
+        if let Some(buffer) = self.get_hashed(path).await? {
+            return Ok(buffer);
+        }
+        let entry = self
+            .entries()
+            .await?
+            .get(path)
+            .ok_or_else(|| I3sError::ResourceNotFound(path.to_string()))?;
+        // the local header usually repeats the extra fields of the central directory
+        let header_size = entry.data_offset();
+        let start = entry.header_start;
+        let buffer = self
+            .fetch_range(start, start + header_size + entry.compressed_size)
+            .await?;
+        let actual_size = io::local_header_size(&buffer)?;
+        if actual_size == header_size {
+            return io::inflate_zip_entry(entry, &buffer[header_size as usize..]);
+        }
+        let data_start = start + actual_size;
+        let data = self
+            .fetch_range(data_start, data_start + entry.compressed_size)
+            .await?;
+        io::inflate_zip_entry(entry, &data)
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
 
-impl Service {
-    pub fn connect(base: Url) -> Self {
-        let client = reqwest::Client::new();
-        Self { base, client }
+    // the hash table is trusted here, a wrong entry only shows when it is read
+    async fn exists(&self, path: &str) -> Result<bool> {
+        if let Some((hash_table, _)) = &self.tail().await?.hash_table {
+            if hash_table.offset(path).is_some() {
+                return Ok(true);
+            }
+        }
+        Ok(self.entries().await?.contains_key(path))
+    }
+
+    async fn node_page_count(&self) -> Result<Option<usize>> {
+        if let Some((hash_table, _)) = &self.tail().await?.hash_table {
+            return Ok(Some(hash_table.node_page_paths().len()));
+        }
+        let paths = self
+            .entries()
+            .await?
+            .keys()
+            .filter(|name| name.starts_with("nodepages/"))
+            .cloned()
+            .collect();
+        sort_node_page_paths(paths).map(|paths| Some(paths.len()))
+    }
+}
+
+impl RemoteSceneLayerPackage {
+    pub fn connect(url: Url) -> Self {
+        Self::with_service(url.clone(), Service::connect(url))
     }
 
     /*
-    Every Service must have scene layer information at layers/0, so we return an error
-    if the file is not found. Something is either wrong with the service or the code.
+    Range requests for the package URL go through a service, so they share
+    its client, timeout, retries, request limit and authentication, e.g.
+    with_service(url.clone(), Service::builder(url).max_retries(5).build()).
+    The base of the service is not used, as services treat it as a directory.
     */
-    pub async fn scene_layer_information(&mut self) -> Result<I3SInfo, Box<dyn Error>> {
-        let mut buffer = self.get("layers/0").await?;
-        buffer = io::decode_gzip_buffer(&buffer)?;
-        unpack_scene_layer_information(&buffer)
+    pub fn with_service(url: Url, service: Service) -> Self {
+        Self {
+            url,
+            service,
+            tail: OnceLock::new(),
+            entries: OnceLock::new(),
+        }
     }
 
-    // TODO add error type
-    pub async fn get(&self, path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
-        match path {
-            "" => {
-                let url = self.base.clone();
-                let resp = self.client.get(url).send().await?;
-                Ok(resp.bytes().await?.to_vec())
+    pub async fn open(url: Url) -> Result<Self> {
+        let package = Self::connect(url);
+        package.tail().await?;
+        Ok(package)
+    }
+
+    async fn tail(&self) -> Result<&PackageTail> {
+        if let Some(tail) = self.tail.get() {
+            return Ok(tail);
+        }
+        let (buffer, start, complete) = self.fetch_tail(io::ZIP_TAIL_SIZE).await?;
+        let (directory_offset, directory_size) = io::find_central_directory(&buffer, start)?;
+        let window_start =
+            directory_offset.saturating_sub(hash::HashTable::search_window(directory_size));
+        // the hash table of smaller packages is part of the tail already
+        let hash_table = match window_start.checked_sub(start) {
+            Some(offset) if directory_offset - start <= buffer.len() as u64 => {
+                let window = &buffer[offset as usize..(directory_offset - start) as usize];
+                hash::HashTable::find_trailing(window, window_start)
             }
             _ => {
-                let url = self.base.join(path)?;
-                let resp = self.client.get(url).send().await?;
-                Ok(resp.bytes().await?.to_vec())
+                let window = self.fetch_range(window_start, directory_offset).await?;
+                hash::HashTable::find_trailing(&window, window_start)
+            }
+        };
+        let tail = PackageTail {
+            buffer,
+            start,
+            complete,
+            directory_offset,
+            directory_size,
+            // a malformed hash table leaves lookups to the central directory
+            hash_table: hash_table.and_then(|hash_table| hash_table.ok()),
+        };
+        Ok(self.tail.get_or_init(|| tail))
+    }
+
+    async fn entries(&self) -> Result<&HashMap<String, io::ZipEntry>> {
+        if let Some(entries) = self.entries.get() {
+            return Ok(entries);
+        }
+        let tail = self.tail().await?;
+        let (offset, size) = (tail.directory_offset, tail.directory_size);
+        // the central directory is usually part of the tail already
+        let directory = match offset.checked_sub(tail.start) {
+            Some(start) if start + size <= tail.buffer.len() as u64 => {
+                io::parse_central_directory(&tail.buffer[start as usize..(start + size) as usize])?
             }
+            _ => io::parse_central_directory(&self.fetch_range(offset, offset + size).await?)?,
+        };
+        let entries = directory
+            .into_iter()
+            .map(|entry| (entry.name.clone(), entry))
+            .collect();
+        Ok(self.entries.get_or_init(|| entries))
+    }
+
+    /*
+    With a hash table, a resource is fetched in one request from its local
+    header up to the next entry. Resources the hash table does not know, or
+    whose header names another resource, go through the central directory.
+    */
+    async fn get_hashed(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        let Some((hash_table, table_start)) = &self.tail().await?.hash_table else {
+            return Ok(None);
+        };
+        let Some(start) = hash_table.offset(path) else {
+            return Ok(None);
+        };
+        let end = hash_table
+            .next_offset(start)
+            .map_or(*table_start, |end| end.min(*table_start));
+        if end <= start {
+            return Ok(None);
+        }
+        let buffer = self.fetch_range(start, end).await?;
+        let entry = match io::read_local_entry(&buffer, start) {
+            Ok(Some(entry))
+                if hash::HashTable::hash(&entry.name) == hash::HashTable::hash(path) =>
+            {
+                entry
+            }
+            _ => return Ok(None),
+        };
+        let data = entry
+            .data_offset()
+            .checked_add(entry.compressed_size)
+            .and_then(|data_end| buffer.get(entry.data_offset() as usize..data_end as usize));
+        match data {
+            Some(data) => io::inflate_zip_entry(&entry, data).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    async fn send_range(&self, range: &str) -> Result<RangeResponse> {
+        let url = &self.url;
+        self.service
+            .send_with_retries(|token| async move {
+                let resp = self
+                    .service
+                    .request(url, token.as_deref())
+                    .header(reqwest::header::RANGE, range)
+                    .send()
+                    .await
+                    .map_err(|err| self.service.request_error(err))?;
+                let status = resp.status();
+                if status == reqwest::StatusCode::NOT_FOUND {
+                    return Err(I3sError::ResourceNotFound(url.to_string()));
+                }
+                if !status.is_success() {
+                    return Err(I3sError::HttpStatus {
+                        path: url.to_string(),
+                        status: status.as_u16(),
+                    });
+                }
+                let total = resp
+                    .headers()
+                    .get(reqwest::header::CONTENT_RANGE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.rsplit('/').next())
+                    .and_then(|total| total.parse::<u64>().ok());
+                Ok(RangeResponse {
+                    partial: status == reqwest::StatusCode::PARTIAL_CONTENT,
+                    total,
+                    data: resp
+                        .bytes()
+                        .await
+                        .map_err(|err| self.service.request_error(err))?
+                        .to_vec(),
+                })
+            })
+            .await
+    }
+
+    async fn fetch_range(&self, start: u64, end: u64) -> Result<Vec<u8>> {
+        let data = match self.tail.get().filter(|tail| tail.complete) {
+            Some(tail) => tail
+                .buffer
+                .get(start as usize..end as usize)
+                .map(<[u8]>::to_vec),
+            None => {
+                let resp = self
+                    .send_range(&format!("bytes={}-{}", start, end.saturating_sub(1)))
+                    .await?;
+                if !resp.partial {
+                    return Err(I3sError::MalformedBuffer(format!(
+                        "zip: {} answered a range request with the whole package",
+                        self.url
+                    )));
+                }
+                Some(resp.data)
+            }
+        };
+        match data {
+            Some(data) if data.len() as u64 == end - start => Ok(data),
+            _ => Err(I3sError::MalformedBuffer(format!(
+                "zip: {} ends before byte {}",
+                self.url, end
+            ))),
         }
     }
+
+    // returns the tail, its offset in the package and whether it is the whole package
+    async fn fetch_tail(&self, length: u64) -> Result<(Vec<u8>, u64, bool)> {
+        let resp = self.send_range(&format!("bytes=-{}", length)).await?;
+        if !resp.partial {
+            return Ok((resp.data, 0, true));
+        }
+        let total = resp.total.ok_or_else(|| {
+            I3sError::MalformedBuffer(format!(
+                "zip: {} answered a range request without Content-Range",
+                self.url
+            ))
+        })?;
+        let start = total.saturating_sub(resp.data.len() as u64);
+        Ok((resp.data, start, start == 0))
+    }
+}
+
+#[derive(Debug)]
+struct RangeResponse {
+    partial: bool,
+    total: Option<u64>,
+    data: Vec<u8>,
 }
 
 #[derive(Debug)]
-pub struct SceneLayerPackage {
-    zip_archive: zip::ZipArchive<std::fs::File>,
+pub struct SceneLayerFolder {
+    pub root: PathBuf,
 }
 
-impl I3SFormat for SceneLayerPackage {}
+impl I3SFormat for SceneLayerFolder {
+    async fn get(&self, path: &str) -> Result<Vec<u8>> {
+        SceneLayerFolder::get(self, path)
+    }
 
-impl SceneLayerPackage {
-    pub fn get(&mut self, path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
-        let mut zip_file = self.zip_archive.by_name(path)?;
-        let mut buffer = Vec::new();
-        zip_file.read_to_end(&mut buffer)?;
-        Ok(buffer)
+    fn is_local(&self) -> bool {
+        true
     }
 
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ZipFileReadError> {
-        let file = std::fs::File::open(path)?;
-        let zip_archive = zip::ZipArchive::new(file)?;
-        Ok(SceneLayerPackage { zip_archive })
+    async fn exists(&self, path: &str) -> Result<bool> {
+        Ok(self.resolve(path).is_some())
+    }
+
+    async fn node_page_count(&self) -> Result<Option<usize>> {
+        self.node_page_paths().map(|paths| Some(paths.len()))
+    }
+}
+
+impl SceneLayerFolder {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let root = path.as_ref().to_path_buf();
+        if !root.is_dir() {
+            return Err(I3sError::ResourceNotFound(root.display().to_string()));
+        }
+        Ok(SceneLayerFolder { root })
     }
 
     /*
-    Every .slpk must have a 3dSceneLayer.json.gz file, so we return an error
-    if the file is not found. Something is either wrong with the file or the code.
+    Extracted packages are sometimes decompressed along the way, so a path is
+    also tried without its .gz suffix, e.g. nodes/0/geometries/0.bin for
+    nodes/0/geometries/0.bin.gz, and the other way around.
     */
-    pub fn scene_layer_information(&mut self) -> Result<I3SInfo, Box<dyn Error>> {
-        let mut buffer = self.get("3dSceneLayer.json.gz")?;
-        buffer = io::decode_gzip_buffer(&buffer)?;
-        unpack_scene_layer_information(&buffer)
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let alternative = match path.strip_suffix(".gz") {
+            Some(stripped) => stripped.to_string(),
+            None => format!("{}.gz", path),
+        };
+        let resolved = [path, alternative.as_str()]
+            .into_iter()
+            .map(|candidate| self.root.join(candidate))
+            .find(|candidate| candidate.is_file());
+        resolved
+    }
+
+    pub fn get(&self, path: &str) -> Result<Vec<u8>> {
+        let resolved = self
+            .resolve(path)
+            .ok_or_else(|| I3sError::ResourceNotFound(path.to_string()))?;
+        Ok(std::fs::read(resolved)?)
     }
 
     /*
-    metadata.json is unique to .slpk files, and is not required so we return
-    None if the file is not found.
+    Like in a .slpk, metadata.json is optional, so we return None if the file
+    is not found.
     */
-    pub fn metadata(&mut self) -> Option<cmn::Metadata> {
-        if let Ok(buffer) = self.get("metadata.json") {
-            if let Ok(metadata) = serde_json::from_slice::<cmn::Metadata>(&buffer) {
-                Some(metadata)
-            } else {
-                None
+    pub fn metadata(&self) -> Option<cmn::Metadata> {
+        let buffer = self.get("metadata.json").ok()?;
+        let buffer = io::decode_gzip_if_compressed("metadata.json", &buffer).ok()?;
+        serde_json::from_slice::<cmn::Metadata>(&buffer).ok()
+    }
+
+    fn node_page_paths(&self) -> Result<Vec<String>> {
+        let directory = match std::fs::read_dir(self.root.join("nodepages")) {
+            Ok(directory) => directory,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(I3sError::IO(err)),
+        };
+        let mut paths = Vec::new();
+        for entry in directory {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                paths.push(format!("nodepages/{}", entry.file_name().to_string_lossy()));
             }
-        } else {
-            None
         }
+        sort_node_page_paths(paths)
+    }
+}
+
+/*
+Resources are kept under the same paths as in a .slpk, which makes this
+useful for tests and for layers that were assembled or downloaded elsewhere.
+*/
+#[derive(Debug, Default, Clone)]
+pub struct MemoryStore {
+    resources: HashMap<String, Vec<u8>>,
+}
+
+impl I3SFormat for MemoryStore {
+    async fn get(&self, path: &str) -> Result<Vec<u8>> {
+        self.resources
+            .get(path)
+            .cloned()
+            .ok_or_else(|| I3sError::ResourceNotFound(path.to_string()))
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool> {
+        Ok(self.resources.contains_key(path))
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert<P: Into<String>>(&mut self, path: P, data: Vec<u8>) -> Option<Vec<u8>> {
+        self.resources.insert(path.into(), data)
+    }
+
+    pub fn remove(&mut self, path: &str) -> Option<Vec<u8>> {
+        self.resources.remove(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.resources.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.zip_archive.is_empty()
+        self.resources.is_empty()
+    }
+}
+
+impl<P: Into<String>> FromIterator<(P, Vec<u8>)> for MemoryStore {
+    fn from_iter<I: IntoIterator<Item = (P, Vec<u8>)>>(iter: I) -> Self {
+        Self {
+            resources: iter
+                .into_iter()
+                .map(|(path, data)| (path.into(), data))
+                .collect(),
+        }
+    }
+}
+
+fn check_node_page(page: usize, node_page: &cmn::NodePage, nodes_per_page: usize) -> Result<()> {
+    let path = format!("nodepages/{}", page);
+    if node_page.nodes.len() > nodes_per_page {
+        return Err(I3sError::MalformedNodePage {
+            path,
+            reason: format!(
+                "{} nodes exceed nodesPerPage of {}",
+                node_page.nodes.len(),
+                nodes_per_page
+            ),
+        });
+    }
+    for (i, node) in node_page.nodes.iter().enumerate() {
+        let expected = page * nodes_per_page + i;
+        if node.index != expected {
+            return Err(I3sError::MalformedNodePage {
+                path,
+                reason: format!("node {} is stored at index {}", node.index, expected),
+            });
+        }
     }
+    Ok(())
+}
+
+fn is_embedded_elevation(attribute: &pcl::AttributeInfo) -> bool {
+    attribute.encoding.as_deref() == Some("embedded-elevation")
+}
+
+fn lepcc_attributes(
+    information: &pcl::SceneLayerInformation,
+) -> (Option<&pcl::AttributeInfo>, Option<&pcl::AttributeInfo>) {
+    let find = |encoding: &str| {
+        information
+            .attribute_storage_info
+            .iter()
+            .find(|attribute| attribute.encoding.as_deref() == Some(encoding))
+    };
+    (find("lepcc-rgb"), find("lepcc-intensity"))
+}
+
+/*
+Profiles only see the node pages they were created with. Services fetch node
+pages lazily, so their profiles start out empty and nodes have to be looked
+up through Service::node instead, which is why these lookups are optional.
+*/
+fn page_node<'a>(
+    node_pages: &'a [cmn::NodePage],
+    nodes_per_page: usize,
+    index: &usize,
+) -> Option<&'a cmn::Node> {
+    let node_page_index = get_node_page_index(index, &nodes_per_page)?;
+    let node_index = get_node_index_in_node_page(index, &nodes_per_page)?;
+    node_pages.get(node_page_index)?.nodes.get(node_index)
 }
 
 #[derive(Default, Debug)]
@@ -184,19 +2164,23 @@ pub struct IntegratedMesh {
 impl I3SProfile for IntegratedMesh {}
 
 impl IntegratedMesh {
-    pub fn node(&self, index: &usize) -> &cmn::Node {
-        let nodes_per_page: f32 = self.nodes_per_page as f32;
-        let index = *index as f32;
-        let node_page_index = get_node_page_index(&index, &nodes_per_page);
-        let node_index = get_node_index_in_node_page(&index, &nodes_per_page);
-        &self.node_pages[node_page_index].nodes[node_index]
+    pub fn new(node_pages: Vec<cmn::NodePage>, definition: &cmn::NodePageDefinition) -> Self {
+        Self {
+            node_pages,
+            root_index: definition.root_index,
+            nodes_per_page: definition.nodes_per_page as usize,
+        }
     }
 
-    pub fn nodes(&self, indices: &[usize]) -> Vec<&cmn::Node> {
+    pub fn node(&self, index: &usize) -> Option<&cmn::Node> {
+        page_node(&self.node_pages, self.nodes_per_page, index)
+    }
+
+    pub fn nodes(&self, indices: &[usize]) -> Option<Vec<&cmn::Node>> {
         indices.iter().map(|i| self.node(i)).collect()
     }
 
-    pub fn root(&self) -> &cmn::Node {
+    pub fn root(&self) -> Option<&cmn::Node> {
         self.node(&self.root_index)
     }
 }
@@ -205,34 +2189,176 @@ impl IntegratedMesh {
 pub struct DDDObject {
     pub statistics: cmn::AttributeStatistics,
     pub node_pages: Vec<cmn::NodePage>,
+    root_index: usize,
+    nodes_per_page: usize,
 }
 
 impl I3SProfile for DDDObject {}
 
+impl DDDObject {
+    pub fn new(node_pages: Vec<cmn::NodePage>, definition: &cmn::NodePageDefinition) -> Self {
+        Self {
+            statistics: cmn::AttributeStatistics::default(),
+            node_pages,
+            root_index: definition.root_index,
+            nodes_per_page: definition.nodes_per_page as usize,
+        }
+    }
+
+    pub fn node(&self, index: &usize) -> Option<&cmn::Node> {
+        page_node(&self.node_pages, self.nodes_per_page, index)
+    }
+
+    pub fn nodes(&self, indices: &[usize]) -> Option<Vec<&cmn::Node>> {
+        indices.iter().map(|i| self.node(i)).collect()
+    }
+
+    pub fn root(&self) -> Option<&cmn::Node> {
+        self.node(&self.root_index)
+    }
+}
+
 #[derive(Debug)]
 pub struct PointCloud {
     pub statistics: pcl::Statistics,
     pub node_pages: Vec<pcl::NodePage>,
+    nodes_per_page: usize,
 }
 
 impl I3SProfile for PointCloud {}
 
+impl PointCloud {
+    pub fn new(node_pages: Vec<pcl::NodePage>, index: &pcl::Index) -> Self {
+        Self {
+            statistics: pcl::Statistics::default(),
+            node_pages,
+            nodes_per_page: index.nodes_per_page,
+        }
+    }
+
+    pub fn node(&self, index: &usize) -> Option<&pcl::Node> {
+        let node_page_index = get_node_page_index(index, &self.nodes_per_page)?;
+        let node_index = get_node_index_in_node_page(index, &self.nodes_per_page)?;
+        self.node_pages.get(node_page_index)?.nodes.get(node_index)
+    }
+
+    pub fn nodes(&self, indices: &[usize]) -> Option<Vec<&pcl::Node>> {
+        indices.iter().map(|i| self.node(i)).collect()
+    }
+
+    pub fn root(&self) -> Option<&pcl::Node> {
+        self.node(&0)
+    }
+}
+
 #[derive(Debug)]
 pub struct Point {
     pub statistics: cmn::Statistics,
     pub node_pages: Vec<cmn::NodePage>,
+    root_index: usize,
+    nodes_per_page: usize,
 }
 
 impl I3SProfile for Point {}
 
+impl Point {
+    pub fn new(node_pages: Vec<cmn::NodePage>, definition: &cmn::NodePageDefinition) -> Self {
+        Self {
+            statistics: cmn::Statistics::default(),
+            node_pages,
+            root_index: definition.root_index,
+            nodes_per_page: definition.nodes_per_page as usize,
+        }
+    }
+
+    pub fn node(&self, index: &usize) -> Option<&cmn::Node> {
+        page_node(&self.node_pages, self.nodes_per_page, index)
+    }
+
+    pub fn nodes(&self, indices: &[usize]) -> Option<Vec<&cmn::Node>> {
+        indices.iter().map(|i| self.node(i)).collect()
+    }
+
+    pub fn root(&self) -> Option<&cmn::Node> {
+        self.node(&self.root_index)
+    }
+}
+
+/*
+Layers before 1.7 have no node pages, so instead of looking nodes up by index
+their node index documents are walked from the store's rootNode.
+*/
+#[derive(Debug)]
+pub struct NodeIndex {
+    pub root_node: String,
+    pub frame: lod::Frame,
+}
+
+impl I3SProfile for NodeIndex {}
+
+impl NodeIndex {
+    pub fn new(root_node: &str, frame: lod::Frame) -> Self {
+        Self {
+            root_node: root_node.to_string(),
+            frame,
+        }
+    }
+
+    // layers with node pages get a profile of their layer type instead
+    pub fn from_information(information: &I3SInfo) -> Option<Self> {
+        match information {
+            I3SInfo::IntegratedMesh(info) | I3SInfo::DDDObject(info)
+                if info.node_pages.nodes_per_page == 0 =>
+            {
+                let frame = lod::Frame::from_spatial_reference(info.spatial_reference.as_ref());
+                Some(Self::new(&info.store.root_node, frame))
+            }
+            I3SInfo::Point(info) if info.point_node_pages.is_none() => {
+                let root_node = info.store.root_node.as_deref();
+                let frame = lod::Frame::from_spatial_reference(info.spatial_reference.as_ref());
+                Some(Self::new(root_node.unwrap_or("./nodes/root"), frame))
+            }
+            _ => None,
+        }
+    }
+
+    pub async fn walk<F, V>(&self, format: &F, visit: V) -> Result<()>
+    where
+        F: I3SFormatExt,
+        V: FnMut(&cmn::NodeIndexDocument) -> bool + Send,
+    {
+        format.walk_node_index(&self.root_node, visit).await
+    }
+
+    pub async fn select<F: I3SFormatExt>(
+        &self,
+        format: &F,
+        camera: &lod::Camera,
+        quality: f64,
+    ) -> Result<Vec<String>> {
+        format
+            .select_node_index(&self.root_node, camera, quality, self.frame)
+            .await
+    }
+}
+
 #[derive(Debug)]
 pub struct Building {
     pub statistics: bld::Statistics,
-    pub sub_layer: bld::SubLayer,
+    pub sub_layers: Vec<bld::SubLayer>,
 }
 
 impl I3SProfile for Building {}
 
+impl Building {
+    pub fn new(sub_layers: Vec<bld::SubLayer>) -> Self {
+        Self {
+            statistics: bld::Statistics::default(),
+            sub_layers,
+        }
+    }
+}
+
 pub struct SceneLayer<F, P>
 where
     F: I3SFormat,
@@ -242,3 +2368,186 @@ where
     pub profile: P,
     pub information: I3SInfo,
 }
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum Format {
+    SceneLayerPackage(SceneLayerPackage),
+    Service(Service),
+    Folder(SceneLayerFolder),
+    Memory(MemoryStore),
+    RemotePackage(RemoteSceneLayerPackage),
+}
+
+/*
+Methods the stores override are forwarded as well, so packages keep counting
+node pages from their directory. Services shadow scene_layer_information and
+profile, which Format forwards in its own impl so services stay lazy.
+*/
+impl I3SFormat for Format {
+    async fn get(&self, path: &str) -> Result<Vec<u8>> {
+        match self {
+            Format::SceneLayerPackage(package) => I3SFormat::get(package, path).await,
+            Format::Service(service) => service.get(path).await,
+            Format::Folder(folder) => I3SFormat::get(folder, path).await,
+            Format::Memory(memory) => memory.get(path).await,
+            Format::RemotePackage(package) => package.get(path).await,
+        }
+    }
+
+    fn is_local(&self) -> bool {
+        match self {
+            Format::SceneLayerPackage(package) => package.is_local(),
+            Format::Service(service) => service.is_local(),
+            Format::Folder(folder) => folder.is_local(),
+            Format::Memory(memory) => memory.is_local(),
+            Format::RemotePackage(package) => package.is_local(),
+        }
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool> {
+        match self {
+            Format::SceneLayerPackage(package) => package.exists(path).await,
+            Format::Service(service) => service.exists(path).await,
+            Format::Folder(folder) => folder.exists(path).await,
+            Format::Memory(memory) => memory.exists(path).await,
+            Format::RemotePackage(package) => package.exists(path).await,
+        }
+    }
+
+    async fn node_page_count(&self) -> Result<Option<usize>> {
+        match self {
+            Format::SceneLayerPackage(package) => package.node_page_count().await,
+            Format::Service(service) => service.node_page_count().await,
+            Format::Folder(folder) => folder.node_page_count().await,
+            Format::Memory(memory) => memory.node_page_count().await,
+            Format::RemotePackage(package) => package.node_page_count().await,
+        }
+    }
+}
+
+impl Format {
+    pub async fn scene_layer_information(&self) -> Result<I3SInfo> {
+        match self {
+            Format::SceneLayerPackage(package) => package.scene_layer_information().await,
+            Format::Service(service) => service.scene_layer_information().await,
+            Format::Folder(folder) => folder.scene_layer_information().await,
+            Format::Memory(memory) => memory.scene_layer_information().await,
+            Format::RemotePackage(package) => package.scene_layer_information().await,
+        }
+    }
+
+    pub async fn profile(&self, information: &I3SInfo) -> Result<Profile> {
+        match self {
+            Format::SceneLayerPackage(package) => package.profile(information).await,
+            Format::Service(service) => service.profile(information).await,
+            Format::Folder(folder) => folder.profile(information).await,
+            Format::Memory(memory) => memory.profile(information).await,
+            Format::RemotePackage(package) => package.profile(information).await,
+        }
+    }
+
+    /*
+    URLs are treated as REST services unless they point at a .slpk,
+    directories as extracted packages and any other existing file as a .slpk.
+    */
+    pub fn open(source: &str) -> Result<Self> {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            let url = Url::parse(source)?;
+            if url.path().to_ascii_lowercase().ends_with(".slpk") {
+                return Ok(Format::RemotePackage(RemoteSceneLayerPackage::connect(url)));
+            }
+            let (base, layer) = split_layer_url(url);
+            return Ok(Format::Service(Service::builder(base).layer(layer).build()));
+        }
+        let path = Path::new(source);
+        if path.is_dir() {
+            Ok(Format::Folder(SceneLayerFolder::open(path)?))
+        } else if path.is_file() {
+            Ok(Format::SceneLayerPackage(SceneLayerPackage::open(path)?))
+        } else {
+            Err(I3sError::ResourceNotFound(source.to_string()))
+        }
+    }
+}
+
+/*
+Service URLs may point at the SceneServer root or directly at one of its
+layers, e.g. .../SceneServer/layers/0. The base always ends up as the root
+so relative resource paths join correctly.
+*/
+fn split_layer_url(mut url: Url) -> (Url, usize) {
+    let segments: Vec<String> = url
+        .path_segments()
+        .map(|segments| {
+            segments
+                .filter(|segment| !segment.is_empty())
+                .map(|segment| segment.to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut layer = 0;
+    let mut end = segments.len();
+    if let Some(position) = segments.iter().rposition(|segment| segment == "layers") {
+        if let Some(id) = segments
+            .get(position + 1)
+            .and_then(|id| id.parse::<usize>().ok())
+        {
+            layer = id;
+        }
+        end = position;
+    }
+    url.set_path(&format!("{}/", segments[..end].join("/")));
+    url.set_query(None);
+    (url, layer)
+}
+
+#[derive(Debug)]
+pub enum Profile {
+    IntegratedMesh(IntegratedMesh),
+    DDDObject(DDDObject),
+    Point(Point),
+    PointCloud(PointCloud),
+    Building(Building),
+    NodeIndex(NodeIndex),
+}
+
+impl I3SProfile for Profile {}
+
+impl Profile {
+    pub fn new(information: &I3SInfo) -> Self {
+        if let Some(node_index) = NodeIndex::from_information(information) {
+            return Profile::NodeIndex(node_index);
+        }
+        match information {
+            I3SInfo::IntegratedMesh(info) => {
+                Profile::IntegratedMesh(IntegratedMesh::new(vec![], &info.node_pages))
+            }
+            I3SInfo::DDDObject(info) => {
+                Profile::DDDObject(DDDObject::new(vec![], &info.node_pages))
+            }
+            I3SInfo::Point(info) => Profile::Point(Point::new(
+                vec![],
+                &info.point_node_pages.clone().unwrap_or_default(),
+            )),
+            I3SInfo::PointCloud(info) => {
+                Profile::PointCloud(PointCloud::new(vec![], &info.store.index))
+            }
+            I3SInfo::Building(info) => Profile::Building(Building::new(info.sub_layers.clone())),
+        }
+    }
+}
+
+pub async fn open(source: &str) -> Result<SceneLayer<Format, Profile>> {
+    open_format(Format::open(source)?).await
+}
+
+async fn open_format(format: Format) -> Result<SceneLayer<Format, Profile>> {
+    let information = format.scene_layer_information().await?;
+    let profile = format.profile(&information).await?;
+    Ok(SceneLayer {
+        format,
+        profile,
+        information,
+    })
+}
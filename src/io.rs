@@ -1,17 +1,15 @@
-use core::fmt;
 use serde_json::Value;
-use std::error;
-use std::fs::File;
 use std::io;
-use std::io::Read;
+use std::io::{Read, Seek};
 use zip::ZipArchive;
 
 use flate2::read::GzDecoder;
 use serde_json;
 use zip::read::ZipFile;
-use zip::result::ZipError;
 
-pub fn find_node_page_paths(zip_archive: &zip::ZipArchive<File>) -> Vec<String> {
+use crate::error::{I3sError, Result};
+
+pub fn find_node_page_paths<R: Read + Seek>(zip_archive: &zip::ZipArchive<R>) -> Vec<String> {
     zip_archive
         .file_names()
         .filter(|name| name.contains("nodepages"))
@@ -19,67 +17,371 @@ pub fn find_node_page_paths(zip_archive: &zip::ZipArchive<File>) -> Vec<String>
         .collect::<Vec<String>>()
 }
 
-#[derive(Debug)]
-pub enum ZipFileReadError {
-    IO(io::Error),
-    Json(serde_json::Error),
-    Zip(ZipError),
+pub trait ZipFileReader {
+    fn from_zip_file(zip_file: ZipFile) -> Result<Self>
+    where
+        Self: Sized + serde::de::DeserializeOwned,
+    {
+        let path = zip_file.name().to_string();
+        let json_string = decode_json_gz(zip_file).map_err(|source| I3sError::Gzip {
+            path: path.clone(),
+            source,
+        })?;
+        parse_json(&path, json_string.as_bytes())
+    }
+}
+
+pub fn decode_json_gz<R: Read>(json_gz: R) -> std::result::Result<String, io::Error> {
+    let mut gzip_decoder = GzDecoder::new(json_gz);
+    let mut json_content = String::new();
+    gzip_decoder.read_to_string(&mut json_content)?;
+    Ok(json_content)
+}
+
+pub fn decode_gzip_buffer(buffer: &[u8]) -> std::result::Result<Vec<u8>, io::Error> {
+    let mut decoder = flate2::read::GzDecoder::new(buffer);
+    let mut decompressed = Vec::new();
+    decoder.read_to_end(&mut decompressed)?;
+    Ok(decompressed)
 }
 
-impl From<ZipError> for ZipFileReadError {
-    fn from(err: ZipError) -> Self {
-        ZipFileReadError::Zip(err)
+pub fn decode_gzip(path: &str, buffer: &[u8]) -> Result<Vec<u8>> {
+    decode_gzip_buffer(buffer).map_err(|source| I3sError::Gzip {
+        path: path.to_string(),
+        source,
+    })
+}
+
+/*
+REST services usually answer with plain JSON (reqwest handles Content-Encoding),
+while packaged resources are gzipped, so only decompress if the gzip magic
+number is present.
+*/
+pub fn decode_gzip_if_compressed(path: &str, buffer: &[u8]) -> Result<Vec<u8>> {
+    if buffer.starts_with(&[0x1f, 0x8b]) {
+        decode_gzip(path, buffer)
+    } else {
+        Ok(buffer.to_vec())
     }
 }
 
-impl From<io::Error> for ZipFileReadError {
-    fn from(err: io::Error) -> Self {
-        ZipFileReadError::IO(err)
+pub fn parse_json<T: serde::de::DeserializeOwned>(path: &str, buffer: &[u8]) -> Result<T> {
+    serde_json::from_slice::<T>(buffer).map_err(|source| I3sError::Json {
+        path: path.to_string(),
+        source,
+    })
+}
+
+pub fn unzip_scene_layer_info<R: Read + Seek>(zip_archive: &mut ZipArchive<R>) -> Result<Value> {
+    let path = "3dSceneLayer.json.gz";
+    let zip_file = zip_archive
+        .by_name(path)
+        .map_err(|err| I3sError::from_zip(path, err))?;
+    let json = decode_json_gz(zip_file).map_err(|source| I3sError::Gzip {
+        path: path.to_string(),
+        source,
+    })?;
+    parse_json::<Value>(path, json.as_bytes())
+}
+
+/*
+Central directory reader for packages that cannot be read through
+zip::ZipArchive, e.g. remote packages that are fetched with range requests.
+Only what is needed to locate and inflate entries is kept.
+*/
+#[derive(Debug, Clone)]
+pub struct ZipEntry {
+    pub name: String,
+    pub header_start: u64,
+    pub compressed_size: u64,
+    pub method: u16,
+    pub flags: u16,
+    pub extra_length: u16,
+}
+
+impl ZipEntry {
+    /*
+    The offset of the data from the local header, exact for entries read from
+    a local header and usually right for central directory entries.
+    */
+    pub fn data_offset(&self) -> u64 {
+        LOCAL_HEADER_SIZE + self.name.len() as u64 + self.extra_length as u64
     }
 }
 
-impl From<serde_json::Error> for ZipFileReadError {
-    fn from(err: serde_json::Error) -> Self {
-        ZipFileReadError::Json(err)
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY: u32 = 0x06064b50;
+const ZIP64_LOCATOR: u32 = 0x07064b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x02014b50;
+const LOCAL_FILE_HEADER: u32 = 0x04034b50;
+
+pub const LOCAL_HEADER_SIZE: u64 = 30;
+
+// the end of central directory record, its longest comment and a zip64 locator and record
+pub const ZIP_TAIL_SIZE: u64 = 22 + 65535 + 20 + 56;
+
+fn malformed_zip(reason: &str) -> I3sError {
+    I3sError::MalformedBuffer(format!("zip: {}", reason))
+}
+
+/*
+Returns the offset and size of the central directory from the tail of a
+package, where tail_start is the offset of the tail in the package.
+*/
+pub fn find_central_directory(tail: &[u8], tail_start: u64) -> Result<(u64, u64)> {
+    let position = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|i| tail[*i..*i + 4] == END_OF_CENTRAL_DIRECTORY.to_le_bytes())
+        .ok_or_else(|| malformed_zip("end of central directory not found"))?;
+    let mut reader = BufferReader::new(&tail[position + 12..]);
+    let size = reader.read_u32()?;
+    let offset = reader.read_u32()?;
+    if size != u32::MAX && offset != u32::MAX {
+        return Ok((offset as u64, size as u64));
     }
+
+    let locator = position
+        .checked_sub(20)
+        .filter(|locator| tail[*locator..*locator + 4] == ZIP64_LOCATOR.to_le_bytes())
+        .ok_or_else(|| malformed_zip("zip64 locator not found"))?;
+    let mut reader = BufferReader::new(&tail[locator + 8..]);
+    let record = reader
+        .read_u64()?
+        .checked_sub(tail_start)
+        .map(|record| record as usize)
+        .filter(|record| record + 56 <= tail.len())
+        .ok_or_else(|| malformed_zip("zip64 end of central directory is out of range"))?;
+    let mut reader = BufferReader::new(&tail[record..]);
+    if reader.read_u32()? != ZIP64_END_OF_CENTRAL_DIRECTORY {
+        return Err(malformed_zip("invalid zip64 end of central directory"));
+    }
+    reader.skip(36)?;
+    let size = reader.read_u64()?;
+    let offset = reader.read_u64()?;
+    Ok((offset, size))
 }
 
-impl fmt::Display for ZipFileReadError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "ZipFileReadError: ")
+pub fn parse_central_directory(buffer: &[u8]) -> Result<Vec<ZipEntry>> {
+    let mut reader = BufferReader::new(buffer);
+    let mut entries = Vec::new();
+    while reader.remaining() >= 4 {
+        if reader.read_u32()? != CENTRAL_DIRECTORY_HEADER {
+            break;
+        }
+        reader.skip(4)?;
+        let flags = reader.read_u16()?;
+        let method = reader.read_u16()?;
+        reader.skip(8)?;
+        let mut compressed_size = reader.read_u32()? as u64;
+        let uncompressed_size = reader.read_u32()?;
+        let name_length = reader.read_u16()? as usize;
+        let extra_length = reader.read_u16()?;
+        let comment_length = reader.read_u16()? as usize;
+        reader.skip(8)?;
+        let mut header_start = reader.read_u32()? as u64;
+        let name = String::from_utf8_lossy(reader.read_bytes(name_length)?).into_owned();
+
+        // zip64 fields are only present for the values that overflowed
+        let mut extra = BufferReader::new(reader.read_bytes(extra_length as usize)?);
+        while extra.remaining() >= 4 {
+            let id = extra.read_u16()?;
+            let size = extra.read_u16()? as usize;
+            let data = extra.read_bytes(size)?;
+            if id != 0x0001 {
+                continue;
+            }
+            let mut data = BufferReader::new(data);
+            if uncompressed_size == u32::MAX {
+                data.read_u64()?;
+            }
+            if compressed_size == u32::MAX as u64 {
+                compressed_size = data.read_u64()?;
+            }
+            if header_start == u32::MAX as u64 {
+                header_start = data.read_u64()?;
+            }
+        }
+        reader.skip(comment_length)?;
+        entries.push(ZipEntry {
+            name,
+            header_start,
+            compressed_size,
+            method,
+            flags,
+            extra_length,
+        });
     }
+    Ok(entries)
 }
 
-pub trait ZipFileReader {
-    fn from_zip_file(zip_file: ZipFile) -> Result<Self, Box<dyn std::error::Error + 'static>>
-    where
-        Self: Sized + serde::de::DeserializeOwned,
-    {
-        let json_string = decode_json_gz(zip_file)?;
-        let result: Self = serde_json::from_str(&json_string)?;
-        Ok(result)
+pub fn local_header_size(header: &[u8]) -> Result<u64> {
+    let mut reader = BufferReader::new(header);
+    if reader.read_u32()? != LOCAL_FILE_HEADER {
+        return Err(malformed_zip("invalid local file header"));
     }
+    reader.skip(22)?;
+    let name_length = reader.read_u16()? as u64;
+    let extra_length = reader.read_u16()? as u64;
+    Ok(LOCAL_HEADER_SIZE + name_length + extra_length)
 }
 
-pub fn decode_json_gz<R: Read>(json_gz: R) -> Result<String, io::Error> {
-    let mut gzip_decoder = GzDecoder::new(json_gz);
-    let mut json_content = String::new();
-    gzip_decoder.read_to_string(&mut json_content)?;
-    Ok(json_content)
+/*
+Reads an entry from its local file header, which repeats the name, method
+and sizes of the central directory unless the sizes follow the data in a data
+descriptor, in which case None is returned. The extra_length of the entry is
+the one of the local header, so the data starts right after the header.
+*/
+pub fn read_local_entry(header: &[u8], header_start: u64) -> Result<Option<ZipEntry>> {
+    let mut reader = BufferReader::new(header);
+    if reader.read_u32()? != LOCAL_FILE_HEADER {
+        return Err(malformed_zip("invalid local file header"));
+    }
+    reader.skip(2)?;
+    let flags = reader.read_u16()?;
+    let method = reader.read_u16()?;
+    reader.skip(8)?;
+    let mut compressed_size = reader.read_u32()? as u64;
+    reader.skip(4)?;
+    let name_length = reader.read_u16()? as usize;
+    let extra_length = reader.read_u16()?;
+    let name = String::from_utf8_lossy(reader.read_bytes(name_length)?).into_owned();
+    if flags & 8 != 0 {
+        return Ok(None);
+    }
+
+    // unlike in the central directory, a local zip64 field holds both sizes
+    let mut extra = BufferReader::new(reader.read_bytes(extra_length as usize)?);
+    while compressed_size == u32::MAX as u64 && extra.remaining() >= 4 {
+        let id = extra.read_u16()?;
+        let size = extra.read_u16()? as usize;
+        let mut data = BufferReader::new(extra.read_bytes(size)?);
+        if id == 0x0001 {
+            data.skip(8)?;
+            compressed_size = data.read_u64()?;
+        }
+    }
+    Ok(Some(ZipEntry {
+        name,
+        header_start,
+        compressed_size,
+        method,
+        flags,
+        extra_length,
+    }))
 }
 
-pub fn decode_gzip_buffer(buffer: &[u8]) -> Result<Vec<u8>, io::Error> {
-    let mut decoder = flate2::read::GzDecoder::new(buffer);
-    let mut decompressed = Vec::new();
-    decoder.read_to_end(&mut decompressed)?;
-    Ok(decompressed)
+/*
+Finds the local header of the entry that ends where buffer ends, by searching
+backwards for a header with the given name. buffer_start is the offset of the
+buffer in the package.
+*/
+pub fn find_trailing_entry(buffer: &[u8], buffer_start: u64, name: &str) -> Option<ZipEntry> {
+    let last = buffer
+        .len()
+        .checked_sub(LOCAL_HEADER_SIZE as usize + name.len())?;
+    (0..=last)
+        .rev()
+        .filter(|i| buffer[*i..*i + 4] == LOCAL_FILE_HEADER.to_le_bytes())
+        .filter_map(|i| {
+            let entry = read_local_entry(&buffer[i..], buffer_start + i as u64).ok()??;
+            let end = (i as u64 + entry.data_offset()).checked_add(entry.compressed_size);
+            (entry.name == name && end == Some(buffer.len() as u64)).then_some(entry)
+        })
+        .next()
 }
 
-pub fn unzip_scene_layer_info(
-    zip_archive: &mut ZipArchive<File>,
-) -> Result<Value, Box<dyn error::Error>> {
-    let zip_file = zip_archive.by_name("3dSceneLayer.json.gz")?;
-    let json = decode_json_gz(zip_file)?;
-    let val = serde_json::from_str::<Value>(&json)?;
-    Ok(val)
+pub fn inflate_zip_entry(entry: &ZipEntry, data: &[u8]) -> Result<Vec<u8>> {
+    if entry.flags & 1 != 0 {
+        return Err(malformed_zip(&format!("{} is encrypted", entry.name)));
+    }
+    match entry.method {
+        0 => Ok(data.to_vec()),
+        8 => {
+            let mut buffer = Vec::new();
+            flate2::read::DeflateDecoder::new(data).read_to_end(&mut buffer)?;
+            Ok(buffer)
+        }
+        method => Err(malformed_zip(&format!(
+            "{} uses unsupported compression method {}",
+            entry.name, method
+        ))),
+    }
+}
+
+/*
+I3S binary buffers are little endian and tightly packed. Reads past the end
+of the buffer are reported as malformed buffers rather than panicking.
+*/
+#[derive(Debug)]
+pub struct BufferReader<'a> {
+    buffer: &'a [u8],
+    position: usize,
+}
+
+macro_rules! read_le {
+    ($name:ident, $type:ty) => {
+        pub fn $name(&mut self) -> Result<$type> {
+            let bytes = self.read_bytes(std::mem::size_of::<$type>())?;
+            Ok(<$type>::from_le_bytes(bytes.try_into().unwrap()))
+        }
+    };
+}
+
+impl<'a> BufferReader<'a> {
+    pub fn new(buffer: &'a [u8]) -> Self {
+        Self {
+            buffer,
+            position: 0,
+        }
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.buffer.len() - self.position
+    }
+
+    pub fn remaining_bytes(&self) -> &'a [u8] {
+        &self.buffer[self.position..]
+    }
+
+    pub fn skip(&mut self, count: usize) -> Result<()> {
+        self.read_bytes(count).map(|_| ())
+    }
+
+    pub fn align(&mut self, alignment: usize) -> Result<()> {
+        let padding = (alignment - self.position % alignment) % alignment;
+        self.skip(padding)
+    }
+
+    pub fn read_bytes(&mut self, count: usize) -> Result<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(count)
+            .filter(|end| *end <= self.buffer.len())
+            .ok_or_else(|| {
+                I3sError::MalformedBuffer(format!(
+                    "cannot read {} bytes at offset {} of a {} byte buffer",
+                    count,
+                    self.position,
+                    self.buffer.len()
+                ))
+            })?;
+        let bytes = &self.buffer[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    read_le!(read_u8, u8);
+    read_le!(read_u16, u16);
+    read_le!(read_u32, u32);
+    read_le!(read_u64, u64);
+    read_le!(read_i8, i8);
+    read_le!(read_i16, i16);
+    read_le!(read_i32, i32);
+    read_le!(read_i64, i64);
+    read_le!(read_f32, f32);
+    read_le!(read_f64, f64);
 }
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+
+use crate::error::{I3sError, Result};
+use crate::geometry::PointData;
+use crate::io::BufferReader;
+
+/*
+Pure Rust port of the decoding half of Esri's LEPCC library. Every blob
+starts with a 16 byte top header (file key, version, checksum) followed by a
+blob specific header whose first field is the total blob size.
+*/
+
+const XYZ_KEY: &[u8; 10] = b"LEPCC     ";
+const RGB_KEY: &[u8; 10] = b"ClusterRGB";
+const INTENSITY_KEY: &[u8; 10] = b"Intensity ";
+
+fn malformed(reason: &str) -> I3sError {
+    I3sError::MalformedBuffer(format!("lepcc: {}", reason))
+}
+
+fn read_top_header<'a>(buffer: &'a [u8], key: &[u8; 10]) -> Result<BufferReader<'a>> {
+    let mut reader = BufferReader::new(buffer);
+    if reader.read_bytes(10)? != key {
+        return Err(malformed(&format!(
+            "expected {} blob",
+            String::from_utf8_lossy(key).trim_end()
+        )));
+    }
+    let version = reader.read_u16()?;
+    if version != 1 {
+        return Err(malformed(&format!("unsupported version {}", version)));
+    }
+    let _checksum = reader.read_u32()?;
+    let blob_size = reader.read_i64()?;
+    if blob_size < 24 || blob_size as usize > buffer.len() {
+        return Err(malformed("blob size exceeds buffer"));
+    }
+    let mut reader = BufferReader::new(&buffer[..blob_size as usize]);
+    reader.skip(24)?;
+    Ok(reader)
+}
+
+/*
+Values are packed LSB first into little endian 32 bit words, but only the
+bytes that hold bits are stored.
+*/
+fn unpack_bits(reader: &mut BufferReader, count: usize, bits: u32) -> Result<Vec<u32>> {
+    if bits == 0 {
+        return Ok(vec![0; count]);
+    }
+    let size = (count * bits as usize).div_ceil(8);
+    let bytes = reader.read_bytes(size)?;
+    let mut values = Vec::with_capacity(count);
+    let mut offset = 0usize;
+    for _ in 0..count {
+        let mut value = 0u32;
+        for bit in 0..bits {
+            let byte = bytes[offset >> 3];
+            value |= (((byte >> (offset & 7)) & 1) as u32) << bit;
+            offset += 1;
+        }
+        values.push(value);
+    }
+    Ok(values)
+}
+
+/*
+Zero bit values take no space, so the count is checked against the number of
+values the caller expects before anything is allocated.
+*/
+fn decode_bit_stuffed(reader: &mut BufferReader, max_count: usize) -> Result<Vec<u32>> {
+    let header = reader.read_u8()?;
+    let count = match header >> 6 {
+        0 => reader.read_u32()? as usize,
+        1 => reader.read_u16()? as usize,
+        2 => reader.read_u8()? as usize,
+        _ => return Err(malformed("invalid bit stuffer header")),
+    };
+    if count > max_count {
+        return Err(malformed("bit stuffed array is longer than expected"));
+    }
+    let bits = (header & 31) as u32;
+    if header & 32 == 0 {
+        return unpack_bits(reader, count, bits);
+    }
+    // values are indices into a lookup table whose first entry is an implicit 0
+    let lut_size = reader.read_u8()? as usize;
+    if lut_size < 2 {
+        return Err(malformed("invalid bit stuffer lookup table"));
+    }
+    let mut lut = vec![0];
+    lut.extend(unpack_bits(reader, lut_size - 1, bits)?);
+    let index_bits = usize::BITS - (lut_size - 1).leading_zeros();
+    unpack_bits(reader, count, index_bits)?
+        .into_iter()
+        .map(|index| {
+            lut.get(index as usize)
+                .copied()
+                .ok_or_else(|| malformed("bit stuffer index out of range"))
+        })
+        .collect()
+}
+
+fn checked_point_count(count: u32, reader: &BufferReader) -> Result<usize> {
+    // every encoding needs at least a bit per point
+    if count as usize > reader.remaining() * 8 + 1 {
+        return Err(malformed("point count exceeds blob size"));
+    }
+    Ok(count as usize)
+}
+
+/*
+Points are quantized to a grid of twice the maximum error, sorted into rows
+by y and stored as row deltas, points per row, x deltas within a row and
+absolute z.
+*/
+pub fn decode_xyz(buffer: &[u8]) -> Result<Vec<[f64; 3]>> {
+    let mut reader = read_top_header(buffer, XYZ_KEY)?;
+    let origin = [reader.read_f64()?, reader.read_f64()?, reader.read_f64()?];
+    let max_error = [reader.read_f64()?, reader.read_f64()?, reader.read_f64()?];
+    let count = checked_point_count(reader.read_u32()?, &reader)?;
+    let _reserved = reader.read_i32()?;
+
+    let y_deltas = decode_bit_stuffed(&mut reader, count)?;
+    let points_per_row = decode_bit_stuffed(&mut reader, count)?;
+    let x_deltas = decode_bit_stuffed(&mut reader, count)?;
+    let z_values = decode_bit_stuffed(&mut reader, count)?;
+    if y_deltas.len() != points_per_row.len()
+        || x_deltas.len() != count
+        || z_values.len() != count
+        || points_per_row.iter().map(|n| *n as usize).sum::<usize>() != count
+    {
+        return Err(malformed("inconsistent xyz point counts"));
+    }
+
+    let step = max_error.map(|error| 2.0 * error);
+    let mut positions = Vec::with_capacity(count);
+    let mut y = 0u64;
+    for (y_delta, row_count) in y_deltas.iter().zip(&points_per_row) {
+        y += *y_delta as u64;
+        let mut x = 0u64;
+        for _ in 0..*row_count {
+            let index = positions.len();
+            x += x_deltas[index] as u64;
+            positions.push([
+                origin[0] + x as f64 * step[0],
+                origin[1] + y as f64 * step[1],
+                origin[2] + z_values[index] as f64 * step[2],
+            ]);
+        }
+    }
+    Ok(positions)
+}
+
+/*
+Huffman codes are stored MSB first in little endian 32 bit words. The code
+table holds a bit stuffed array of code lengths for the symbol range
+[i0, i1), which may wrap around the alphabet, followed by the codes.
+*/
+struct Huffman {
+    codes: HashMap<(u32, u32), u32>,
+    max_length: u32,
+}
+
+struct MsbReader<'a> {
+    bytes: &'a [u8],
+    word: usize,
+    position: u32,
+}
+
+impl MsbReader<'_> {
+    fn read_bit(&mut self) -> Result<u32> {
+        let start = self.word * 4;
+        let mut word = [0u8; 4];
+        let available = self.bytes.len().saturating_sub(start).min(4);
+        if available == 0 {
+            return Err(malformed("huffman data ends early"));
+        }
+        word[..available].copy_from_slice(&self.bytes[start..start + available]);
+        let bit = (u32::from_le_bytes(word) >> (31 - self.position)) & 1;
+        self.position += 1;
+        if self.position == 32 {
+            self.position = 0;
+            self.word += 1;
+        }
+        Ok(bit)
+    }
+
+    fn bytes_read(&self) -> usize {
+        self.word * 4 + if self.position > 0 { 4 } else { 0 }
+    }
+}
+
+impl Huffman {
+    fn read(reader: &mut BufferReader) -> Result<Self> {
+        let version = reader.read_i32()?;
+        let size = reader.read_i32()?;
+        let i0 = reader.read_i32()?;
+        let i1 = reader.read_i32()?;
+        if version < 2 || size <= 0 || i0 < 0 || i0 >= i1 || i1 - i0 > size {
+            return Err(malformed("invalid huffman code table"));
+        }
+        let lengths = decode_bit_stuffed(reader, (i1 - i0) as usize)?;
+        if lengths.len() != (i1 - i0) as usize || lengths.iter().any(|length| *length > 32) {
+            return Err(malformed("invalid huffman code lengths"));
+        }
+        let symbol = |i: i32| (if i < size { i } else { i - size }) as u32;
+
+        let mut bits = MsbReader {
+            bytes: reader.remaining_bytes(),
+            word: 0,
+            position: 0,
+        };
+        let mut codes = HashMap::new();
+        for (i, length) in (i0..i1).zip(&lengths) {
+            if *length == 0 {
+                continue;
+            }
+            let mut code = 0;
+            for _ in 0..*length {
+                code = (code << 1) | bits.read_bit()?;
+            }
+            codes.insert((*length, code), symbol(i));
+        }
+        reader.skip(bits.bytes_read().min(reader.remaining()))?;
+        Ok(Self {
+            codes,
+            max_length: lengths.into_iter().max().unwrap_or(0),
+        })
+    }
+
+    fn decode(&self, bits: &mut MsbReader) -> Result<u32> {
+        let mut code = 0;
+        for length in 1..=self.max_length {
+            code = (code << 1) | bits.read_bit()?;
+            if let Some(symbol) = self.codes.get(&(length, code)) {
+                return Ok(*symbol);
+            }
+        }
+        Err(malformed("invalid huffman code"))
+    }
+}
+
+const COLOR_LOOKUP_NONE: u8 = 0;
+const COLOR_INDEX_UNCOMPRESSED: u8 = 0;
+const COLOR_INDEX_HUFFMAN: u8 = 1;
+
+/*
+Colors are either stored raw or as indices into a color map of at most 256
+RGBA entries. The indices may be huffman coded.
+*/
+pub fn decode_rgb(buffer: &[u8]) -> Result<Vec<[u8; 3]>> {
+    let mut reader = read_top_header(buffer, RGB_KEY)?;
+    let count = checked_point_count(reader.read_u32()?, &reader)?;
+    let color_count = reader.read_u16()? as usize;
+    let lookup = reader.read_u8()?;
+    let compression = reader.read_u8()?;
+
+    if lookup == COLOR_LOOKUP_NONE {
+        return Ok(reader
+            .read_bytes(count * 3)?
+            .chunks_exact(3)
+            .map(|rgb| [rgb[0], rgb[1], rgb[2]])
+            .collect());
+    }
+    if color_count == 0 || color_count > 256 {
+        return Err(malformed("invalid color map size"));
+    }
+    let color_map: Vec<[u8; 3]> = reader
+        .read_bytes(color_count * 4)?
+        .chunks_exact(4)
+        .map(|rgba| [rgba[0], rgba[1], rgba[2]])
+        .collect();
+    let indices: Vec<u32> = match compression {
+        COLOR_INDEX_UNCOMPRESSED => reader
+            .read_bytes(count)?
+            .iter()
+            .map(|index| *index as u32)
+            .collect(),
+        COLOR_INDEX_HUFFMAN => {
+            let huffman = Huffman::read(&mut reader)?;
+            let mut bits = MsbReader {
+                bytes: reader.remaining_bytes(),
+                word: 0,
+                position: 0,
+            };
+            (0..count)
+                .map(|_| huffman.decode(&mut bits))
+                .collect::<Result<_>>()?
+        }
+        method => {
+            return Err(malformed(&format!(
+                "unsupported color index compression {}",
+                method
+            )))
+        }
+    };
+    indices
+        .into_iter()
+        .map(|index| {
+            color_map
+                .get(index as usize)
+                .copied()
+                .ok_or_else(|| malformed("color index out of range"))
+        })
+        .collect()
+}
+
+/*
+Intensities are scaled down by a common factor and either stored raw (8 or
+16 bits per point) or bit stuffed.
+*/
+pub fn decode_intensity(buffer: &[u8]) -> Result<Vec<u16>> {
+    let mut reader = read_top_header(buffer, INTENSITY_KEY)?;
+    let count = checked_point_count(reader.read_u32()?, &reader)?;
+    let scale = reader.read_u16()?.max(1);
+    let bits_per_point = reader.read_u8()?;
+    let _reserved = reader.read_u8()?;
+
+    let values: Vec<u32> = match bits_per_point {
+        8 => reader
+            .read_bytes(count)?
+            .iter()
+            .map(|value| *value as u32)
+            .collect(),
+        16 => (0..count)
+            .map(|_| reader.read_u16().map(|value| value as u32))
+            .collect::<Result<_>>()?,
+        _ => decode_bit_stuffed(&mut reader, count)?,
+    };
+    if values.len() != count {
+        return Err(malformed("inconsistent intensity point count"));
+    }
+    Ok(values
+        .into_iter()
+        .map(|value| (value * scale as u32).min(u16::MAX as u32) as u16)
+        .collect())
+}
+
+pub fn decode_points(
+    xyz: &[u8],
+    rgb: Option<&[u8]>,
+    intensity: Option<&[u8]>,
+) -> Result<PointData> {
+    let mut points = PointData {
+        positions: decode_xyz(xyz)?,
+        ..Default::default()
+    };
+    if let Some(rgb) = rgb {
+        points.colors = decode_rgb(rgb)?;
+    }
+    if let Some(intensity) = intensity {
+        points.intensities = decode_intensity(intensity)?;
+    }
+    let count = points.positions.len();
+    if (!points.colors.is_empty() && points.colors.len() != count)
+        || (!points.intensities.is_empty() && points.intensities.len() != count)
+    {
+        return Err(malformed("attribute point count does not match positions"));
+    }
+    Ok(points)
+}
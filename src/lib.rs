@@ -1,12 +1,26 @@
+pub mod attribute;
+pub mod auth;
 pub mod bld;
+pub mod cache;
 pub mod cmn;
+#[cfg(feature = "draco")]
+pub mod draco;
+mod error;
+pub mod geometry;
+pub mod hash;
 mod i3s;
 pub mod io;
+pub mod lepcc;
+pub mod lod;
 pub mod pcl;
 pub mod psl;
 pub mod stream;
+pub mod texture;
 
+pub use error::I3sError;
 pub use i3s::{
-    get_layer_type, Building, DDDObject, I3SFormat, I3SInfo, I3SProfile, IntegratedMesh, Point,
-    PointCloud, SceneLayer, SceneLayerPackage, Service,
+    get_layer_type, open, Building, DDDObject, Format, I3SFormat, I3SFormatExt, I3SInfo,
+    I3SProfile, IntegratedMesh, MemoryStore, NodeIndex, Point, PointCloud, Profile,
+    RemoteSceneLayerPackage, SceneLayer, SceneLayerFolder, SceneLayerPackage, Service,
+    ServiceBuilder,
 };
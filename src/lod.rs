@@ -0,0 +1,420 @@
+use std::collections::HashSet;
+
+use crate::cmn;
+use crate::error::{I3sError, Result};
+use crate::pcl;
+
+/*
+Level of detail selection the way ArcGIS clients do it. Nodes outside the
+view frustum are culled, and a visible node is rendered unless its lodThreshold
+says it is too coarse for its size on screen, in which case its children are
+visited instead. The camera and the bounding volumes must share a cartesian
+frame, see Frame for layers in a geographic CRS.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LodMetric {
+    // the diameter of the node's bounding sphere on screen, in pixels
+    MaxScreenThreshold,
+    // the area of the node's bounding sphere on screen, in square pixels
+    MaxScreenThresholdSQ,
+    // the area per point of a point cloud node, in square units of its OBB
+    DensityThreshold,
+}
+
+impl LodMetric {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "maxScreenThreshold" => Some(LodMetric::MaxScreenThreshold),
+            "maxScreenThresholdSQ" => Some(LodMetric::MaxScreenThresholdSQ),
+            "density-threshold" => Some(LodMetric::DensityThreshold),
+            _ => None,
+        }
+    }
+
+    fn parse(name: &str) -> Result<Self> {
+        Self::from_name(name).ok_or_else(|| {
+            I3sError::MalformedBuffer(format!("unsupported lod selection metric: {}", name))
+        })
+    }
+}
+
+// points of a point cloud are refined until they are about a pixel apart
+const DENSITY_TARGET: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub center: [f64; 3],
+    pub radius: f64,
+}
+
+impl Bounds {
+    pub fn from_obb(obb: &cmn::OBB) -> Self {
+        Self {
+            center: obb.center,
+            radius: length(obb.half_size),
+        }
+    }
+
+    pub fn from_mbs(mbs: [f64; 4]) -> Self {
+        Self {
+            center: [mbs[0], mbs[1], mbs[2]],
+            radius: mbs[3],
+        }
+    }
+}
+
+// WGS84, which is also close enough for layers in CGCS2000
+const SEMI_MAJOR_AXIS: f64 = 6_378_137.0;
+const ECCENTRICITY_SQ: f64 = 6.694_379_990_14e-3;
+
+/*
+The frame the centers of a layer's bounding volumes are in. Layers in a
+geographic CRS give centers as longitude and latitude in degrees and a height
+in metres, but half sizes and radii in metres. Their centers are converted to
+earth-centered, earth-fixed coordinates, so their camera has to be in ECEF too.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Frame {
+    #[default]
+    Cartesian,
+    Geographic,
+}
+
+impl Frame {
+    pub fn from_spatial_reference(spatial_reference: Option<&cmn::SpatialReference>) -> Self {
+        let Some(spatial_reference) = spatial_reference else {
+            return Frame::Cartesian;
+        };
+        // WGS84 and CGCS2000, the geographic CRSs global scene layers can use
+        let wkids = [spatial_reference.wkid, spatial_reference.latest_wkid];
+        let geographic_wkid = wkids
+            .into_iter()
+            .flatten()
+            .any(|wkid| wkid == 4326 || wkid == 4490);
+        let wkt = spatial_reference.wkt.trim_start();
+        if geographic_wkid || wkt.starts_with("GEOGCS") || wkt.starts_with("GEOGCRS") {
+            Frame::Geographic
+        } else {
+            Frame::Cartesian
+        }
+    }
+
+    pub fn bounds(self, bounds: Bounds) -> Bounds {
+        match self {
+            Frame::Cartesian => bounds,
+            Frame::Geographic => Bounds {
+                center: geographic_to_ecef(bounds.center),
+                ..bounds
+            },
+        }
+    }
+}
+
+fn geographic_to_ecef([longitude, latitude, height]: [f64; 3]) -> [f64; 3] {
+    let (sin_longitude, cos_longitude) = longitude.to_radians().sin_cos();
+    let (sin_latitude, cos_latitude) = latitude.to_radians().sin_cos();
+    let normal = SEMI_MAJOR_AXIS / (1.0 - ECCENTRICITY_SQ * sin_latitude * sin_latitude).sqrt();
+    [
+        (normal + height) * cos_latitude * cos_longitude,
+        (normal + height) * cos_latitude * sin_longitude,
+        (normal * (1.0 - ECCENTRICITY_SQ) + height) * sin_latitude,
+    ]
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn length(a: [f64; 3]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+fn normalize(a: [f64; 3]) -> [f64; 3] {
+    let length = length(a);
+    if length > 0.0 {
+        a.map(|v| v / length)
+    } else {
+        a
+    }
+}
+
+fn plane(forward: [f64; 3], side: [f64; 3], half_angle: f64) -> [f64; 3] {
+    let (sin, cos) = half_angle.sin_cos();
+    normalize([
+        forward[0] * sin + side[0] * cos,
+        forward[1] * sin + side[1] * cos,
+        forward[2] * sin + side[2] * cos,
+    ])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    Culled,
+    Render,
+    Refine,
+}
+
+/*
+A perspective camera. fov is the vertical field of view in radians and
+viewport the width and height in pixels. The frustum planes point inwards,
+and are only defined if up is not parallel to the direction.
+*/
+#[derive(Debug, Clone)]
+pub struct Camera {
+    pub position: [f64; 3],
+    pub direction: [f64; 3],
+    pub up: [f64; 3],
+    pub fov: f64,
+    pub viewport: [u32; 2],
+    planes: [[f64; 3]; 5],
+}
+
+impl Camera {
+    pub fn new(
+        position: [f64; 3],
+        direction: [f64; 3],
+        up: [f64; 3],
+        fov: f64,
+        viewport: [u32; 2],
+    ) -> Result<Self> {
+        let forward = normalize(direction);
+        let side = cross(forward, up);
+        if length(side) <= f64::EPSILON * length(up) {
+            return Err(I3sError::InvalidCamera(format!(
+                "up {:?} is parallel to direction {:?}",
+                up, direction
+            )));
+        }
+        let right = normalize(side);
+        let up = cross(right, forward);
+        let half_height = fov / 2.0;
+        let aspect = viewport[0] as f64 / viewport[1].max(1) as f64;
+        let half_width = (half_height.tan() * aspect).atan();
+        let planes = [
+            forward,
+            plane(forward, right.map(|v| -v), half_width),
+            plane(forward, right, half_width),
+            plane(forward, up.map(|v| -v), half_height),
+            plane(forward, up, half_height),
+        ];
+        Ok(Self {
+            position,
+            direction: forward,
+            up,
+            fov,
+            viewport,
+            planes,
+        })
+    }
+
+    // conservative, a sphere that only touches the frustum counts as visible
+    pub fn is_visible(&self, bounds: &Bounds) -> bool {
+        let offset = sub(bounds.center, self.position);
+        self.planes
+            .iter()
+            .all(|normal| dot(offset, *normal) >= -bounds.radius)
+    }
+
+    // the size in pixels of a unit at the distance of the bounds, infinite inside them
+    pub fn pixels_per_unit(&self, bounds: &Bounds) -> f64 {
+        let distance = length(sub(bounds.center, self.position)) - bounds.radius;
+        if distance <= 0.0 {
+            return f64::INFINITY;
+        }
+        self.viewport[1] as f64 / (2.0 * distance * (self.fov / 2.0).tan())
+    }
+
+    pub fn screen_size(&self, bounds: &Bounds) -> f64 {
+        2.0 * bounds.radius * self.pixels_per_unit(bounds)
+    }
+
+    /*
+    Whether a node is too coarse and its children should be rendered instead.
+    A quality above 1 refines further than ArcGIS clients do by default and a
+    quality below 1 less. Nodes without a threshold are always refined.
+    */
+    pub fn needs_refinement(
+        &self,
+        metric: LodMetric,
+        bounds: &Bounds,
+        threshold: Option<f64>,
+        quality: f64,
+    ) -> bool {
+        let Some(threshold) = threshold else {
+            return true;
+        };
+        match metric {
+            LodMetric::MaxScreenThreshold => self.screen_size(bounds) * quality > threshold,
+            LodMetric::MaxScreenThresholdSQ => {
+                let radius = self.screen_size(bounds) / 2.0;
+                std::f64::consts::PI * radius * radius * quality * quality > threshold
+            }
+            LodMetric::DensityThreshold => {
+                let pixels_per_unit = self.pixels_per_unit(bounds);
+                threshold * pixels_per_unit * pixels_per_unit * quality * quality > DENSITY_TARGET
+            }
+        }
+    }
+
+    pub fn classify(
+        &self,
+        metric: LodMetric,
+        bounds: &Bounds,
+        threshold: Option<f64>,
+        is_leaf: bool,
+        quality: f64,
+    ) -> Selection {
+        if !self.is_visible(bounds) {
+            Selection::Culled
+        } else if !is_leaf && self.needs_refinement(metric, bounds, threshold, quality) {
+            Selection::Refine
+        } else {
+            Selection::Render
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LodNode {
+    pub bounds: Bounds,
+    pub threshold: Option<f64>,
+    pub children: Vec<usize>,
+}
+
+/*
+Selects the nodes to render, starting at root. node describes a node by its
+index and returns None for nodes that do not exist, which are skipped. Like
+I3SFormatExt::walk_node_index, a node that is reached twice is an error
+rather than a reason to loop forever.
+*/
+pub fn select<F>(
+    camera: &Camera,
+    quality: f64,
+    metric: LodMetric,
+    root: usize,
+    node: F,
+) -> Result<Vec<usize>>
+where
+    F: Fn(usize) -> Option<LodNode>,
+{
+    let mut selected = Vec::new();
+    let mut indices = vec![root];
+    let mut visited = HashSet::new();
+    while let Some(index) = indices.pop() {
+        if !visited.insert(index) {
+            return Err(I3sError::MalformedNodePage {
+                path: format!("nodes/{}", index),
+                reason: "node is referenced more than once".to_string(),
+            });
+        }
+        let Some(lod_node) = node(index) else {
+            continue;
+        };
+        match camera.classify(
+            metric,
+            &lod_node.bounds,
+            lod_node.threshold,
+            lod_node.children.is_empty(),
+            quality,
+        ) {
+            Selection::Culled => {}
+            Selection::Render => selected.push(index),
+            Selection::Refine => indices.extend(lod_node.children.iter().rev()),
+        }
+    }
+    Ok(selected)
+}
+
+fn page_node<T, N>(
+    node_pages: &[T],
+    nodes: impl Fn(&T) -> &[N],
+    nodes_per_page: usize,
+    index: usize,
+) -> Option<&N> {
+    let page = node_pages.get(index / nodes_per_page.max(1))?;
+    nodes(page).get(index % nodes_per_page.max(1))
+}
+
+pub fn select_mesh_nodes(
+    camera: &Camera,
+    quality: f64,
+    frame: Frame,
+    definition: &cmn::NodePageDefinition,
+    node_pages: &[cmn::NodePage],
+) -> Result<Vec<usize>> {
+    let metric = LodMetric::parse(&definition.lod_selection_metric_type)?;
+    let nodes_per_page = definition.nodes_per_page as usize;
+    select(camera, quality, metric, definition.root_index, |index| {
+        let node = page_node(node_pages, |page| &page.nodes, nodes_per_page, index)?;
+        Some(LodNode {
+            bounds: frame.bounds(Bounds::from_obb(&node.obb)),
+            threshold: node.lod_threshold.map(f64::from),
+            children: node.children.clone(),
+        })
+    })
+}
+
+pub fn select_point_cloud_nodes(
+    camera: &Camera,
+    quality: f64,
+    frame: Frame,
+    index: &pcl::Index,
+    node_pages: &[pcl::NodePage],
+) -> Result<Vec<usize>> {
+    let metric = LodMetric::parse(&index.lod_selection_metric_type)?;
+    select(camera, quality, metric, 0, |i| {
+        let node = page_node(node_pages, |page| &page.nodes, index.nodes_per_page, i)?;
+        Some(LodNode {
+            bounds: frame.bounds(Bounds::from_obb(&node.obb)),
+            threshold: node.lod_threshold,
+            children: (node.first_child..node.first_child + node.child_count).collect(),
+        })
+    })
+}
+
+/*
+For the node index documents of layers before 1.7, see
+I3SFormatExt::select_node_index. The first lodSelection with a known metric
+decides, and nodes without one are refined.
+*/
+pub fn classify_node_index(
+    camera: &Camera,
+    quality: f64,
+    frame: Frame,
+    node: &cmn::NodeIndexDocument,
+) -> Selection {
+    let Some(bounds) = node
+        .obb
+        .as_ref()
+        .map(Bounds::from_obb)
+        .or(node.mbs.map(Bounds::from_mbs))
+        .map(|bounds| frame.bounds(bounds))
+    else {
+        return if node.is_leaf() {
+            Selection::Render
+        } else {
+            Selection::Refine
+        };
+    };
+    let (metric, threshold) = node
+        .lod_selection
+        .iter()
+        .find_map(|selection| {
+            let metric = LodMetric::from_name(&selection.metric_type)?;
+            Some((metric, selection.max_error))
+        })
+        .unwrap_or((LodMetric::MaxScreenThreshold, None));
+    camera.classify(metric, &bounds, threshold, node.is_leaf(), quality)
+}
@@ -275,7 +275,7 @@ pub struct BitfieldLabel {
     pub label: String,
 }
 
-#[derive(Default, Debug, Deserialize)]
+#[derive(Default, Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Node {
     pub resource_id: usize,
@@ -293,3 +293,36 @@ pub struct NodePage {
 }
 
 impl ZipFileReader for NodePage {}
+
+/*
+Packages store LEPCC blobs with an extension naming the encoding, e.g.
+nodes/0/geometries/0.bin.pccxyz, and everything else gzipped.
+*/
+fn local_extension(encoding: &str) -> &'static str {
+    match encoding {
+        "lepcc-xyz" => ".bin.pccxyz",
+        "lepcc-rgb" => ".bin.pccrgb",
+        "lepcc-intensity" => ".bin.pccint",
+        _ => ".bin.gz",
+    }
+}
+
+impl Node {
+    pub fn geometry_resource_path(&self, encoding: &str, local: bool) -> String {
+        let mut path = format!("nodes/{}/geometries/0", self.resource_id);
+        if local {
+            path.push_str(local_extension(encoding));
+        }
+        path
+    }
+
+    pub fn attribute_resource_path(&self, attribute: &AttributeInfo, local: bool) -> String {
+        let mut path = format!("nodes/{}/attributes/{}", self.resource_id, attribute.key);
+        if local {
+            path.push_str(local_extension(
+                attribute.encoding.as_deref().unwrap_or_default(),
+            ));
+        }
+        path
+    }
+}
@@ -1,12 +1,38 @@
-use std::error::Error;
-
 use reqwest::Client;
 use serde_json::Value;
+use url::Url;
+
+use crate::cmn::SceneServiceInformation;
+use crate::error::{I3sError, Result};
+
+// the same errors Service reports for a missing resource or a failed request
+fn check_status(url: &Url, resp: reqwest::Response) -> Result<reqwest::Response> {
+    let status = resp.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(I3sError::ResourceNotFound(url.to_string()));
+    }
+    if !status.is_success() {
+        return Err(I3sError::HttpStatus {
+            path: url.to_string(),
+            status: status.as_u16(),
+        });
+    }
+    Ok(resp)
+}
 
-pub async fn request_scene_layer_info(
+// base is the SceneServer root, e.g. https://host/.../SceneServer/
+pub async fn request_scene_service_info(
     client: &Client,
-) -> Result<serde_json::Value, Box<dyn Error>> {
-    let resp = client.get("layers/0").send().await?;
+    base: &Url,
+) -> Result<SceneServiceInformation> {
+    let resp = check_status(base, client.get(base.clone()).send().await?)?;
+    let val = resp.json::<SceneServiceInformation>().await?;
+    Ok(val)
+}
+
+pub async fn request_scene_layer_info(client: &Client, base: &Url, layer: usize) -> Result<Value> {
+    let url = base.join(&format!("layers/{}", layer))?;
+    let resp = check_status(&url, client.get(url.clone()).send().await?)?;
     let val = resp.json::<Value>().await?;
     Ok(val)
 }
@@ -0,0 +1,319 @@
+use crate::cmn;
+use crate::error::{I3sError, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    Jpg,
+    Png,
+    Dds,
+    Ktx2,
+}
+
+impl TextureFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "jpg" | "jpeg" => Some(TextureFormat::Jpg),
+            "png" => Some(TextureFormat::Png),
+            "dds" => Some(TextureFormat::Dds),
+            "ktx2" => Some(TextureFormat::Ktx2),
+            _ => None,
+        }
+    }
+
+    // the mime types textures of layers before 1.7 declare as their encoding
+    pub fn from_encoding(encoding: &str) -> Option<Self> {
+        match encoding.to_ascii_lowercase().as_str() {
+            "image/jpeg" | "image/jpg" => Some(TextureFormat::Jpg),
+            "image/png" => Some(TextureFormat::Png),
+            "image/vnd-ms.dds" | "image/vnd.ms-dds" | "image/dds" => Some(TextureFormat::Dds),
+            "image/ktx2" => Some(TextureFormat::Ktx2),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            TextureFormat::Jpg => "jpg",
+            TextureFormat::Png => "png",
+            TextureFormat::Dds => "dds",
+            TextureFormat::Ktx2 => "ktx2",
+        }
+    }
+
+    /*
+    Texture set definitions may be wrong about the actual encoding (png data
+    stored as jpg is common), so the magic number wins over the declared format.
+    */
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(TextureFormat::Jpg)
+        } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(TextureFormat::Png)
+        } else if data.starts_with(b"DDS ") {
+            Some(TextureFormat::Dds)
+        } else if data.starts_with(b"\xabKTX 20\xbb\r\n\x1a\n") {
+            Some(TextureFormat::Ktx2)
+        } else {
+            None
+        }
+    }
+}
+
+pub const DEFAULT_PREFERENCES: [TextureFormat; 4] = [
+    TextureFormat::Jpg,
+    TextureFormat::Png,
+    TextureFormat::Ktx2,
+    TextureFormat::Dds,
+];
+
+#[derive(Debug, Clone)]
+pub struct Texture {
+    pub format: TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+    #[cfg(feature = "image")]
+    pub rgba: Option<Vec<u8>>,
+}
+
+fn be_u16(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?) as u32)
+}
+
+fn u32_at(data: &[u8], offset: usize, big_endian: bool) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?.try_into().ok()?;
+    Some(if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    })
+}
+
+fn jpg_size(data: &[u8]) -> Option<(u32, u32)> {
+    let mut offset = 2;
+    while offset + 4 <= data.len() {
+        if data[offset] != 0xff {
+            return None;
+        }
+        let marker = data[offset + 1];
+        if marker == 0xff {
+            offset += 1;
+            continue;
+        }
+        let length = be_u16(data, offset + 2)? as usize;
+        // start of frame markers, excluding DHT (c4), JPG (c8) and DAC (cc)
+        if matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+            return Some((be_u16(data, offset + 7)?, be_u16(data, offset + 5)?));
+        }
+        offset += 2 + length;
+    }
+    None
+}
+
+pub fn texture_size(format: TextureFormat, data: &[u8]) -> Option<(u32, u32)> {
+    match format {
+        TextureFormat::Jpg => jpg_size(data),
+        TextureFormat::Png => Some((u32_at(data, 16, true)?, u32_at(data, 20, true)?)),
+        TextureFormat::Dds => Some((u32_at(data, 16, false)?, u32_at(data, 12, false)?)),
+        TextureFormat::Ktx2 => Some((u32_at(data, 20, false)?, u32_at(data, 24, false)?)),
+    }
+}
+
+#[cfg(feature = "image")]
+fn decode_rgba(format: TextureFormat, data: &[u8]) -> Option<Vec<u8>> {
+    let format = match format {
+        TextureFormat::Jpg => image::ImageFormat::Jpeg,
+        TextureFormat::Png => image::ImageFormat::Png,
+        // block compressed formats are meant to be uploaded to the GPU as they are
+        TextureFormat::Dds | TextureFormat::Ktx2 => return None,
+    };
+    image::load_from_memory_with_format(data, format)
+        .ok()
+        .map(|image| image.into_rgba8().into_raw())
+}
+
+impl Texture {
+    pub fn new(declared: TextureFormat, data: Vec<u8>) -> Result<Self> {
+        let format = TextureFormat::detect(&data).unwrap_or(declared);
+        let (width, height) = texture_size(format, &data).ok_or_else(|| {
+            I3sError::MalformedBuffer(format!("cannot read {:?} texture header", format))
+        })?;
+        Ok(Self {
+            format,
+            width,
+            height,
+            #[cfg(feature = "image")]
+            rgba: decode_rgba(format, &data),
+            data,
+        })
+    }
+}
+
+/*
+Picks the first preferred format the texture set provides. If none of the
+preferences are available, any format this crate understands is used.
+*/
+pub fn select_format<'a>(
+    definition: &'a cmn::TextureSetDefinition,
+    preferences: &[TextureFormat],
+) -> Option<(TextureFormat, &'a cmn::TextureSetDefinitionFormat)> {
+    let available = || {
+        definition
+            .formats
+            .iter()
+            .filter_map(|format| Some((TextureFormat::from_name(&format.format)?, format)))
+    };
+    preferences
+        .iter()
+        .find_map(|preference| available().find(|(format, _)| format == preference))
+        .or_else(|| available().next())
+}
+
+pub fn texture_set_definition<'a>(
+    information: &'a cmn::SceneLayerInformation,
+    material: &cmn::MeshMaterial,
+) -> Option<&'a cmn::TextureSetDefinition> {
+    let definition = information
+        .material_definitions
+        .as_ref()?
+        .get(usize::try_from(material.definition).ok()?)?;
+    let texture = definition
+        .pbr_metallic_roughness
+        .base_color_texture
+        .as_ref()?;
+    information
+        .texture_set_definitions
+        .as_ref()?
+        .get(usize::try_from(texture.texture_set_definition_id).ok()?)
+}
+
+/*
+Resolves the texture of a node to its format and the candidate paths of the
+resource. REST services address textures by name, while packages append the
+extension and sometimes a .bin and gzip suffix, e.g. 0_0_1.bin.dds.gz.
+*/
+pub fn texture_paths(
+    information: &cmn::SceneLayerInformation,
+    node: &cmn::Node,
+    preferences: &[TextureFormat],
+    local: bool,
+) -> Option<(TextureFormat, Vec<String>)> {
+    let material = node.mesh.as_ref()?.material.as_ref()?;
+    if material.resource < 0 {
+        return None;
+    }
+    let (format, definition) =
+        select_format(texture_set_definition(information, material)?, preferences)?;
+    let base = format!("nodes/{}/textures", material.resource);
+    let paths = if local {
+        vec![
+            format!("{}/{}", base, definition.file_name()),
+            format!("{}/{}.gz", base, definition.file_name()),
+            format!("{}/{}.bin.{}.gz", base, definition.name, definition.format),
+            format!("{}/{}.bin.{}", base, definition.name, definition.format),
+        ]
+    } else {
+        vec![format!("{}/{}", base, definition.name)]
+    };
+    Some((format, paths))
+}
+
+#[derive(Debug, Clone)]
+pub struct LegacyTexture {
+    pub format: TextureFormat,
+    pub atlas: bool,
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LegacyMaterial {
+    pub name: String,
+    pub render_mode: String,
+    pub vertex_colors: bool,
+    pub color: [f32; 4],
+    pub texture: Option<LegacyTexture>,
+}
+
+fn component_reference(reference: &str, id: Option<usize>) -> Option<String> {
+    match reference {
+        "" => id.map(|id| id.to_string()),
+        reference => Some(reference.to_string()),
+    }
+}
+
+/*
+The largest image of a texture is used in the preferred encoding. Its hrefs
+are relative to the shared resource, e.g. ../textures/0_0 from nodes/0/shared.
+*/
+fn resolve_legacy_texture(
+    definition: &cmn::TextureDefinition,
+    shared_path: &str,
+    preferences: &[TextureFormat],
+    local: bool,
+) -> Option<LegacyTexture> {
+    let image = definition
+        .images
+        .iter()
+        .max_by_key(|image| image.size.unwrap_or_default())?;
+    let available = || {
+        definition
+            .encoding
+            .iter()
+            .zip(image.href.iter())
+            .filter_map(|(encoding, href)| Some((TextureFormat::from_encoding(encoding)?, href)))
+    };
+    let (format, href) = preferences
+        .iter()
+        .find_map(|preference| available().find(|(format, _)| format == preference))
+        .or_else(|| available().next())?;
+    let path = cmn::resolve_href(shared_path, href);
+    let extension = format.extension();
+    let paths = if local {
+        vec![
+            format!("{}.{}", path, extension),
+            format!("{}.{}.gz", path, extension),
+            format!("{}.bin.{}.gz", path, extension),
+            format!("{}.bin.{}", path, extension),
+        ]
+    } else {
+        vec![path]
+    };
+    Some(LegacyTexture {
+        format,
+        atlas: definition.atlas,
+        paths,
+    })
+}
+
+/*
+Resolves the material and texture a geometry component references to the
+material color, diffuse with the opacity as alpha, and the candidate paths
+of the texture. Components reference by href or by materialID and textureID.
+*/
+pub fn resolve_legacy_material(
+    shared: &cmn::SharedResource,
+    shared_path: &str,
+    component: &cmn::SingleComponentParams,
+    preferences: &[TextureFormat],
+    local: bool,
+) -> Option<LegacyMaterial> {
+    let material_reference = component_reference(&component.material, component.material_id)?;
+    let material = shared.material(&material_reference)?;
+    let params = material.params.clone().unwrap_or_default();
+    let diffuse = params.diffuse.unwrap_or_default();
+    let channel = |index: usize| diffuse.get(index).copied().unwrap_or(1.0);
+    let opacity = 1.0 - params.transparency.unwrap_or_default();
+    let texture = component_reference(
+        &component.texture,
+        component.texture_id.map(|[texture_id]| texture_id),
+    )
+    .and_then(|reference| shared.texture(&reference))
+    .and_then(|definition| resolve_legacy_texture(definition, shared_path, preferences, local));
+    Some(LegacyMaterial {
+        name: material.name.clone(),
+        render_mode: params.render_mode,
+        vertex_colors: params.vertex_colors,
+        color: [channel(0), channel(1), channel(2), opacity],
+        texture,
+    })
+}
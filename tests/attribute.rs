@@ -0,0 +1,137 @@
+use i3s::attribute::{decode_attribute_buffer, AttributeColumn, AttributeValue};
+use i3s::{cmn, I3SFormatExt, I3sError, MemoryStore};
+
+mod common;
+
+fn storage_info(key: &str, name: &str, values: serde_json::Value) -> serde_json::Value {
+    let mut info = serde_json::json!({
+        "key": key,
+        "name": name,
+        "header": [{"property": "count", "valueType": "UInt32"}]
+    });
+    info.as_object_mut()
+        .unwrap()
+        .extend(values.as_object().unwrap().clone());
+    info
+}
+
+fn heights() -> serde_json::Value {
+    storage_info(
+        "f_1",
+        "HEIGHT",
+        serde_json::json!({"attributeValues": {"valueType": "Float64", "valuesPerElement": 1}}),
+    )
+}
+
+fn names() -> serde_json::Value {
+    storage_info(
+        "f_2",
+        "NAME",
+        serde_json::json!({
+            "ordering": ["attributeByteCounts", "attributeValues"],
+            "attributeByteCounts": {"valueType": "UInt32", "valuesPerElement": 1},
+            "attributeValues": {"valueType": "String", "encoding": "UTF-8", "valuesPerElement": 1}
+        }),
+    )
+}
+
+fn decode(info: serde_json::Value, buffer: &[u8]) -> Result<AttributeColumn, I3sError> {
+    let info: cmn::AttributeStorageInfo = serde_json::from_value(info).unwrap();
+    decode_attribute_buffer(&info, buffer)
+}
+
+fn height_buffer() -> Vec<u8> {
+    let mut buffer = 2u32.to_le_bytes().to_vec();
+    // Float64 values are aligned to 8 bytes
+    buffer.extend([0; 4]);
+    buffer.extend(12.5f64.to_le_bytes());
+    buffer.extend(3.0f64.to_le_bytes());
+    buffer
+}
+
+fn name_buffer() -> Vec<u8> {
+    let mut buffer = [2u32, 6, 6].map(u32::to_le_bytes).concat();
+    buffer.extend(b"Tower\0H\xc3\xb6fe\0");
+    buffer
+}
+
+#[test]
+fn numbers_after_alignment_padding() {
+    let column = decode(heights(), &height_buffer()).unwrap();
+    assert!(matches!(column, AttributeColumn::Float64(ref values) if *values == [12.5, 3.0]));
+    assert!(decode(heights(), &height_buffer()[..20]).is_err());
+}
+
+#[test]
+fn strings_with_byte_counts() {
+    let column = decode(names(), &name_buffer()).unwrap();
+    assert_eq!(column.len(), 2);
+    assert_eq!(column.get(1), Some(AttributeValue::String("Höfe")));
+    assert_eq!(column.get_f64(0), None);
+
+    let mut invalid = name_buffer();
+    invalid[20] = 0xff;
+    assert!(decode(names(), &invalid).is_err());
+}
+
+#[test]
+fn object_ids() {
+    let info = storage_info(
+        "f_0",
+        "OBJECTID",
+        serde_json::json!({"objectIds": {"valueType": "Oid32", "valuesPerElement": 1}}),
+    );
+    let buffer = [3u32, 7, 8, 9].map(u32::to_le_bytes).concat();
+    let column = decode(info, &buffer).unwrap();
+    assert_eq!(column.get_f64(2), Some(9.0));
+}
+
+#[test]
+fn malformed_buffers() {
+    let mut info = heights();
+    info["header"] = serde_json::json!([]);
+    assert!(decode(info, &height_buffer()).is_err());
+    let mut info = heights();
+    info["attributeValues"]["valueType"] = "Decimal".into();
+    assert!(decode(info, &height_buffer()).is_err());
+    let mut info = names();
+    info["ordering"] = serde_json::json!(["attributeValues"]);
+    assert!(decode(info, &name_buffer()).is_err());
+}
+
+#[tokio::test]
+async fn node_attribute_table() {
+    let mut layer = common::mesh_layer();
+    layer["attributeStorageInfo"] = serde_json::json!([heights(), names()]);
+    let mut store = MemoryStore::new();
+    store.insert("3dSceneLayer.json.gz", common::gzip_json(&layer));
+    store.insert(
+        "nodes/4/attributes/f_1/0.bin.gz",
+        common::gzip(&height_buffer()),
+    );
+    store.insert(
+        "nodes/4/attributes/f_2/0.bin.gz",
+        common::gzip(&name_buffer()),
+    );
+    let i3s::I3SInfo::IntegratedMesh(information) = store.scene_layer_information().await.unwrap()
+    else {
+        panic!();
+    };
+    let mut node = common::mesh_node(0, 1);
+    node["mesh"]["attribute"] = serde_json::json!({"resource": 4});
+    let node: cmn::Node = serde_json::from_value(node).unwrap();
+
+    let table = store
+        .attributes(&information, &node, &[42, 7])
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(table.value(7, "HEIGHT").unwrap().as_f64(), Some(3.0));
+    assert_eq!(
+        table.value(42, "NAME"),
+        Some(AttributeValue::String("Tower"))
+    );
+    assert!(table.value(8, "NAME").is_none());
+    // every column needs a value per feature
+    assert!(store.attributes(&information, &node, &[42]).await.is_err());
+}
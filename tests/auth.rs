@@ -0,0 +1,100 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use i3s::auth::Authentication;
+use i3s::{I3SFormat, I3sError, Service};
+
+mod common;
+
+fn connect(url: &url::Url, authentication: Authentication) -> Service {
+    Service::builder(url.join("SceneServer/").unwrap())
+        .authentication(authentication)
+        .max_retries(0)
+        .build()
+}
+
+fn ok(_: &common::Request) -> common::Response {
+    common::Response::ok(b"node".to_vec())
+}
+
+#[tokio::test]
+async fn tokens_are_sent_as_a_header() {
+    let server = common::Server::start(ok);
+    let authentication = Authentication::token("secret").header("Referer", "https://app");
+    connect(&server.url, authentication)
+        .get("nodes/0")
+        .await
+        .unwrap();
+    let request = server.requests().pop().unwrap();
+    assert_eq!(
+        request.header("X-Esri-Authorization"),
+        Some("Bearer secret")
+    );
+    assert_eq!(request.header("Referer"), Some("https://app"));
+    assert_eq!(request.query, "");
+}
+
+#[tokio::test]
+async fn tokens_in_the_query() {
+    let server = common::Server::start(ok);
+    let authentication = Authentication::api_key("secret").token_in_query();
+    connect(&server.url, authentication)
+        .get("nodes/0")
+        .await
+        .unwrap();
+    let request = server.requests().pop().unwrap();
+    assert_eq!(request.path, "/SceneServer/layers/0/nodes/0");
+    assert_eq!(request.query, "token=secret");
+    assert_eq!(request.header("X-Esri-Authorization"), None);
+}
+
+#[tokio::test]
+async fn tokens_stay_out_of_errors() {
+    // a port nothing listens on
+    let url = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        url::Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap()
+    };
+    for authentication in [
+        Authentication::token("secret"),
+        Authentication::token("secret").token_in_query(),
+    ] {
+        assert!(!format!("{:?}", authentication).contains("secret"));
+        let err = connect(&url, authentication)
+            .get("nodes/0")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, I3sError::Request(_)), "{:?}", err);
+        assert!(!err.to_string().contains("secret"), "{}", err);
+        assert!(!format!("{:?}", err).contains("secret"), "{:?}", err);
+    }
+}
+
+// the server only accepts the latest token, and the provider hands out new ones
+#[tokio::test]
+async fn expired_tokens_are_refreshed() {
+    let server = common::Server::start(|request: &common::Request| match request.query.as_str() {
+        "token=token-2" => ok(request),
+        _ => common::Response::status(498),
+    });
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let authentication = Authentication::token_provider(move || {
+        let call = counter.fetch_add(1, Ordering::SeqCst) + 1;
+        async move { Ok(format!("token-{}", call)) }
+    })
+    .token_in_query();
+    let service = connect(&server.url, authentication);
+    assert_eq!(service.get("nodes/0").await.unwrap(), b"node");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert_eq!(server.requests().len(), 2);
+    assert_eq!(service.get("nodes/1").await.unwrap(), b"node");
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    let rejected = common::Server::start(|_| common::Response::status(498));
+    let err = connect(&rejected.url, Authentication::token("expired"))
+        .get("nodes/0")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, I3sError::HttpStatus { status: 498, .. }));
+}
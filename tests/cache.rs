@@ -0,0 +1,153 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use i3s::cache::{CachedResponse, DiskCache};
+use i3s::{I3SFormat, Service};
+
+mod common;
+
+const LAYER: &str = "https://host/SceneServer/layers/0";
+const NODE: &str = "https://host/SceneServer/layers/0/nodes/0";
+
+fn response(data: &[u8], etag: &str) -> CachedResponse {
+    CachedResponse {
+        data: data.to_vec(),
+        etag: Some(etag.to_string()),
+        last_modified: None,
+    }
+}
+
+#[test]
+fn responses_are_stored_per_url() {
+    let cache = DiskCache::new(common::temp_dir("cache-responses"));
+    assert!(cache.read(LAYER, NODE).is_none());
+    cache
+        .write(LAYER, NODE, &response(b"first", "\"1\""))
+        .unwrap();
+    cache
+        .write(LAYER, NODE, &response(b"second", "\"2\""))
+        .unwrap();
+    let cached = cache.read(LAYER, NODE).unwrap();
+    assert_eq!(
+        (cached.data, cached.etag.unwrap()),
+        (b"second".to_vec(), "\"2\"".to_string())
+    );
+    assert!(cache.read(LAYER, &format!("{}/features", NODE)).is_none());
+
+    // nothing but the entries and no temporary files are left behind
+    let mut files: Vec<_> = walk(cache.root());
+    files.sort();
+    assert_eq!(files.len(), 2, "{:?}", files);
+    assert!(files[0].ends_with(".bin") && files[1].ends_with(".json"));
+}
+
+#[test]
+fn layers_are_invalidated() {
+    let cache = DiskCache::new(common::temp_dir("cache-invalidated"));
+    cache
+        .write(LAYER, NODE, &response(b"node", "\"1\""))
+        .unwrap();
+    cache.set_last_update(LAYER, 1700).unwrap();
+    assert_eq!(cache.last_update(LAYER), Some(1700));
+    cache.invalidate(LAYER).unwrap();
+    assert!(cache.read(LAYER, NODE).is_none());
+    assert_eq!(cache.last_update(LAYER), None);
+    cache.invalidate(LAYER).unwrap();
+}
+
+fn walk(directory: &std::path::Path) -> Vec<String> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(walk(&path));
+        } else if !path.ends_with("last_update") {
+            files.push(path.to_string_lossy().into_owned());
+        }
+    }
+    files
+}
+
+// a scene server that revalidates by etag and reports the current last_update
+fn server(last_update: Arc<AtomicU64>) -> common::Server {
+    let scene_server = common::scene_server(common::mesh_entries(3));
+    common::Server::start(move |request| {
+        let last_update = last_update.load(Ordering::SeqCst);
+        let etag = format!("\"{}\"", last_update);
+        if request.header("If-None-Match") == Some(etag.as_str()) {
+            return common::Response::status(304);
+        }
+        let response = match request.path.as_str() {
+            "/SceneServer/layers/0" => {
+                let mut layer = common::mesh_layer();
+                layer["serviceUpdateTimeStamp"] = serde_json::json!({ "lastUpdate": last_update });
+                common::Response::ok(layer.to_string().into_bytes())
+            }
+            _ => scene_server(request),
+        };
+        response.header("ETag", &etag)
+    })
+}
+
+fn service(server: &common::Server, cache: &std::path::Path) -> Service {
+    Service::builder(server.url.join("SceneServer/").unwrap())
+        .cache(cache)
+        .build()
+}
+
+const PAGE: &str = "/SceneServer/layers/0/nodepages/0";
+
+#[tokio::test]
+async fn unchanged_layers_are_read_from_disk() {
+    let cache = common::temp_dir("cache-service");
+    let server = server(Arc::new(AtomicU64::new(1700)));
+    let first = service(&server, &cache);
+    first.scene_layer_information().await.unwrap();
+    assert_eq!(first.root().await.unwrap().index, 0);
+    assert_eq!(server.count(PAGE), 1);
+
+    // a new service only asks for the layer document
+    let second = service(&server, &cache);
+    second.scene_layer_information().await.unwrap();
+    assert_eq!(second.root().await.unwrap().index, 0);
+    assert_eq!(server.count(PAGE), 1);
+    assert_eq!(
+        server.requests().pop().unwrap().path,
+        "/SceneServer/layers/0"
+    );
+
+    // before the layer document is read, cached resources are revalidated
+    let third = service(&server, &cache);
+    assert!(third.get("nodepages/0").await.is_ok());
+    assert_eq!(server.count(PAGE), 2);
+    let last = server.requests().pop().unwrap();
+    assert_eq!(last.header("If-None-Match"), Some("\"1700\""));
+    third.scene_layer_information().await.unwrap();
+    let last = server.requests().pop().unwrap();
+    assert_eq!(
+        (last.path.as_str(), last.header("If-None-Match")),
+        ("/SceneServer/layers/0", Some("\"1700\""))
+    );
+}
+
+#[tokio::test]
+async fn updated_layers_are_fetched_again() {
+    let cache = common::temp_dir("cache-updated");
+    let last_update = Arc::new(AtomicU64::new(1700));
+    let server = server(last_update.clone());
+    let old = service(&server, &cache);
+    old.scene_layer_information().await.unwrap();
+    old.root().await.unwrap();
+
+    last_update.store(1800, Ordering::SeqCst);
+    let new = service(&server, &cache);
+    new.scene_layer_information().await.unwrap();
+    new.root().await.unwrap();
+    assert_eq!(server.count(PAGE), 2);
+    // the page was dropped with the layer, so it is fetched without an etag
+    let last = server.requests().pop().unwrap();
+    assert_eq!(
+        (last.path.as_str(), last.header("If-None-Match")),
+        (PAGE, None)
+    );
+}
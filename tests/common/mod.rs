@@ -0,0 +1,374 @@
+#![allow(dead_code)]
+
+use std::io::{Cursor, Read, Write};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use zip::write::SimpleFileOptions;
+
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+pub fn gzip_json(value: &serde_json::Value) -> Vec<u8> {
+    gzip(value.to_string().as_bytes())
+}
+
+// a .slpk with stored entries in the given order
+pub fn package(entries: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    for (name, data) in entries {
+        writer.start_file(name.as_str(), options).unwrap();
+        writer.write_all(data).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+// a directory of its own for every test, emptied first
+pub fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("i3s-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub fn write_folder(root: &std::path::Path, entries: &[(String, Vec<u8>)]) {
+    for (name, data) in entries {
+        let path = root.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    }
+}
+
+pub const NODES_PER_PAGE: usize = 2;
+
+pub fn mesh_layer() -> serde_json::Value {
+    serde_json::json!({
+        "id": 0,
+        "layerType": "IntegratedMesh",
+        "name": "mesh",
+        "capabilities": ["View"],
+        "store": {"profile": "meshes", "version": "1.7"},
+        "nodePages": {
+            "nodesPerPage": NODES_PER_PAGE,
+            "lodSelectionMetricType": "maxScreenThresholdSQ",
+            "rootIndex": 0
+        }
+    })
+}
+
+// node i of a binary tree of count nodes, stacked along z
+pub fn mesh_node(index: usize, count: usize) -> serde_json::Value {
+    let children: Vec<usize> = [2 * index + 1, 2 * index + 2]
+        .into_iter()
+        .filter(|child| *child < count)
+        .collect();
+    let mut node = serde_json::json!({
+        "index": index,
+        "obb": {"center": [10.0, 20.0, index as f64], "halfSize": [1.0, 1.0, 1.0]},
+        "children": children,
+        "lodThreshold": 100.0 * (index + 1) as f64,
+        "mesh": {"geometry": {
+            "definition": 0,
+            "resource": index,
+            "vertexCount": 3,
+            "featureCount": 1
+        }}
+    });
+    if index > 0 {
+        node["parentIndex"] = serde_json::json!((index - 1) / 2);
+    }
+    node
+}
+
+// the layer document, metadata and node pages of a mesh layer
+pub fn mesh_entries(count: usize) -> Vec<(String, Vec<u8>)> {
+    let mut entries = vec![
+        ("3dSceneLayer.json.gz".to_string(), gzip_json(&mesh_layer())),
+        (
+            "metadata.json".to_string(),
+            serde_json::json!({"I3SVersion": "1.7", "nodeCount": count})
+                .to_string()
+                .into_bytes(),
+        ),
+    ];
+    for page in 0..count.div_ceil(NODES_PER_PAGE) {
+        let nodes: Vec<_> = (page * NODES_PER_PAGE..count.min((page + 1) * NODES_PER_PAGE))
+            .map(|index| mesh_node(index, count))
+            .collect();
+        entries.push((
+            format!("nodepages/{}.json.gz", page),
+            gzip_json(&serde_json::json!({ "nodes": nodes })),
+        ));
+    }
+    entries
+}
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub path: String,
+    pub query: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn ok(body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            headers: Vec::new(),
+            body,
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/*
+A plain HTTP/1.1 server on a free local port that answers every request with
+the handler and closes the connection. Requests are recorded in order.
+*/
+pub struct Server {
+    pub url: url::Url,
+    requests: std::sync::Arc<std::sync::Mutex<Vec<Request>>>,
+}
+
+impl Server {
+    pub fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        use std::io::{BufRead, BufReader};
+        use std::sync::{Arc, Mutex};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = url::Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let handler = Arc::new(handler);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let (handler, recorded) = (handler.clone(), recorded.clone());
+                std::thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let target = line.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+                    let mut headers = Vec::new();
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        match line.trim_end().split_once(':') {
+                            Some((name, value)) => {
+                                headers.push((name.to_string(), value.trim().to_string()))
+                            }
+                            None => break,
+                        }
+                    }
+                    let request = Request {
+                        path: path.to_string(),
+                        query: query.to_string(),
+                        headers,
+                    };
+                    recorded.lock().unwrap().push(request.clone());
+                    let response = handler(&request);
+                    let mut head = format!(
+                        "HTTP/1.1 {} Status\r\nContent-Length: {}\r\nConnection: close\r\n",
+                        response.status,
+                        response.body.len()
+                    );
+                    for (name, value) in &response.headers {
+                        head.push_str(&format!("{}: {}\r\n", name, value));
+                    }
+                    head.push_str("\r\n");
+                    let _ = stream.write_all(head.as_bytes());
+                    let _ = stream.write_all(&response.body);
+                });
+            }
+        });
+        Server { url, requests }
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn count(&self, prefix: &str) -> usize {
+        self.requests()
+            .iter()
+            .filter(|request| request.path.starts_with(prefix))
+            .count()
+    }
+}
+
+/*
+Serves package entries the way a SceneServer does, e.g. nodepages/0.json.gz
+as /SceneServer/layers/0/nodepages/0 and 3dSceneLayer.json.gz as
+/SceneServer/layers/0, with the layer listed at /SceneServer.
+*/
+pub fn scene_server(entries: Vec<(String, Vec<u8>)>) -> impl Fn(&Request) -> Response {
+    let entries: std::collections::HashMap<String, Vec<u8>> = entries.into_iter().collect();
+    move |request| {
+        let decode = |data: &Vec<u8>| {
+            let mut decoded = Vec::new();
+            match flate2::read::GzDecoder::new(data.as_slice()).read_to_end(&mut decoded) {
+                Ok(_) => decoded,
+                Err(_) => data.clone(),
+            }
+        };
+        let path = request.path.trim_end_matches('/');
+        if path == "/SceneServer" {
+            let layer: serde_json::Value =
+                serde_json::from_slice(&decode(&entries["3dSceneLayer.json.gz"])).unwrap();
+            let root = serde_json::json!({"serviceName": "test", "layers": [layer]});
+            return Response::ok(root.to_string().into_bytes());
+        }
+        let Some(rest) = path.strip_prefix("/SceneServer/layers/0") else {
+            return Response::status(404);
+        };
+        let rest = rest.trim_start_matches('/');
+        let candidates = match rest {
+            "" => vec!["3dSceneLayer.json.gz".to_string()],
+            _ => vec![
+                rest.to_string(),
+                format!("{}.json.gz", rest),
+                format!("{}.bin.gz", rest),
+                format!("{}/3dNodeIndexDocument.json.gz", rest),
+                format!("{}/sharedResource.json.gz", rest),
+            ],
+        };
+        candidates
+            .iter()
+            .find_map(|candidate| entries.get(candidate))
+            .map_or_else(|| Response::status(404), |data| Response::ok(decode(data)))
+    }
+}
+
+// a 1.6 3DObject layer without node pages
+pub fn legacy_layer() -> serde_json::Value {
+    serde_json::json!({
+        "id": 0,
+        "layerType": "3DObject",
+        "name": "legacy",
+        "capabilities": ["View"],
+        "store": {
+            "profile": "meshes",
+            "version": "1.6",
+            "rootNode": "./nodes/root",
+            "defaultGeometrySchema": {
+                "geometryType": "triangles",
+                "topology": "PerAttributeArray",
+                "header": [
+                    {"property": "vertexCount", "type": "UInt32"},
+                    {"property": "featureCount", "type": "UInt32"}
+                ],
+                "ordering": ["position", "normal", "uv0", "color"],
+                "vertexAttributes": {
+                    "position": {"valueType": "Float32", "valuesPerElement": 3},
+                    "normal": {"valueType": "Float32", "valuesPerElement": 3},
+                    "uv0": {"valueType": "Float32", "valuesPerElement": 2},
+                    "color": {"valueType": "UInt8", "valuesPerElement": 4}
+                },
+                "featureAttributeOrder": ["id", "faceRange"],
+                "featureAttributes": {
+                    "id": {"valueType": "UInt64", "valuesPerElement": 1},
+                    "faceRange": {"valueType": "UInt32", "valuesPerElement": 2}
+                }
+            }
+        }
+    })
+}
+
+// the node tree root -> 0 -> (1, 2) of the legacy layer
+pub const LEGACY_NODES: [(&str, Option<&str>, &[&str]); 4] = [
+    ("root", None, &["0"]),
+    ("0", Some("root"), &["1", "2"]),
+    ("1", Some("0"), &[]),
+    ("2", Some("0"), &[]),
+];
+
+fn legacy_reference(id: &str) -> serde_json::Value {
+    serde_json::json!({"id": id, "href": format!("../{}", id), "mbs": [10.0, 20.0, 0.0, 5.0]})
+}
+
+pub fn legacy_node(id: &str) -> serde_json::Value {
+    let (level, (_, parent, children)) = LEGACY_NODES
+        .iter()
+        .map(|node| {
+            let mut level = 0;
+            let mut parent = node.1;
+            while let Some(id) = parent {
+                level += 1;
+                parent = LEGACY_NODES.iter().find(|node| node.0 == id).unwrap().1;
+            }
+            (level, node)
+        })
+        .find(|(_, node)| node.0 == id)
+        .unwrap();
+    let children: Vec<_> = children
+        .iter()
+        .map(|child| legacy_reference(child))
+        .collect();
+    let mut node = serde_json::json!({
+        "id": id,
+        "level": level,
+        "mbs": [10.0, 20.0, 0.0, 10.0 / (level + 1) as f64],
+        "children": children,
+        "lodSelection": [{"metricType": "maxScreenThreshold", "maxError": 100.0 * (level + 1) as f64}]
+    });
+    if let Some(parent) = parent {
+        node["parentNode"] = legacy_reference(parent);
+        node["sharedResource"] = serde_json::json!({"href": "./shared"});
+        node["featureData"] = serde_json::json!([{"href": "./features/0"}]);
+        node["geometryData"] = serde_json::json!([{"href": "./geometries/0"}]);
+    }
+    node
+}
+
+// the layer document and node index documents of the legacy layer
+pub fn legacy_entries() -> Vec<(String, Vec<u8>)> {
+    let mut entries = vec![(
+        "3dSceneLayer.json.gz".to_string(),
+        gzip_json(&legacy_layer()),
+    )];
+    for (id, _, _) in LEGACY_NODES {
+        entries.push((
+            format!("nodes/{}/3dNodeIndexDocument.json.gz", id),
+            gzip_json(&legacy_node(id)),
+        ));
+    }
+    entries
+}
@@ -0,0 +1,151 @@
+#![cfg(feature = "draco")]
+
+use i3s::draco::decode_mesh;
+use i3s::I3sError;
+
+fn fixture(name: &str) -> Vec<u8> {
+    std::fs::read(format!(
+        "{}/tests/fixtures/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+}
+
+// faces as position triangles, each rotated to start at its smallest vertex
+fn triangles<F: IntoIterator<Item = [[i32; 3]; 3]>>(faces: F) -> Vec<[[i32; 3]; 3]> {
+    let mut triangles: Vec<_> = faces
+        .into_iter()
+        .map(|face| {
+            let first = (0..3).min_by_key(|i| face[*i]).unwrap();
+            [face[first], face[(first + 1) % 3], face[(first + 2) % 3]]
+        })
+        .collect();
+    triangles.sort();
+    triangles
+}
+
+fn decoded_triangles(positions: &[[f32; 3]]) -> Vec<[[i32; 3]; 3]> {
+    triangles(
+        positions
+            .chunks_exact(3)
+            .map(|face| [0, 1, 2].map(|i| face[i].map(|value| value.round() as i32))),
+    )
+}
+
+#[test]
+fn sequential_mesh() {
+    let mesh = decode_mesh(&fixture("tri.drc")).unwrap();
+    assert_eq!(mesh.vertex_count(), 6);
+    assert_eq!(mesh.positions[1], [1.0, 0.0, 0.0]);
+    assert_eq!(mesh.positions[5], [1.0, 1.0, 1.0]);
+    assert!((mesh.normals[0][0] - 1.0).abs() < 1e-3);
+    assert_eq!(mesh.colors[0], [10, 20, 30, 255]);
+    assert_eq!(mesh.uv_regions[3], [1, 2, 3, 4]);
+    assert_eq!(mesh.feature_ids, vec![42, 7]);
+    assert_eq!(mesh.face_ranges, vec![[0, 0], [1, 1]]);
+}
+
+// a 3 by 3 vertex grid with parallelogram predicted positions and texture
+// coordinates that have a seam along x = 100
+#[test]
+fn edgebreaker_grid() {
+    let mesh = decode_mesh(&fixture("grid.drc")).unwrap();
+    let p = |x: i32, y: i32| [x * 100, y * 100, x * y * 10];
+    let mut faces = vec![];
+    for y in 0..2 {
+        for x in 0..2 {
+            faces.push([p(x, y), p(x + 1, y), p(x + 1, y + 1)]);
+            faces.push([p(x, y), p(x + 1, y + 1), p(x, y + 1)]);
+        }
+    }
+    assert_eq!(decoded_triangles(&mesh.positions), triangles(faces));
+    for (face, uvs) in mesh.positions.chunks_exact(3).zip(mesh.uv0.chunks_exact(3)) {
+        let column = face.iter().map(|p| p[0]).fold(f32::MAX, f32::min) / 100.0;
+        for (p, uv) in face.iter().zip(uvs) {
+            assert_eq!(*uv, [p[0] / 10.0 + 100.0 * column, p[1] / 10.0]);
+        }
+    }
+}
+
+// a closed mesh with valence coded symbols, an interior start face and
+// constrained multi parallelogram predicted positions
+#[test]
+fn edgebreaker_octahedron() {
+    let mesh = decode_mesh(&fixture("octahedron.drc")).unwrap();
+    let v = [
+        [500, 500, 900],
+        [900, 500, 500],
+        [500, 900, 500],
+        [100, 500, 500],
+        [500, 100, 500],
+        [500, 500, 100],
+    ];
+    let faces = [
+        [0, 1, 2],
+        [0, 2, 3],
+        [0, 3, 4],
+        [0, 4, 1],
+        [5, 2, 1],
+        [5, 3, 2],
+        [5, 4, 3],
+        [5, 1, 4],
+    ];
+    assert_eq!(mesh.vertex_count(), 24);
+    assert_eq!(
+        decoded_triangles(&mesh.positions),
+        triangles(faces.map(|face| face.map(|i| v[i])))
+    );
+}
+
+// the handles of a torus need topology splits
+#[test]
+fn edgebreaker_torus() {
+    let mesh = decode_mesh(&fixture("torus.drc")).unwrap();
+    let v = |i: i32, j: i32| {
+        let (i, j) = (i % 3, j % 3);
+        [i * 100 + 50, j * 100 + 50, (i + 2 * j) % 3 * 100]
+    };
+    let mut faces = vec![];
+    for j in 0..3 {
+        for i in 0..3 {
+            faces.push([v(i, j), v(i + 1, j), v(i + 1, j + 1)]);
+            faces.push([v(i, j), v(i + 1, j + 1), v(i, j + 1)]);
+        }
+    }
+    assert_eq!(decoded_triangles(&mesh.positions), triangles(faces));
+}
+
+#[test]
+fn unsupported_version() {
+    let mut buffer = fixture("tri.drc");
+    buffer[6] = 1;
+    assert!(matches!(
+        decode_mesh(&buffer),
+        Err(I3sError::UnsupportedEncoding(_))
+    ));
+}
+
+#[test]
+fn truncated_edgebreaker_mesh() {
+    let buffer = fixture("torus.drc");
+    for length in 0..buffer.len() {
+        assert!(decode_mesh(&buffer[..length]).is_err());
+    }
+}
+
+// truncated or corrupt attribute data must be rejected without a panic
+#[test]
+fn corrupt_sequential_mesh() {
+    let buffer = fixture("tri.drc");
+    for length in 0..buffer.len() {
+        assert!(decode_mesh(&buffer[..length]).is_err());
+    }
+    for position in 0..buffer.len() {
+        for value in [0, 1, 2, 3, 0x7f, 0x80, 0xfe, 0xff] {
+            let mut corrupt = buffer.clone();
+            corrupt[position] = value;
+            let _ = decode_mesh(&corrupt);
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::error::Error;
+
+use i3s::{I3SFormat, I3SFormatExt, I3sError, MemoryStore, SceneLayerPackage};
+
+mod common;
+
+#[tokio::test]
+async fn missing_resources() {
+    let store = MemoryStore::new();
+    let err = store.get("nodepages/0.json.gz").await.unwrap_err();
+    assert!(err.is_not_found());
+    assert_eq!(err.to_string(), "resource not found: nodepages/0.json.gz");
+
+    let package = common::package(&[("metadata.json".to_string(), b"{}".to_vec())]);
+    let package = SceneLayerPackage::from_bytes(package).unwrap();
+    assert!(package
+        .get("nodepages/0.json.gz")
+        .unwrap_err()
+        .is_not_found());
+}
+
+#[tokio::test]
+async fn malformed_resources_name_their_path() {
+    let mut store = MemoryStore::new();
+    store.insert("broken.json.gz", vec![0x1f, 0x8b, 0, 0]);
+    store.insert("invalid.json", b"{\"nodes\":".to_vec());
+
+    let err = store.get_resource("broken.json.gz").await.unwrap_err();
+    assert!(matches!(&err, I3sError::Gzip { path, .. } if path == "broken.json.gz"));
+    assert!(err.source().is_some());
+
+    let err = store
+        .get_json::<serde_json::Value>("invalid.json")
+        .await
+        .unwrap_err();
+    assert!(matches!(&err, I3sError::Json { path, .. } if path == "invalid.json"));
+    assert!(err
+        .to_string()
+        .starts_with("cannot parse invalid.json at line 1"));
+}
+
+#[tokio::test]
+async fn layer_types() {
+    let mut store = MemoryStore::new();
+    store.insert(
+        "3dSceneLayer.json.gz",
+        common::gzip_json(&serde_json::json!({"id": 0, "layerType": "Voxel"})),
+    );
+    let err = store.scene_layer_information().await.unwrap_err();
+    assert!(matches!(err, I3sError::UnsupportedLayerType(layer_type) if layer_type == "Voxel"));
+
+    store.insert(
+        "3dSceneLayer.json.gz",
+        common::gzip_json(&serde_json::json!({"id": 0})),
+    );
+    let err = store.scene_layer_information().await.unwrap_err();
+    assert!(matches!(err, I3sError::MissingLayerType));
+}
+
+#[test]
+fn packages_that_are_not_zip_files() {
+    let err = SceneLayerPackage::from_bytes(b"not a package".to_vec()).unwrap_err();
+    assert!(matches!(err, I3sError::Zip(_)));
+    assert!(!err.is_not_found());
+}
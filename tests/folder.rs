@@ -0,0 +1,74 @@
+use i3s::{I3SFormat, I3SFormatExt, I3SInfo, Profile, SceneLayerFolder};
+
+mod common;
+
+#[tokio::test]
+async fn extracted_packages() {
+    let dir = common::temp_dir("folder-extracted");
+    let mut entries = common::mesh_entries(5);
+    entries.push(("nodes/0/geometries/0.bin".to_string(), b"plain".to_vec()));
+    entries.push((
+        "nodes/1/geometries/0.bin.gz".to_string(),
+        common::gzip(b"gzipped"),
+    ));
+    common::write_folder(&dir, &entries);
+    let folder = SceneLayerFolder::open(&dir).unwrap();
+
+    let I3SInfo::IntegratedMesh(information) = folder.scene_layer_information().await.unwrap()
+    else {
+        panic!();
+    };
+    assert_eq!(information.name, "mesh");
+    assert_eq!(folder.metadata().unwrap().node_count, Some(5));
+    assert_eq!(folder.node_page_count().await.unwrap(), Some(3));
+
+    // a path is tried with and without its .gz suffix
+    let plain = "nodes/0/geometries/0.bin.gz";
+    assert_eq!(folder.get(plain).unwrap(), b"plain");
+    assert_eq!(folder.get_resource(plain).await.unwrap(), b"plain");
+    let gzipped = "nodes/1/geometries/0.bin";
+    assert_eq!(folder.get_resource(gzipped).await.unwrap(), b"gzipped");
+    assert!(folder.exists(gzipped).await.unwrap());
+    assert!(folder
+        .get("nodes/2/geometries/0.bin.gz")
+        .unwrap_err()
+        .is_not_found());
+}
+
+#[tokio::test]
+async fn decompressed_documents() {
+    let dir = common::temp_dir("folder-decompressed");
+    let layer = common::mesh_layer();
+    let nodes: Vec<_> = (0..2).map(|index| common::mesh_node(index, 2)).collect();
+    let page = serde_json::json!({ "nodes": nodes });
+    common::write_folder(
+        &dir,
+        &[
+            (
+                "3dSceneLayer.json".to_string(),
+                layer.to_string().into_bytes(),
+            ),
+            (
+                "nodepages/0.json".to_string(),
+                page.to_string().into_bytes(),
+            ),
+        ],
+    );
+    let folder = SceneLayerFolder::open(&dir).unwrap();
+    let information = folder.scene_layer_information().await.unwrap();
+    let Profile::IntegratedMesh(mesh) = folder.profile(&information).await.unwrap() else {
+        panic!();
+    };
+    assert_eq!(mesh.root().unwrap().children, vec![1]);
+    assert!(folder.metadata().is_none());
+}
+
+#[test]
+fn missing_folders() {
+    let dir = common::temp_dir("folder-missing");
+    assert!(SceneLayerFolder::open(dir.join("layer"))
+        .unwrap_err()
+        .is_not_found());
+    std::fs::write(dir.join("layer.slpk"), b"").unwrap();
+    assert!(SceneLayerFolder::open(dir.join("layer.slpk")).is_err());
+}
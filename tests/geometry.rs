@@ -0,0 +1,87 @@
+use i3s::geometry::decode_geometry_buffer;
+use i3s::{cmn, I3SFormatExt, MemoryStore};
+
+mod common;
+
+fn definition() -> serde_json::Value {
+    serde_json::json!({
+        "offset": 8,
+        "position": {"type": "Float32", "component": 3},
+        "normal": {"type": "Float32", "component": 3},
+        "uv0": {"type": "Float32", "component": 2},
+        "color": {"type": "UInt8", "component": 4},
+        "featureId": {"type": "UInt64", "component": 1, "binding": "per-feature"},
+        "faceRange": {"type": "UInt32", "component": 2, "binding": "per-feature"}
+    })
+}
+
+fn f32s(values: &[f32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+// one triangle of one feature, after an 8 byte header
+fn buffer() -> Vec<u8> {
+    let mut buffer = [3u32.to_le_bytes(), 1u32.to_le_bytes()].concat();
+    buffer.extend(f32s(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]));
+    buffer.extend(f32s(&[0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0]));
+    buffer.extend(f32s(&[0.0, 0.0, 1.0, 0.0, 0.0, 1.0]));
+    buffer.extend([255, 0, 0, 255].repeat(3));
+    buffer.extend(42u64.to_le_bytes());
+    buffer.extend([0u32.to_le_bytes(), 0u32.to_le_bytes()].concat());
+    buffer
+}
+
+#[test]
+fn attributes_in_buffer_order() {
+    let definition: cmn::GeometryBuffer = serde_json::from_value(definition()).unwrap();
+    let mesh = decode_geometry_buffer(&buffer(), &definition, 3, 1).unwrap();
+    assert_eq!(
+        mesh.positions,
+        vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+    );
+    assert_eq!(mesh.normals[2], [0.0, 0.0, 1.0]);
+    assert_eq!(mesh.uv0[1], [1.0, 0.0]);
+    assert_eq!(mesh.colors, vec![[255, 0, 0, 255]; 3]);
+    assert_eq!(mesh.feature_ids, vec![42]);
+    assert_eq!(mesh.face_ranges, vec![[0, 0]]);
+}
+
+#[test]
+fn counts_larger_than_the_buffer() {
+    let definition: cmn::GeometryBuffer = serde_json::from_value(definition()).unwrap();
+    assert!(decode_geometry_buffer(&buffer(), &definition, 4, 1).is_err());
+    assert!(decode_geometry_buffer(&buffer(), &definition, usize::MAX, 1).is_err());
+    assert!(decode_geometry_buffer(&buffer()[..40], &definition, 3, 1).is_err());
+}
+
+#[test]
+fn unsupported_types() {
+    let mut definition = definition();
+    definition["color"]["type"] = "Float32".into();
+    let definition: cmn::GeometryBuffer = serde_json::from_value(definition).unwrap();
+    assert!(decode_geometry_buffer(&buffer(), &definition, 3, 1).is_err());
+}
+
+#[tokio::test]
+async fn node_geometry() {
+    let mut layer = common::mesh_layer();
+    layer["geometryDefinitions"] = serde_json::json!([{ "geometryBuffers": [definition()] }]);
+    let mut store = MemoryStore::new();
+    store.insert("3dSceneLayer.json.gz", common::gzip_json(&layer));
+    store.insert("nodes/0/geometries/0.bin.gz", common::gzip(&buffer()));
+    let i3s::I3SInfo::IntegratedMesh(information) = store.scene_layer_information().await.unwrap()
+    else {
+        panic!();
+    };
+    let node: cmn::Node = serde_json::from_value(common::mesh_node(0, 1)).unwrap();
+    let mesh = store.geometry(&information, &node).await.unwrap().unwrap();
+    assert_eq!(mesh.vertex_count(), 3);
+    assert_eq!(mesh.feature_count(), 1);
+
+    let mut node = node;
+    node.mesh = None;
+    assert!(store.geometry(&information, &node).await.unwrap().is_none());
+}
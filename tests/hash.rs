@@ -0,0 +1,106 @@
+use i3s::hash::{HashTable, HashTableStatus, HASH_TABLE_PATH};
+use i3s::SceneLayerPackage;
+
+mod common;
+
+fn entries() -> Vec<(String, Vec<u8>)> {
+    vec![
+        ("3dSceneLayer.json.gz".to_string(), b"layer".to_vec()),
+        ("nodes/0/geometries/0.bin.gz".to_string(), b"zero".to_vec()),
+        ("nodes/1/Textures/0.jpg".to_string(), b"one".to_vec()),
+    ]
+}
+
+fn table(hashes: &[(&str, u64)]) -> Vec<u8> {
+    hashes
+        .iter()
+        .flat_map(|(path, offset)| {
+            HashTable::hash(path)
+                .into_iter()
+                .chain(offset.to_le_bytes())
+        })
+        .collect()
+}
+
+// the entries with a hash table of their local header offsets as the last entry
+fn hashed_package(entries: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let package = common::package(entries);
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(package)).unwrap();
+    let hashes: Vec<(String, u64)> = (0..archive.len())
+        .map(|index| {
+            let file = archive.by_index_raw(index).unwrap();
+            (file.name().to_string(), file.header_start())
+        })
+        .collect();
+    let hashes: Vec<(&str, u64)> = hashes.iter().map(|(n, o)| (n.as_str(), *o)).collect();
+    let mut entries = entries.to_vec();
+    entries.push((HASH_TABLE_PATH.to_string(), table(&hashes)));
+    common::package(&entries)
+}
+
+#[test]
+fn hashes_of_normalized_paths() {
+    assert_eq!(
+        HashTable::hash("Nodes\\0\\3dNodeIndexDocument.json.gz"),
+        HashTable::hash("nodes/0/3dnodeindexdocument.json.gz")
+    );
+    assert_ne!(HashTable::hash("nodes/0"), HashTable::hash("nodes/1"));
+}
+
+#[test]
+fn offsets() {
+    let hash_table = HashTable::parse(&table(&[
+        ("nodepages/0.json.gz", 100),
+        ("nodepages/1.json.gz", 40),
+        ("nodepages/3.json.gz", 0),
+    ]))
+    .unwrap();
+    assert_eq!(hash_table.len(), 3);
+    assert_eq!(hash_table.offset("NodePages/1.json.gz"), Some(40));
+    assert_eq!(hash_table.offset("nodepages/2.json.gz"), None);
+    assert_eq!(hash_table.next_offset(40), Some(100));
+    assert_eq!(hash_table.next_offset(100), None);
+    // pages after a gap are not counted
+    assert_eq!(hash_table.node_page_paths().len(), 2);
+
+    assert!(HashTable::parse(&[0; 25]).is_err());
+}
+
+#[test]
+fn lookups_ignore_case_with_and_without_a_hash_table() {
+    for (bytes, status) in [
+        (
+            hashed_package(&entries()),
+            HashTableStatus::Valid { entries: 3 },
+        ),
+        (common::package(&entries()), HashTableStatus::Missing),
+    ] {
+        let package = SceneLayerPackage::from_bytes(bytes).unwrap();
+        assert_eq!(package.hash_table_status(), status);
+        assert_eq!(package.get("3dscenelayer.json.gz").unwrap(), b"layer");
+        assert_eq!(package.get("nodes/1/textures/0.jpg").unwrap(), b"one");
+        assert_eq!(
+            package.get("NODES\\0\\geometries\\0.bin.gz").unwrap(),
+            b"zero"
+        );
+        assert!(package
+            .get("nodes/2/textures/0.jpg")
+            .unwrap_err()
+            .is_not_found());
+    }
+}
+
+#[test]
+fn tables_that_point_at_other_entries() {
+    let mut entries = entries();
+    // every path points at the first local header
+    let hashes: Vec<(&str, u64)> = entries.iter().map(|(name, _)| (name.as_str(), 0)).collect();
+    let table = table(&hashes);
+    entries.push((HASH_TABLE_PATH.to_string(), table));
+    let package = SceneLayerPackage::from_bytes(common::package(&entries)).unwrap();
+    assert_eq!(package.get("nodes/1/Textures/0.jpg").unwrap(), b"one");
+    assert!(matches!(
+        package.hash_table_status(),
+        HashTableStatus::Inconsistent(_)
+    ));
+}
@@ -0,0 +1,82 @@
+use i3s::{Format, I3SInfo, Profile, Service};
+
+mod common;
+
+/*
+A SceneServer with the mesh layer of common::scene_server as layer 0 and a
+larger one named city as layer 3.
+*/
+fn server() -> common::Server {
+    let mesh = common::scene_server(common::mesh_entries(3));
+    let mut entries = common::mesh_entries(7);
+    let mut city = common::mesh_layer();
+    city["id"] = 3.into();
+    city["name"] = "city".into();
+    city["alias"] = "City Model".into();
+    entries[0].1 = common::gzip_json(&city);
+    let city_server = common::scene_server(entries);
+    common::Server::start(move |request| {
+        let path = request.path.trim_end_matches('/');
+        if path == "/SceneServer" {
+            let layers = [common::mesh_layer(), city.clone()];
+            let root = serde_json::json!({
+                "serviceName": "test",
+                "serviceVersion": "1.7",
+                "layers": layers
+            });
+            return common::Response::ok(root.to_string().into_bytes());
+        }
+        match path.strip_prefix("/SceneServer/layers/3") {
+            Some(rest) => city_server(&common::Request {
+                path: format!("/SceneServer/layers/0{}", rest),
+                ..request.clone()
+            }),
+            None => mesh(request),
+        }
+    })
+}
+
+async fn open_layer(service: &Service, layer: &str) -> Service {
+    let layer = service.open_layer(layer).await.unwrap();
+    assert!(matches!(layer.profile, Profile::IntegratedMesh(_)));
+    match layer.format {
+        Format::Service(service) => service,
+        format => panic!("{:?}", format),
+    }
+}
+
+#[tokio::test]
+async fn layers_by_id_name_or_alias() {
+    let server = server();
+    for base in ["SceneServer/", "SceneServer"] {
+        let service = Service::connect(server.url.join(base).unwrap());
+        let information = service.service_information().await.unwrap();
+        assert_eq!(information.service_version.as_deref(), Some("1.7"));
+        let ids: Vec<usize> = information.layers.iter().map(|layer| layer.id).collect();
+        assert_eq!(ids, vec![0, 3]);
+
+        assert_eq!(service.find_layer("city").await.unwrap().id, 3);
+        assert_eq!(service.find_layer("City Model").await.unwrap().id, 3);
+        assert_eq!(service.find_layer("0").await.unwrap().name, "mesh");
+        assert!(service.find_layer("7").await.unwrap_err().is_not_found());
+
+        let mesh = open_layer(&service, "mesh").await;
+        assert_eq!(mesh.layer, 0);
+        assert!(mesh.node(&6).await.is_err());
+        let city = open_layer(&service, "3").await;
+        assert_eq!(city.layer, 3);
+        assert_eq!(city.node(&6).await.unwrap().index, 6);
+    }
+    assert!(server.count("/SceneServer/layers/3/nodepages/3") > 0);
+}
+
+#[tokio::test]
+async fn layers_of_a_layer_url() {
+    let server = server();
+    let url = server.url.join("SceneServer/layers/3").unwrap();
+    let layer = i3s::open(url.as_str()).await.unwrap();
+    let I3SInfo::IntegratedMesh(information) = &layer.information else {
+        panic!("{:?}", layer.information);
+    };
+    assert_eq!(information.name, "city");
+}
@@ -0,0 +1,469 @@
+use i3s::texture::{TextureFormat, DEFAULT_PREFERENCES};
+use i3s::{cmn, Format, I3SFormatExt, I3SInfo, I3sError, MemoryStore, Profile};
+
+mod common;
+
+fn f32s(values: &[f32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+/*
+A buffer laid out by the defaultGeometrySchema of common::legacy_layer, with
+one feature per triangle. Triangle k of node i lies at height k, and its last
+vertex at depth i.
+*/
+fn geometry(node: usize, triangles: usize) -> Vec<u8> {
+    let mut buffer = [3 * triangles as u32, triangles as u32]
+        .map(u32::to_le_bytes)
+        .concat();
+    for k in 0..triangles {
+        let (k, i) = (k as f32, node as f32);
+        buffer.extend(f32s(&[0.0, 0.0, k, 1.0, 0.0, k, 0.0, 1.0, i]));
+    }
+    buffer.extend(f32s(&[0.0, 0.0, 1.0]).repeat(3 * triangles));
+    buffer.extend(f32s(&[0.0, 1.0]).repeat(3 * triangles));
+    buffer.extend([0, 255, 0, 255].repeat(3 * triangles));
+    for k in 0..triangles {
+        buffer.extend((100 + node as u64 + k as u64).to_le_bytes());
+    }
+    for k in 0..triangles as u32 {
+        buffer.extend([k.to_le_bytes(), k.to_le_bytes()].concat());
+    }
+    buffer
+}
+
+fn reference(face_range: Option<[u32; 2]>) -> serde_json::Value {
+    let mut params = serde_json::json!({"href": "/geometryData/1", "type": "triangles"});
+    if let Some(face_range) = face_range {
+        params["faceRange"] = serde_json::json!(face_range);
+    }
+    serde_json::json!({"id": 2, "type": "GeometryReference", "params": params})
+}
+
+/*
+Node 1 has a feature per triangle, the second moved by 10 along x. The other
+nodes have a single feature that covers the whole geometry.
+*/
+fn features(node: usize) -> serde_json::Value {
+    let feature_data = match node {
+        1 => {
+            let mut moved = reference(Some([1, 1]));
+            moved["transformation"] = serde_json::json!([
+                1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 10.0, 0.0, 0.0, 1.0
+            ]);
+            serde_json::json!([
+                {"id": 7, "geometries": [reference(Some([0, 0]))]},
+                {"id": 8, "geometries": [moved]}
+            ])
+        }
+        _ => serde_json::json!([{"id": 9, "geometries": [reference(None)]}]),
+    };
+    let components = serde_json::json!([
+        {"id": 1, "material": "/materialDefinitions/Mat0", "texture": "/textureDefinitions/0"},
+        {"id": 2, "materialID": 1}
+    ]);
+    let position =
+        serde_json::json!({"valueType": "Float32", "valuesPerElement": 3, "byteOffset": 8});
+    serde_json::json!({
+        "featureData": feature_data,
+        "geometryData": [{
+            "id": 1,
+            "type": "ArrayBufferView",
+            "params": {
+                "type": "triangles",
+                "topology": "PerAttributeArray",
+                "vertexAttributes": {"position": position},
+                "components": components
+            }
+        }]
+    })
+}
+
+// a standard material with a texture atlas in two encodings, and a plain one
+fn shared() -> serde_json::Value {
+    serde_json::json!({
+        "materialDefinitions": {
+            "Mat0": {
+                "type": "standard",
+                "name": "standard",
+                "params": {
+                    "renderMode": "textured",
+                    "transparency": 0.25,
+                    "diffuse": [0.5, 0.25, 1.0]
+                }
+            },
+            "1": {"type": "standard", "name": "plain", "params": {"renderMode": "solid", "vertexColors": true}}
+        },
+        "textureDefinitions": {
+            "0": {
+                "encoding": ["image/jpeg", "image/vnd-ms.dds"],
+                "atlas": true,
+                "images": [
+                    {"id": "1", "size": 1, "href": ["../textures/0_small", "../textures/0_small_1"]},
+                    {"id": "2", "size": 2, "href": ["../textures/0_0", "../textures/0_0_1"]}
+                ]
+            }
+        }
+    })
+}
+
+// an APP0 segment followed by a baseline start of frame
+fn jpg_header(width: u16, height: u16) -> Vec<u8> {
+    let mut data = vec![0xff, 0xd8, 0xff, 0xe0, 0, 4, 0, 0, 0xff, 0xc0, 0, 11, 8];
+    data.extend(height.to_be_bytes());
+    data.extend(width.to_be_bytes());
+    data
+}
+
+fn entries() -> Vec<(String, Vec<u8>)> {
+    let mut entries = common::legacy_entries();
+    for node in 0..3 {
+        entries.push((
+            format!("nodes/{}/geometries/0.bin.gz", node),
+            common::gzip(&geometry(node, if node == 1 { 2 } else { 1 })),
+        ));
+        entries.push((
+            format!("nodes/{}/features/0.json.gz", node),
+            common::gzip_json(&features(node)),
+        ));
+        entries.push((
+            format!("nodes/{}/shared/sharedResource.json.gz", node),
+            common::gzip_json(&shared()),
+        ));
+        entries.push((format!("nodes/{}/textures/0_0.jpg", node), jpg_header(2, 1)));
+    }
+    entries
+}
+
+fn store() -> MemoryStore {
+    entries().into_iter().collect()
+}
+
+fn service(server: &common::Server) -> i3s::Service {
+    i3s::Service::connect(server.url.join("SceneServer/").unwrap())
+}
+
+async fn walk<F: I3SFormatExt>(format: &F) -> Vec<(String, u32)> {
+    let I3SInfo::DDDObject(information) = format.scene_layer_information().await.unwrap() else {
+        panic!();
+    };
+    let mut nodes = Vec::new();
+    format
+        .walk_node_index(&information.store.root_node, |node| {
+            nodes.push((node.id.clone(), node.level));
+            true
+        })
+        .await
+        .unwrap();
+    nodes
+}
+
+#[tokio::test]
+async fn node_indexes_are_walked_depth_first() {
+    let expected = [("root", 0), ("0", 1), ("1", 2), ("2", 2)]
+        .map(|(id, level)| (id.to_string(), level))
+        .to_vec();
+    let store = store();
+    assert_eq!(walk(&store).await, expected);
+    let server = common::Server::start(common::scene_server(entries()));
+    assert_eq!(walk(&service(&server)).await, expected);
+
+    // children are only visited when visit returns true
+    let mut levels = Vec::new();
+    store
+        .walk_node_index("./nodes/root", |node| {
+            levels.push(node.level);
+            node.level < 1
+        })
+        .await
+        .unwrap();
+    assert_eq!(levels, vec![0, 1]);
+}
+
+#[tokio::test]
+async fn references_are_resolved_against_the_node() {
+    let store = store();
+    let node = store.node_index_document("nodes/0").await.unwrap();
+    assert_eq!(
+        node.resolve(&node.geometry_data[0].href),
+        "nodes/0/geometries/0"
+    );
+    assert_eq!(
+        node.reference_path(node.parent_node.as_ref().unwrap()),
+        "nodes/root"
+    );
+    assert_eq!(node.reference_path(&node.children[1]), "nodes/2");
+    assert!(!node.is_root() && !node.is_leaf());
+
+    let root = store.node_index_document("nodes/root").await.unwrap();
+    assert!(root.is_root() && !root.is_leaf());
+    assert!(store
+        .node_index_document("nodes/1")
+        .await
+        .unwrap()
+        .is_leaf());
+}
+
+#[tokio::test]
+async fn node_index_profile() {
+    let store = Format::Memory(store());
+    let information = store.scene_layer_information().await.unwrap();
+    assert_eq!(information.layer_type(), "3DObject");
+    let Profile::NodeIndex(index) = store.profile(&information).await.unwrap() else {
+        panic!();
+    };
+    assert_eq!(index.root_node, "./nodes/root");
+    let mut ids = Vec::new();
+    index
+        .walk(&store, |node| {
+            ids.push(node.id.clone());
+            true
+        })
+        .await
+        .unwrap();
+    assert_eq!(ids, vec!["root", "0", "1", "2"]);
+}
+
+#[tokio::test]
+async fn cycles_are_rejected() {
+    let mut store = store();
+    let mut node = common::legacy_node("2");
+    node["children"] = serde_json::json!([{"id": "root", "href": "../root"}]);
+    store.insert(
+        "nodes/2/3dNodeIndexDocument.json.gz",
+        common::gzip_json(&node),
+    );
+    let err = store
+        .walk_node_index("./nodes/root", |_| true)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, I3sError::MalformedNodePage { ref path, .. } if path == "nodes/root"),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn geometry_by_the_default_schema() {
+    let server = common::Server::start(common::scene_server(entries()));
+    for format in [Format::Memory(store()), Format::Service(service(&server))] {
+        let I3SInfo::DDDObject(information) = format.scene_layer_information().await.unwrap()
+        else {
+            panic!();
+        };
+        let node = format.node_index_document("nodes/2").await.unwrap();
+        let mesh = format
+            .legacy_geometry(&information, &node)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(mesh.vertex_count(), 3);
+        assert_eq!(mesh.positions[2], [0.0, 1.0, 2.0]);
+        assert_eq!(mesh.normals, vec![[0.0, 0.0, 1.0]; 3]);
+        assert_eq!(mesh.uv0[1], [0.0, 1.0]);
+        assert_eq!(mesh.colors[0], [0, 255, 0, 255]);
+        assert_eq!(mesh.feature_ids, vec![102]);
+        assert_eq!(mesh.face_ranges, vec![[0, 0]]);
+
+        let node = format.node_index_document("nodes/1").await.unwrap();
+        let mesh = format
+            .legacy_geometry(&information, &node)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(mesh.vertex_count(), 6);
+        assert_eq!(mesh.positions[3], [0.0, 0.0, 1.0]);
+        assert_eq!(mesh.feature_ids, vec![101, 102]);
+        assert_eq!(mesh.face_ranges, vec![[0, 0], [1, 1]]);
+
+        // the root node has no geometry
+        let root = format.node_index_document("nodes/root").await.unwrap();
+        let geometry = format.legacy_geometry(&information, &root).await.unwrap();
+        assert!(geometry.is_none());
+    }
+}
+
+#[tokio::test]
+async fn geometry_that_does_not_match_the_schema() {
+    let mut layer = common::legacy_layer();
+    let schema = &mut layer["store"]["defaultGeometrySchema"];
+    schema["ordering"] = serde_json::json!(["position", "tangent"]);
+    let mut unknown = store();
+    unknown.insert("3dSceneLayer.json.gz", common::gzip_json(&layer));
+    let I3SInfo::DDDObject(information) = unknown.scene_layer_information().await.unwrap() else {
+        panic!();
+    };
+    let node = unknown.node_index_document("nodes/2").await.unwrap();
+    let err = unknown
+        .legacy_geometry(&information, &node)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, I3sError::MalformedBuffer(_)), "{:?}", err);
+
+    // a vertex count larger than the buffer
+    let mut short = store();
+    let mut buffer = geometry(2, 1);
+    buffer[..4].copy_from_slice(&6u32.to_le_bytes());
+    short.insert("nodes/2/geometries/0.bin.gz", common::gzip(&buffer));
+    let I3SInfo::DDDObject(information) = short.scene_layer_information().await.unwrap() else {
+        panic!();
+    };
+    let err = short
+        .legacy_geometry(&information, &node)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, I3sError::MalformedBuffer(_)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn features_are_cut_out_of_the_geometry() {
+    let server = common::Server::start(common::scene_server(entries()));
+    for format in [Format::Memory(store()), Format::Service(service(&server))] {
+        let I3SInfo::DDDObject(information) = format.scene_layer_information().await.unwrap()
+        else {
+            panic!();
+        };
+        let node = format.node_index_document("nodes/1").await.unwrap();
+        let document = format.legacy_features(&node).await.unwrap().unwrap();
+        assert_eq!(document.feature_data.len(), 2);
+        let meshes = format
+            .legacy_feature_geometries(&information, &node)
+            .await
+            .unwrap();
+        assert_eq!(meshes.len(), 2);
+        assert_eq!(meshes[0].feature_ids, vec![7]);
+        assert_eq!(
+            meshes[0].positions,
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 1.0]]
+        );
+        assert_eq!(
+            meshes[1].positions,
+            vec![[10.0, 0.0, 1.0], [11.0, 0.0, 1.0], [10.0, 1.0, 1.0]]
+        );
+        assert_eq!(meshes[1].face_ranges, vec![[0, 0]]);
+
+        let node = format.node_index_document("nodes/2").await.unwrap();
+        let meshes = format
+            .legacy_feature_geometries(&information, &node)
+            .await
+            .unwrap();
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].vertex_count(), 3);
+        assert_eq!(meshes[0].feature_ids, vec![9]);
+    }
+}
+
+#[tokio::test]
+async fn nodes_without_vertices() {
+    let mut store = store();
+    store.insert("nodes/2/geometries/0.bin.gz", common::gzip(&geometry(2, 0)));
+    let I3SInfo::DDDObject(information) = store.scene_layer_information().await.unwrap() else {
+        panic!();
+    };
+    let node = store.node_index_document("nodes/2").await.unwrap();
+    let mesh = store
+        .legacy_geometry(&information, &node)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(mesh.vertex_count(), 0);
+    assert!(mesh.feature_ids.is_empty());
+    let meshes = store
+        .legacy_feature_geometries(&information, &node)
+        .await
+        .unwrap();
+    assert_eq!(meshes.len(), 1);
+    assert_eq!(meshes[0].vertex_count(), 0);
+    assert_eq!(meshes[0].feature_ids, vec![9]);
+    assert!(meshes[0].face_ranges.is_empty());
+
+    // a face range cannot reach past the geometry
+    let node = store.node_index_document("nodes/1").await.unwrap();
+    store.insert("nodes/1/geometries/0.bin.gz", common::gzip(&geometry(1, 0)));
+    let err = store
+        .legacy_feature_geometries(&information, &node)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, I3sError::MalformedBuffer(_)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn materials_of_the_shared_resource() {
+    let server = common::Server::start(common::scene_server(entries()));
+    for format in [Format::Memory(store()), Format::Service(service(&server))] {
+        let node = format.node_index_document("nodes/1").await.unwrap();
+        let shared = format.shared_resource(&node).await.unwrap().unwrap();
+        assert_eq!(shared.material_definitions.len(), 2);
+        let document = format.legacy_features(&node).await.unwrap().unwrap();
+        let cmn::GeometryParams::Vested(view) = &document.geometry_data[0].params else {
+            panic!();
+        };
+
+        // by href, with the largest image in the preferred encoding
+        let material = format
+            .legacy_material(&node, &view.components[0], &DEFAULT_PREFERENCES)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(material.color, [0.5, 0.25, 1.0, 0.75]);
+        let texture = material.texture.as_ref().unwrap();
+        assert!(texture.atlas);
+        assert_eq!(texture.format, TextureFormat::Jpg);
+        assert!(
+            texture.paths[0].starts_with("nodes/1/textures/0_0"),
+            "{:?}",
+            texture.paths
+        );
+        let material = format
+            .legacy_material(&node, &view.components[0], &[TextureFormat::Dds])
+            .await
+            .unwrap()
+            .unwrap();
+        let texture = material.texture.unwrap();
+        assert_eq!(texture.format, TextureFormat::Dds);
+        assert!(texture.paths[0].starts_with("nodes/1/textures/0_0_1"));
+
+        // by materialID, without a texture
+        let plain = format
+            .legacy_material(&node, &view.components[1], &[TextureFormat::Dds])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(plain.color, [1.0; 4]);
+        assert!(plain.vertex_colors && plain.texture.is_none());
+
+        let root = format.node_index_document("nodes/root").await.unwrap();
+        assert!(format.shared_resource(&root).await.unwrap().is_none());
+    }
+}
+
+#[tokio::test]
+async fn textures_of_the_shared_resource() {
+    let store = store();
+    let node = store.node_index_document("nodes/2").await.unwrap();
+    let document = store.legacy_features(&node).await.unwrap().unwrap();
+    let cmn::GeometryParams::Vested(view) = &document.geometry_data[0].params else {
+        panic!();
+    };
+    let material = store
+        .legacy_material(&node, &view.components[0], &DEFAULT_PREFERENCES)
+        .await
+        .unwrap()
+        .unwrap();
+    let texture = store.legacy_texture(&material).await.unwrap().unwrap();
+    assert_eq!(texture.format, TextureFormat::Jpg);
+    assert_eq!((texture.width, texture.height), (2, 1));
+
+    // none of the candidate paths of the dds encoding exist
+    let material = store
+        .legacy_material(&node, &view.components[0], &[TextureFormat::Dds])
+        .await
+        .unwrap()
+        .unwrap();
+    let err = store.legacy_texture(&material).await.unwrap_err();
+    assert!(err.is_not_found(), "{:?}", err);
+}
@@ -0,0 +1,83 @@
+use i3s::lepcc::{decode_intensity, decode_points, decode_rgb, decode_xyz};
+
+fn fixture(name: &str) -> Vec<u8> {
+    std::fs::read(format!(
+        "{}/tests/fixtures/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+}
+
+// two rows of points, z is bit stuffed through a lookup table
+#[test]
+fn xyz() {
+    assert_eq!(
+        decode_xyz(&fixture("pts.pccxyz")).unwrap(),
+        vec![[10.0, 20.0, 30.5], [12.0, 20.0, 30.0], [11.0, 23.0, 32.0]]
+    );
+}
+
+// huffman coded indices into a color map of two colors
+#[test]
+fn rgb() {
+    assert_eq!(
+        decode_rgb(&fixture("pts.pccrgb")).unwrap(),
+        vec![[255, 0, 0], [0, 0, 255], [0, 0, 255]]
+    );
+}
+
+// bit stuffed intensities scaled by 4
+#[test]
+fn intensity() {
+    assert_eq!(
+        decode_intensity(&fixture("pts.pccint")).unwrap(),
+        vec![4, 8, 12]
+    );
+}
+
+#[test]
+fn points() {
+    let points = decode_points(
+        &fixture("pts.pccxyz"),
+        Some(&fixture("pts.pccrgb")),
+        Some(&fixture("pts.pccint")),
+    )
+    .unwrap();
+    assert_eq!(points.positions.len(), 3);
+    assert_eq!(points.colors[0], [255, 0, 0]);
+    assert_eq!(points.intensities[2], 12);
+}
+
+#[test]
+fn arrays_longer_than_the_point_count() {
+    let mut xyz = fixture("pts.pccxyz");
+    xyz[72..76].copy_from_slice(&1u32.to_le_bytes());
+    assert!(decode_xyz(&xyz).is_err());
+}
+
+#[test]
+fn truncated_blobs() {
+    for name in ["pts.pccxyz", "pts.pccrgb", "pts.pccint"] {
+        let blob = fixture(name);
+        for length in 0..blob.len() {
+            let blob = &blob[..length];
+            assert!(decode_xyz(blob).is_err() && decode_rgb(blob).is_err());
+            assert!(decode_intensity(blob).is_err());
+        }
+    }
+}
+
+// zero bit arrays take no space, so only the blob size bounds the point count
+#[test]
+fn point_counts_larger_than_the_blob() {
+    let mut xyz = fixture("pts.pccxyz")[..80].to_vec();
+    xyz[72..76].copy_from_slice(&u32::MAX.to_le_bytes());
+    for _ in 0..4 {
+        xyz.push(0);
+        xyz.extend(u32::MAX.to_le_bytes());
+    }
+    let size = xyz.len() as i64;
+    xyz[16..24].copy_from_slice(&size.to_le_bytes());
+    assert!(decode_xyz(&xyz).is_err());
+}
@@ -0,0 +1,232 @@
+use i3s::lod::{
+    classify_node_index, select, select_mesh_nodes, Bounds, Camera, Frame, LodMetric, LodNode,
+    Selection,
+};
+use i3s::{cmn, I3sError};
+
+mod common;
+
+// 100 units in front of the origin of the mesh nodes, 800 by 600 pixels
+fn looking(direction: [f64; 3]) -> Camera {
+    Camera::new(
+        [10.0, 20.0, -100.0],
+        direction,
+        [0.0, 1.0, 0.0],
+        60f64.to_radians(),
+        [800, 600],
+    )
+    .unwrap()
+}
+
+fn bounds(center: [f64; 3], radius: f64) -> Bounds {
+    Bounds { center, radius }
+}
+
+#[test]
+fn cameras_need_an_up_vector() {
+    for (direction, up) in [
+        ([0.0, 1.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, -2.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, 1.0], [0.0, 0.0, 0.0]),
+        ([0.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ] {
+        let err = Camera::new([0.0; 3], direction, up, 1.0, [800, 600]).unwrap_err();
+        assert!(matches!(err, I3sError::InvalidCamera(_)), "{:?}", err);
+    }
+    let camera = looking([0.0, 0.0, 2.0]);
+    assert_eq!(camera.direction, [0.0, 0.0, 1.0]);
+    assert_eq!(camera.up, [0.0, 1.0, 0.0]);
+}
+
+#[test]
+fn frustum_culling() {
+    let camera = looking([0.0, 0.0, 1.0]);
+    assert!(camera.is_visible(&bounds([10.0, 20.0, 0.0], 1.0)));
+    assert!(!camera.is_visible(&bounds([10.0, 20.0, -200.0], 1.0)));
+    assert!(!camera.is_visible(&bounds([1000.0, 20.0, 0.0], 1.0)));
+
+    // spheres that only touch the near plane count as visible
+    assert!(camera.is_visible(&bounds([10.0, 20.0, -102.0], 2.0)));
+    assert!(!camera.is_visible(&bounds([10.0, 20.0, -102.0], 1.5)));
+
+    // the horizontal field of view follows from the vertical one and the aspect
+    let half_width = (30f64.to_radians().tan() * 800.0 / 600.0).atan();
+    let edge = 10.0 + 100.0 * half_width.tan();
+    assert!(camera.is_visible(&bounds([edge + 0.5, 20.0, 0.0], 1.0)));
+    assert!(!camera.is_visible(&bounds([edge + 2.0, 20.0, 0.0], 1.0)));
+    let edge = 20.0 + 100.0 * 30f64.to_radians().tan();
+    assert!(camera.is_visible(&bounds([10.0, edge + 0.5, 0.0], 1.0)));
+    assert!(!camera.is_visible(&bounds([10.0, edge + 2.0, 0.0], 1.0)));
+}
+
+#[test]
+fn screen_size() {
+    let camera = looking([0.0, 0.0, 1.0]);
+    let node = bounds([10.0, 20.0, 0.0], 1.0);
+    let pixels_per_unit = 600.0 / (2.0 * 99.0 * 30f64.to_radians().tan());
+    assert!((camera.pixels_per_unit(&node) - pixels_per_unit).abs() < 1e-9);
+    let size = camera.screen_size(&node);
+    assert!((size - 2.0 * pixels_per_unit).abs() < 1e-9);
+    assert_eq!(
+        camera.pixels_per_unit(&bounds([10.0, 20.0, 0.0], 200.0)),
+        f64::INFINITY
+    );
+
+    let refines = |metric, threshold, quality| {
+        camera.needs_refinement(metric, &node, Some(threshold), quality)
+    };
+    assert!(refines(LodMetric::MaxScreenThreshold, size - 0.1, 1.0));
+    assert!(!refines(LodMetric::MaxScreenThreshold, size + 0.1, 1.0));
+    assert!(refines(LodMetric::MaxScreenThreshold, 1.5 * size, 2.0));
+    let area = std::f64::consts::PI * size * size / 4.0;
+    assert!(refines(LodMetric::MaxScreenThresholdSQ, area - 0.1, 1.0));
+    assert!(!refines(LodMetric::MaxScreenThresholdSQ, area + 0.1, 1.0));
+    let density = 1.0 / (pixels_per_unit * pixels_per_unit);
+    assert!(refines(LodMetric::DensityThreshold, 2.0 * density, 1.0));
+    assert!(!refines(LodMetric::DensityThreshold, 0.5 * density, 1.0));
+    assert!(camera.needs_refinement(LodMetric::MaxScreenThreshold, &node, None, 1.0));
+}
+
+#[test]
+fn geographic_frames() {
+    let frame = |json: &str| {
+        let spatial_reference: cmn::SpatialReference = serde_json::from_str(json).unwrap();
+        Frame::from_spatial_reference(Some(&spatial_reference))
+    };
+    assert_eq!(frame(r#"{"wkid": 4326}"#), Frame::Geographic);
+    assert_eq!(
+        frame(r#"{"wkid": 102100, "latestWkid": 4490}"#),
+        Frame::Geographic
+    );
+    assert_eq!(
+        frame(r#"{"wkt": "GEOGCS[\"GCS_WGS_1984\"]"}"#),
+        Frame::Geographic
+    );
+    assert_eq!(frame(r#"{"wkid": 3857}"#), Frame::Cartesian);
+    assert_eq!(Frame::from_spatial_reference(None), Frame::Cartesian);
+
+    // centers move to ECEF, radii stay in metres
+    let ecef = |center| Frame::Geographic.bounds(bounds(center, 5.0));
+    assert_eq!(ecef([0.0, 0.0, 10.0]), bounds([6_378_147.0, 0.0, 0.0], 5.0));
+    let east = ecef([90.0, 0.0, 0.0]).center;
+    assert!(east[0].abs() < 1e-6 && (east[1] - 6_378_137.0).abs() < 1e-6);
+    let pole = ecef([90.0, 90.0, 0.0]).center;
+    assert!(pole[0].abs() < 1e-6 && (pole[2] - 6_356_752.314).abs() < 1e-3);
+    let cartesian = bounds([10.0, 20.0, 30.0], 5.0);
+    assert_eq!(Frame::Cartesian.bounds(cartesian), cartesian);
+}
+
+/*
+A root that is too coarse, with a child in front of the camera, one behind it
+and one that does not exist.
+*/
+fn lod_node(index: usize) -> Option<LodNode> {
+    let (center, radius, children) = match index {
+        0 => ([10.0, 20.0, 0.0], 100.0, vec![1, 2, 3]),
+        1 => ([10.0, 20.0, 50.0], 1.0, vec![]),
+        2 => ([10.0, 20.0, -300.0], 1.0, vec![]),
+        _ => return None,
+    };
+    Some(LodNode {
+        bounds: bounds(center, radius),
+        threshold: Some(1.0),
+        children,
+    })
+}
+
+#[test]
+fn nodes_are_culled_or_kept() {
+    let camera = looking([0.0, 0.0, 1.0]);
+    let selected = select(&camera, 1.0, LodMetric::MaxScreenThreshold, 0, lod_node).unwrap();
+    assert_eq!(selected, vec![1]);
+    let behind = lod_node(2).unwrap();
+    let classify = |node: &LodNode| {
+        camera.classify(
+            LodMetric::MaxScreenThreshold,
+            &node.bounds,
+            node.threshold,
+            node.children.is_empty(),
+            1.0,
+        )
+    };
+    assert_eq!(classify(&behind), Selection::Culled);
+    assert_eq!(classify(&lod_node(1).unwrap()), Selection::Render);
+    assert_eq!(classify(&lod_node(0).unwrap()), Selection::Refine);
+
+    // looking the other way keeps only the node behind
+    let back = looking([0.0, 0.0, -1.0]);
+    let selected = select(&back, 1.0, LodMetric::MaxScreenThreshold, 0, lod_node).unwrap();
+    assert_eq!(selected, vec![2]);
+}
+
+#[test]
+fn nodes_reached_twice() {
+    let camera = looking([0.0, 0.0, 1.0]);
+    let graph = |children: fn(usize) -> Vec<usize>| {
+        move |index: usize| {
+            Some(LodNode {
+                bounds: bounds([10.0, 20.0, 0.0], 1.0),
+                threshold: None,
+                children: children(index),
+            })
+        }
+    };
+    let cycle = graph(|index| vec![(index + 1) % 2]);
+    let err = select(&camera, 1.0, LodMetric::MaxScreenThreshold, 0, cycle).unwrap_err();
+    assert!(
+        matches!(err, I3sError::MalformedNodePage { ref path, .. } if path == "nodes/0"),
+        "{:?}",
+        err
+    );
+    let duplicate = graph(|index| if index == 0 { vec![1, 1] } else { vec![] });
+    let err = select(&camera, 1.0, LodMetric::MaxScreenThreshold, 0, duplicate).unwrap_err();
+    assert!(
+        matches!(err, I3sError::MalformedNodePage { ref path, .. } if path == "nodes/1"),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn mesh_node_pages() {
+    let definition: cmn::NodePageDefinition =
+        serde_json::from_value(common::mesh_layer()["nodePages"].clone()).unwrap();
+    let nodes: Vec<cmn::Node> = (0..7)
+        .map(|index| serde_json::from_value(common::mesh_node(index, 7)).unwrap())
+        .collect();
+    let node_pages: Vec<cmn::NodePage> = nodes
+        .chunks(common::NODES_PER_PAGE)
+        .map(|nodes| cmn::NodePage {
+            nodes: nodes.to_vec(),
+        })
+        .collect();
+    let selected = |camera: &Camera, quality| {
+        select_mesh_nodes(camera, quality, Frame::Cartesian, &definition, &node_pages).unwrap()
+    };
+    let camera = looking([0.0, 0.0, 1.0]);
+    assert_eq!(selected(&camera, 0.001), vec![0]);
+    assert_eq!(selected(&camera, 1000.0), vec![3, 4, 5, 6]);
+    assert!(selected(&looking([0.0, 0.0, -1.0]), 1000.0).is_empty());
+}
+
+// a camera in ECEF 63 metres above null island and a node 10 metres up
+#[test]
+fn geographic_node_index() {
+    let node: cmn::NodeIndexDocument =
+        serde_json::from_str(r#"{"id": "root", "level": 0, "mbs": [0.0, 0.0, 10.0, 5.0]}"#)
+            .unwrap();
+    let ecef = |direction| {
+        Camera::new(
+            [6_378_200.0, 0.0, 0.0],
+            direction,
+            [0.0, 0.0, 1.0],
+            1.0,
+            [800, 600],
+        )
+        .unwrap()
+    };
+    let classify = |camera: Camera| classify_node_index(&camera, 1.0, Frame::Geographic, &node);
+    assert_eq!(classify(ecef([-1.0, 0.0, 0.0])), Selection::Render);
+    assert_eq!(classify(ecef([1.0, 0.0, 0.0])), Selection::Culled);
+    assert_eq!(classify(ecef([0.0, 1.0, 0.0])), Selection::Culled);
+}
@@ -0,0 +1,69 @@
+use std::io::{Cursor, Write};
+
+use i3s::SceneLayerPackage;
+
+mod common;
+
+// stored and deflated entries, which are both read from the map
+fn entries() -> Vec<(String, Vec<u8>, zip::CompressionMethod)> {
+    (0..64)
+        .map(|index| {
+            let method = match index % 2 {
+                0 => zip::CompressionMethod::Stored,
+                _ => zip::CompressionMethod::Deflated,
+            };
+            (
+                format!("nodes/{}/geometries/0.bin.gz", index),
+                format!("geometry {}", index).repeat(index + 1).into_bytes(),
+                method,
+            )
+        })
+        .collect()
+}
+
+fn package() -> Vec<u8> {
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, data, method) in entries() {
+        let options = zip::write::SimpleFileOptions::default().compression_method(method);
+        writer.start_file(name, options).unwrap();
+        writer.write_all(&data).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+#[test]
+fn parallel_reads() {
+    let dir = common::temp_dir("mmap-parallel");
+    let path = dir.join("layer.slpk");
+    std::fs::write(&path, package()).unwrap();
+    let package = SceneLayerPackage::open_mapped(&path).unwrap();
+
+    let entries = entries();
+    std::thread::scope(|scope| {
+        for thread in 0..8 {
+            let (package, entries) = (&package, &entries);
+            scope.spawn(move || {
+                for (name, data, _) in entries.iter().skip(thread).step_by(8) {
+                    assert_eq!(package.get(name).unwrap(), *data);
+                }
+            });
+        }
+    });
+    assert!(package
+        .get("nodes/64/geometries/0.bin.gz")
+        .unwrap_err()
+        .is_not_found());
+}
+
+// the map and the zip reader agree on every entry
+#[test]
+fn mapped_and_unmapped_reads() {
+    let dir = common::temp_dir("mmap-unmapped");
+    let path = dir.join("layer.slpk");
+    std::fs::write(&path, package()).unwrap();
+    let mapped = SceneLayerPackage::open_mapped(&path).unwrap();
+    let unmapped = SceneLayerPackage::open(&path).unwrap();
+    for (name, _, _) in entries() {
+        assert_eq!(mapped.get(&name).unwrap(), unmapped.get(&name).unwrap());
+    }
+}
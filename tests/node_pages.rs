@@ -0,0 +1,71 @@
+use i3s::{I3SFormatExt, I3sError, Profile, SceneLayerPackage};
+
+mod common;
+
+fn open(entries: &[(String, Vec<u8>)]) -> SceneLayerPackage<std::io::Cursor<Vec<u8>>> {
+    SceneLayerPackage::from_bytes(common::package(entries)).unwrap()
+}
+
+async fn profile(entries: &[(String, Vec<u8>)]) -> Result<Profile, I3sError> {
+    let package = open(entries);
+    let information = package.scene_layer_information().await?;
+    package.profile(&information).await
+}
+
+#[tokio::test]
+async fn pages_are_loaded_in_order() {
+    let mut entries = common::mesh_entries(7);
+    // the central directory is not ordered by page number
+    entries.reverse();
+    let Profile::IntegratedMesh(mesh) = profile(&entries).await.unwrap() else {
+        panic!();
+    };
+    assert_eq!(mesh.node_pages.len(), 4);
+    let indices: Vec<usize> = (0..7).map(|i| mesh.node(&i).unwrap().index).collect();
+    assert_eq!(indices, (0..7).collect::<Vec<_>>());
+    assert_eq!(mesh.nodes(&[6, 2]).unwrap()[1].children, vec![5, 6]);
+}
+
+#[tokio::test]
+async fn point_layers() {
+    let mut entries = common::mesh_entries(3);
+    let mut layer = common::mesh_layer();
+    layer["layerType"] = "Point".into();
+    layer["pointNodePages"] = layer["nodePages"].take();
+    layer["version"] = "1.7".into();
+    entries[0].1 = common::gzip_json(&layer);
+    let Profile::Point(point) = profile(&entries).await.unwrap() else {
+        panic!();
+    };
+    assert_eq!(point.root().unwrap().children, vec![1, 2]);
+    assert!(point.node(&3).is_none());
+}
+
+#[tokio::test]
+async fn missing_pages() {
+    let entries: Vec<_> = common::mesh_entries(7)
+        .into_iter()
+        .filter(|(name, _)| name != "nodepages/1.json.gz")
+        .collect();
+    let err = profile(&entries).await.unwrap_err();
+    assert!(
+        matches!(&err, I3sError::MalformedNodePage { path, .. } if path == "nodepages/2.json.gz"),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn nodes_that_do_not_fit_their_page() {
+    let mut entries = common::mesh_entries(3);
+    let nodes: Vec<_> = (0..3).map(|i| common::mesh_node(i, 3)).collect();
+    entries[2].1 = common::gzip_json(&serde_json::json!({ "nodes": nodes }));
+    let err = profile(&entries).await.unwrap_err();
+    assert!(err.to_string().contains("3 nodes exceed nodesPerPage of 2"));
+
+    let mut entries = common::mesh_entries(3);
+    let nodes = [common::mesh_node(1, 3), common::mesh_node(0, 3)];
+    entries[2].1 = common::gzip_json(&serde_json::json!({ "nodes": nodes }));
+    let err = profile(&entries).await.unwrap_err();
+    assert!(err.to_string().contains("node 1 is stored at index 0"));
+}
@@ -0,0 +1,67 @@
+use i3s::{Format, I3sError, Profile};
+
+mod common;
+
+#[test]
+fn service_urls() {
+    for (source, base, layer) in [
+        (
+            "https://host/arcgis/rest/services/City/SceneServer/layers/3",
+            "https://host/arcgis/rest/services/City/SceneServer/",
+            3,
+        ),
+        (
+            "https://host/arcgis/rest/services/City/SceneServer",
+            "https://host/arcgis/rest/services/City/SceneServer/",
+            0,
+        ),
+        (
+            "https://host/arcgis/rest/services/City/SceneServer/?f=json",
+            "https://host/arcgis/rest/services/City/SceneServer/",
+            0,
+        ),
+    ] {
+        let Format::Service(service) = Format::open(source).unwrap() else {
+            panic!("{} is not a service", source);
+        };
+        assert_eq!(service.base.as_str(), base);
+        assert_eq!(service.layer, layer);
+    }
+}
+
+#[test]
+fn sources() {
+    let remote = Format::open("https://host/data/City.SLPK").unwrap();
+    assert!(matches!(remote, Format::RemotePackage(_)));
+
+    let dir = common::temp_dir("open-sources");
+    let entries = common::mesh_entries(3);
+    common::write_folder(&dir.join("layer"), &entries);
+    std::fs::write(dir.join("layer.slpk"), common::package(&entries)).unwrap();
+    assert!(matches!(
+        Format::open(dir.join("layer").to_str().unwrap()).unwrap(),
+        Format::Folder(_)
+    ));
+    assert!(matches!(
+        Format::open(dir.join("layer.slpk").to_str().unwrap()).unwrap(),
+        Format::SceneLayerPackage(_)
+    ));
+    let missing = Format::open(dir.join("missing.slpk").to_str().unwrap()).unwrap_err();
+    assert!(matches!(missing, I3sError::ResourceNotFound(_)));
+}
+
+#[tokio::test]
+async fn profile_of_the_layer_type() {
+    let dir = common::temp_dir("open-profile");
+    let path = dir.join("layer.slpk");
+    std::fs::write(&path, common::package(&common::mesh_entries(5))).unwrap();
+
+    let layer = i3s::open(path.to_str().unwrap()).await.unwrap();
+    assert_eq!(layer.information.layer_type(), "IntegratedMesh");
+    let Profile::IntegratedMesh(mesh) = &layer.profile else {
+        panic!("{:?}", layer.profile);
+    };
+    assert_eq!(mesh.root().unwrap().index, 0);
+    assert_eq!(mesh.node(&4).unwrap().parent, Some(1));
+    assert!(mesh.node(&5).is_none());
+}
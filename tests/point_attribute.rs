@@ -0,0 +1,123 @@
+use i3s::attribute::AttributeColumn;
+use i3s::geometry::decode_point_attribute;
+use i3s::{pcl, I3SFormatExt, MemoryStore};
+
+mod common;
+
+fn fixture(name: &str) -> Vec<u8> {
+    std::fs::read(format!(
+        "{}/tests/fixtures/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+}
+
+fn attribute(value: serde_json::Value) -> pcl::AttributeInfo {
+    serde_json::from_value(value).unwrap()
+}
+
+fn return_numbers() -> pcl::AttributeInfo {
+    attribute(serde_json::json!({
+        "key": "4",
+        "name": "RETURNS",
+        "encoding": "",
+        "attributeValues": {"valueType": "UInt8", "valuesPerElement": 2}
+    }))
+}
+
+#[test]
+fn values_per_element() {
+    let attribute = decode_point_attribute(&return_numbers(), &[1, 2, 2, 2, 1, 1]).unwrap();
+    assert_eq!(attribute.point_count(), 3);
+    assert_eq!(attribute.value(1, 0), Some(2.0));
+    assert_eq!(attribute.value(2, 1), Some(1.0));
+    assert_eq!(attribute.value(0, 2), None);
+    assert_eq!(attribute.value(3, 0), None);
+}
+
+#[test]
+fn little_endian_values() {
+    let gps_time = attribute(serde_json::json!({
+        "key": "8",
+        "name": "GPS_TIME",
+        "attributeValues": {"valueType": "Float64", "valuesPerElement": 1}
+    }));
+    let buffer: Vec<u8> = [0.5f64, 2.25]
+        .iter()
+        .flat_map(|t| t.to_le_bytes())
+        .collect();
+    let attribute = decode_point_attribute(&gps_time, &buffer).unwrap();
+    assert!(
+        matches!(attribute.values, AttributeColumn::Float64(ref times) if *times == [0.5, 2.25])
+    );
+    assert!(decode_point_attribute(&gps_time, &buffer[..12]).is_err());
+}
+
+#[test]
+fn lepcc_attributes() {
+    let intensity = attribute(serde_json::json!({
+        "key": "2",
+        "name": "INTENSITY",
+        "encoding": "lepcc-intensity"
+    }));
+    let intensities = decode_point_attribute(&intensity, &fixture("pts.pccint")).unwrap();
+    assert_eq!(intensities.point_count(), 3);
+    assert_eq!(intensities.value(2, 0), Some(12.0));
+
+    let rgb = attribute(serde_json::json!({
+        "key": "3",
+        "name": "RGB",
+        "encoding": "lepcc-rgb"
+    }));
+    let colors = decode_point_attribute(&rgb, &fixture("pts.pccrgb")).unwrap();
+    assert_eq!(colors.values_per_element, 3);
+    assert_eq!(colors.value(0, 0), Some(255.0));
+}
+
+#[test]
+fn malformed_attributes() {
+    // an odd number of values for two values per point
+    assert!(decode_point_attribute(&return_numbers(), &[1, 2, 2]).is_err());
+    let mut unknown = return_numbers();
+    unknown.attribute_values.as_mut().unwrap().value_type = "Int128".to_string();
+    assert!(decode_point_attribute(&unknown, &[1, 2]).is_err());
+    unknown.attribute_values = None;
+    assert!(decode_point_attribute(&unknown, &[1, 2]).is_err());
+}
+
+#[tokio::test]
+async fn node_attributes_by_name() {
+    let mut information = pcl::SceneLayerInformation {
+        attribute_storage_info: vec![
+            attribute(serde_json::json!({
+                "key": "1",
+                "name": "ELEVATION",
+                "encoding": "embedded-elevation"
+            })),
+            return_numbers(),
+        ],
+        ..Default::default()
+    };
+    information.store.default_geometry_schema.encoding = "lepcc-xyz".to_string();
+    let node = pcl::Node {
+        resource_id: 7,
+        ..Default::default()
+    };
+    let mut store = MemoryStore::new();
+    store.insert("nodes/7/geometries/0.bin.pccxyz", fixture("pts.pccxyz"));
+    store.insert(
+        "nodes/7/attributes/4.bin.gz",
+        common::gzip(&[1, 1, 1, 2, 2, 2]),
+    );
+    let attributes = store.point_attributes(&information, &node).await.unwrap();
+    assert_eq!(attributes["ELEVATION"].value(2, 0), Some(32.0));
+    assert_eq!(attributes["RETURNS"].value(1, 1), Some(2.0));
+
+    store.remove("nodes/7/attributes/4.bin.gz");
+    let err = store
+        .point_attributes(&information, &node)
+        .await
+        .unwrap_err();
+    assert!(err.is_not_found());
+}
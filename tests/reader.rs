@@ -0,0 +1,62 @@
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use i3s::{I3SFormatExt, Profile, SceneLayerPackage};
+
+mod common;
+
+// a reader that is neither a file nor a cursor, counting the bytes read
+struct CountingReader {
+    inner: Cursor<Vec<u8>>,
+    read: Arc<AtomicUsize>,
+}
+
+impl Read for CountingReader {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buffer)?;
+        self.read.fetch_add(read, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+impl Seek for CountingReader {
+    fn seek(&mut self, position: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(position)
+    }
+}
+
+#[tokio::test]
+async fn packages_from_any_reader() {
+    let read = Arc::new(AtomicUsize::new(0));
+    let package = SceneLayerPackage::from_reader(CountingReader {
+        inner: Cursor::new(common::package(&common::mesh_entries(5))),
+        read: read.clone(),
+    })
+    .unwrap();
+    let information = package.scene_layer_information().await.unwrap();
+    let Profile::IntegratedMesh(mesh) = package.profile(&information).await.unwrap() else {
+        panic!();
+    };
+    assert_eq!(mesh.node_pages.len(), 3);
+    assert!(read.load(Ordering::Relaxed) > 0);
+}
+
+#[test]
+fn packages_in_memory() {
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let deflated = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    writer.start_file("metadata.json", deflated).unwrap();
+    writer.write_all(&[b'x'; 1000]).unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+    assert!(bytes.len() < 1000);
+
+    let package = SceneLayerPackage::from_bytes(bytes).unwrap();
+    assert_eq!(package.get("metadata.json").unwrap(), [b'x'; 1000]);
+    assert!(package
+        .get("3dSceneLayer.json.gz")
+        .unwrap_err()
+        .is_not_found());
+    assert!(SceneLayerPackage::from_bytes(b"not a zip file".to_vec()).is_err());
+}
@@ -0,0 +1,100 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use i3s::{I3SFormat, I3sError, Service};
+
+mod common;
+
+fn builder(server: &common::Server) -> i3s::ServiceBuilder {
+    Service::builder(server.url.join("SceneServer").unwrap()).backoff(Duration::from_millis(1))
+}
+
+// answers the first failures requests with status, and the rest with a resource
+fn flaky(status: u16, failures: usize) -> impl Fn(&common::Request) -> common::Response {
+    let requests = AtomicUsize::new(0);
+    move |_| match requests.fetch_add(1, Ordering::SeqCst) < failures {
+        true => common::Response::status(status),
+        false => common::Response::ok(b"resource".to_vec()),
+    }
+}
+
+#[tokio::test]
+async fn server_errors_are_retried() {
+    let server = common::Server::start(flaky(503, 2));
+    let service = builder(&server).max_retries(3).build();
+    assert_eq!(service.get("nodes/0").await.unwrap(), b"resource");
+    assert_eq!(server.count("/SceneServer/layers/0/nodes/0"), 3);
+
+    let server = common::Server::start(flaky(429, 5));
+    let service = builder(&server).max_retries(1).build();
+    let err = service.get("nodes/0").await.unwrap_err();
+    assert!(
+        matches!(err, I3sError::HttpStatus { status: 429, .. }),
+        "{:?}",
+        err
+    );
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn client_errors_are_not_retried() {
+    let server = common::Server::start(flaky(404, 1));
+    let service = builder(&server).max_retries(3).build();
+    assert!(service.get("nodes/0").await.unwrap_err().is_not_found());
+
+    let server = common::Server::start(flaky(403, 1));
+    let service = builder(&server).max_retries(3).build();
+    let err = service.get("nodes/0").await.unwrap_err();
+    assert!(matches!(err, I3sError::HttpStatus { status: 403, .. }));
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn errors_in_successful_responses() {
+    let server = common::Server::start(|request: &common::Request| {
+        let code = request.path.rsplit('/').next().unwrap().to_string();
+        let body = format!(r#" {{"error":{{"code":{},"message":"failed"}}}}"#, code);
+        common::Response::ok(body.into_bytes())
+    });
+    let service = builder(&server).build();
+    assert!(service.get("404").await.unwrap_err().is_not_found());
+    let err = service.get("400").await.unwrap_err();
+    assert!(matches!(err, I3sError::HttpStatus { status: 400, .. }));
+}
+
+#[tokio::test]
+async fn concurrent_requests_are_bounded() {
+    let active = Arc::new(AtomicUsize::new(0));
+    let most = Arc::new(AtomicUsize::new(0));
+    let (counter, maximum) = (active.clone(), most.clone());
+    let server = common::Server::start(move |_| {
+        let now = counter.fetch_add(1, Ordering::SeqCst) + 1;
+        maximum.fetch_max(now, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(20));
+        counter.fetch_sub(1, Ordering::SeqCst);
+        common::Response::ok(b"node".to_vec())
+    });
+    let service = builder(&server).max_concurrent_requests(2).build();
+    let paths: Vec<String> = (0..8).map(|index| format!("nodes/{}", index)).collect();
+    let results = service.get_many(&paths).await;
+    assert!(results.iter().all(|result| result.is_ok()));
+    assert_eq!(server.requests().len(), 8);
+    assert!(most.load(Ordering::SeqCst) <= 2);
+}
+
+#[tokio::test]
+async fn timeouts() {
+    let server = common::Server::start(|_| {
+        std::thread::sleep(Duration::from_millis(500));
+        common::Response::ok(Vec::new())
+    });
+    let service = builder(&server)
+        .timeout(Some(Duration::from_millis(50)))
+        .max_retries(0)
+        .build();
+    assert!(matches!(
+        service.get("nodes/0").await,
+        Err(I3sError::Request(_))
+    ));
+}
@@ -0,0 +1,71 @@
+use i3s::{I3sError, Service};
+
+mod common;
+
+fn service(server: &common::Server) -> Service {
+    Service::connect(server.url.join("SceneServer/").unwrap())
+}
+
+#[tokio::test]
+async fn node_pages_are_fetched_when_used() {
+    let server = common::Server::start(common::scene_server(common::mesh_entries(7)));
+    let service = service(&server);
+
+    assert_eq!(service.root().await.unwrap().children, vec![1, 2]);
+    assert_eq!(server.count("/SceneServer/layers/0/nodepages/"), 1);
+    // both lookups share the service
+    let (three, five) = tokio::join!(service.node(&3), service.node(&5));
+    assert_eq!((three.unwrap().index, five.unwrap().index), (3, 5));
+    assert_eq!(server.count("/SceneServer/layers/0/nodepages/"), 3);
+
+    let nodes = service.nodes(&[1, 6, 4]).await.unwrap();
+    let indices: Vec<usize> = nodes.iter().map(|node| node.index).collect();
+    assert_eq!(indices, vec![1, 6, 4]);
+    assert_eq!(server.count("/SceneServer/layers/0/nodepages/"), 4);
+    assert_eq!(service.node_page(2).await.unwrap().nodes.len(), 2);
+    assert_eq!(server.count("/SceneServer/layers/0/nodepages/"), 4);
+}
+
+#[tokio::test]
+async fn node_pages_are_checked() {
+    let mut entries = common::mesh_entries(5);
+    let nodes = [common::mesh_node(3, 5), common::mesh_node(2, 5)];
+    entries[3].1 = common::gzip_json(&serde_json::json!({ "nodes": nodes }));
+    let server = common::Server::start(common::scene_server(entries));
+    let service = service(&server);
+
+    assert_eq!(service.node(&0).await.unwrap().index, 0);
+    let err = service.node(&2).await.unwrap_err();
+    assert!(
+        matches!(&err, I3sError::MalformedNodePage { path, reason }
+            if path == "layers/0/nodepages/1" && reason == "node 3 is stored at index 2"),
+        "{:?}",
+        err
+    );
+    assert!(service.nodes(&[0, 3]).await.is_err());
+}
+
+#[tokio::test]
+async fn nodes_past_the_last_page() {
+    let server = common::Server::start(common::scene_server(common::mesh_entries(5)));
+    let service = service(&server);
+    assert!(service.node(&9).await.unwrap_err().is_not_found());
+    let err = service.node(&5).await.unwrap_err();
+    assert!(
+        matches!(err, I3sError::MalformedNodePage { .. }),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn bases_without_a_trailing_slash() {
+    let server = common::Server::start(common::scene_server(common::mesh_entries(3)));
+    let service = Service::connect(server.url.join("SceneServer").unwrap());
+    assert_eq!(
+        service.base.as_str(),
+        server.url.join("SceneServer/").unwrap().as_str()
+    );
+    assert_eq!(service.root().await.unwrap().children, vec![1, 2]);
+    assert_eq!(server.count("/SceneServer/layers/0/nodepages/0"), 1);
+}
@@ -0,0 +1,64 @@
+use i3s::{I3SFormat, I3SFormatExt, MemoryStore, Profile, SceneLayerFolder, SceneLayerPackage};
+
+mod common;
+
+// profile code written once for any source
+async fn children_of_root<F: I3SFormat>(store: &F) -> Vec<usize> {
+    let information = store.scene_layer_information().await.unwrap();
+    let Profile::IntegratedMesh(mesh) = store.profile(&information).await.unwrap() else {
+        panic!();
+    };
+    mesh.root().unwrap().children.clone()
+}
+
+#[tokio::test]
+async fn every_store_loads_the_same_layer() {
+    let entries = common::mesh_entries(5);
+    let dir = common::temp_dir("store-sources");
+    common::write_folder(&dir, &entries);
+
+    let memory: MemoryStore = entries.clone().into_iter().collect();
+    let package = SceneLayerPackage::from_bytes(common::package(&entries)).unwrap();
+    let folder = SceneLayerFolder::open(&dir).unwrap();
+    assert_eq!(children_of_root(&memory).await, vec![1, 2]);
+    assert_eq!(children_of_root(&package).await, vec![1, 2]);
+    assert_eq!(children_of_root(&folder).await, vec![1, 2]);
+    assert_eq!(
+        children_of_root(&i3s::Format::Memory(memory)).await,
+        vec![1, 2]
+    );
+}
+
+#[tokio::test]
+async fn resources_are_decompressed_when_gzipped() {
+    let mut store = MemoryStore::new();
+    store.insert("nodes/0/geometries/0.bin.gz", common::gzip(b"gzipped"));
+    store.insert("nodes/0/geometries/1.bin", b"plain".to_vec());
+
+    assert_ne!(
+        store.get("nodes/0/geometries/0.bin.gz").await.unwrap(),
+        b"gzipped"
+    );
+    assert_eq!(
+        store
+            .get_resource("nodes/0/geometries/0.bin.gz")
+            .await
+            .unwrap(),
+        b"gzipped"
+    );
+    assert_eq!(
+        store
+            .get_resource("nodes/0/geometries/1.bin")
+            .await
+            .unwrap(),
+        b"plain"
+    );
+    let paths = ["nodes/0/geometries/2.bin", "nodes/0/geometries/1.bin"].map(String::from);
+    assert_eq!(store.get_first(&paths).await.unwrap(), b"plain");
+    let results = store.get_many(&paths).await;
+    assert!(results[0].as_ref().unwrap_err().is_not_found());
+    assert_eq!(results[1].as_ref().unwrap(), b"plain");
+
+    assert!(store.exists("nodes/0/geometries/1.bin").await.unwrap());
+    assert!(!store.exists("nodes/0/geometries/2.bin").await.unwrap());
+}
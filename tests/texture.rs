@@ -0,0 +1,149 @@
+use i3s::texture::{texture_size, Texture, TextureFormat, DEFAULT_PREFERENCES};
+use i3s::{cmn, I3SFormatExt, MemoryStore};
+
+mod common;
+
+// the signature and IHDR chunk of a png
+fn png_header(width: u32, height: u32) -> Vec<u8> {
+    let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+    data.extend(width.to_be_bytes());
+    data.extend(height.to_be_bytes());
+    data
+}
+
+// an APP0 segment followed by a baseline start of frame
+fn jpg_header(width: u16, height: u16) -> Vec<u8> {
+    let mut data = vec![0xff, 0xd8, 0xff, 0xe0, 0, 4, 0, 0, 0xff, 0xc0, 0, 11, 8];
+    data.extend(height.to_be_bytes());
+    data.extend(width.to_be_bytes());
+    data
+}
+
+fn dds_header(width: u32, height: u32) -> Vec<u8> {
+    let mut data = b"DDS \x7c\0\0\0\0\0\0\0".to_vec();
+    data.extend(height.to_le_bytes());
+    data.extend(width.to_le_bytes());
+    data
+}
+
+#[test]
+fn sizes_from_headers() {
+    assert_eq!(
+        texture_size(TextureFormat::Png, &png_header(64, 32)),
+        Some((64, 32))
+    );
+    assert_eq!(
+        texture_size(TextureFormat::Jpg, &jpg_header(640, 480)),
+        Some((640, 480))
+    );
+    assert_eq!(
+        texture_size(TextureFormat::Dds, &dds_header(256, 128)),
+        Some((256, 128))
+    );
+    let mut ktx2 = b"\xabKTX 20\xbb\r\n\x1a\n".to_vec();
+    ktx2.extend([0; 8]);
+    ktx2.extend([16u32, 8].map(u32::to_le_bytes).concat());
+    assert_eq!(TextureFormat::detect(&ktx2), Some(TextureFormat::Ktx2));
+    assert_eq!(texture_size(TextureFormat::Ktx2, &ktx2), Some((16, 8)));
+    assert_eq!(
+        texture_size(TextureFormat::Png, &png_header(64, 32)[..20]),
+        None
+    );
+}
+
+#[test]
+fn magic_numbers_win_over_declared_formats() {
+    let texture = Texture::new(TextureFormat::Jpg, png_header(2, 4)).unwrap();
+    assert_eq!(texture.format, TextureFormat::Png);
+    assert_eq!((texture.width, texture.height), (2, 4));
+    assert!(Texture::new(TextureFormat::Jpg, b"not an image".to_vec()).is_err());
+}
+
+#[test]
+fn format_names() {
+    assert_eq!(TextureFormat::from_name("JPEG"), Some(TextureFormat::Jpg));
+    assert_eq!(
+        TextureFormat::from_encoding("image/vnd-ms.dds"),
+        Some(TextureFormat::Dds)
+    );
+    assert_eq!(TextureFormat::from_name("basis"), None);
+}
+
+fn textured_layer() -> serde_json::Value {
+    let mut layer = common::mesh_layer();
+    layer["materialDefinitions"] = serde_json::json!([
+        {"pbrMetallicRoughness": {"baseColorTexture": {"textureSetDefinitionId": 0}}}
+    ]);
+    layer["textureSetDefinitions"] = serde_json::json!([
+        {"formats": [{"name": "0", "format": "jpg"}, {"name": "0_0_1", "format": "dds"}]}
+    ]);
+    layer
+}
+
+fn textured_node() -> cmn::Node {
+    let mut node = common::mesh_node(0, 1);
+    node["mesh"]["material"] = serde_json::json!({
+        "definition": 0,
+        "resource": 3,
+        "texelCountHint": 0
+    });
+    serde_json::from_value(node).unwrap()
+}
+
+#[tokio::test]
+async fn node_textures_in_the_preferred_format() {
+    let mut store = MemoryStore::new();
+    store.insert("3dSceneLayer.json.gz", common::gzip_json(&textured_layer()));
+    store.insert("nodes/3/textures/0.jpg", jpg_header(8, 8));
+    store.insert(
+        "nodes/3/textures/0_0_1.bin.dds.gz",
+        common::gzip(&dds_header(4, 4)),
+    );
+    let i3s::I3SInfo::IntegratedMesh(information) = store.scene_layer_information().await.unwrap()
+    else {
+        panic!();
+    };
+    let node = textured_node();
+
+    let texture = store
+        .texture(&information, &node, &DEFAULT_PREFERENCES)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!((texture.format, texture.width), (TextureFormat::Jpg, 8));
+    let texture = store
+        .texture(&information, &node, &[TextureFormat::Dds])
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!((texture.format, texture.width), (TextureFormat::Dds, 4));
+    // a format the layer does not have falls back to one it does
+    let texture = store
+        .texture(&information, &node, &[TextureFormat::Ktx2])
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(texture.format, TextureFormat::Jpg);
+
+    let mut untextured = node;
+    untextured.mesh.as_mut().unwrap().material = None;
+    assert!(store
+        .texture(&information, &untextured, &DEFAULT_PREFERENCES)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn rgba_pixels() {
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::RgbImage::from_pixel(2, 1, image::Rgb([10, 20, 30]))
+        .write_to(&mut png, image::ImageFormat::Png)
+        .unwrap();
+    let texture = Texture::new(TextureFormat::Png, png.into_inner()).unwrap();
+    assert_eq!(texture.rgba.unwrap(), [10, 20, 30, 255].repeat(2));
+
+    let texture = Texture::new(TextureFormat::Dds, dds_header(4, 4)).unwrap();
+    assert!(texture.rgba.is_none());
+}
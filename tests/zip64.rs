@@ -0,0 +1,134 @@
+use i3s::io::{find_central_directory, parse_central_directory, read_local_entry};
+use i3s::SceneLayerPackage;
+
+const NAME: &[u8] = b"metadata.json";
+const DATA: &[u8] = b"{\"folderPattern\":\"basic\"}";
+
+fn zip64_extra(values: &[u64]) -> Vec<u8> {
+    let mut extra = Vec::new();
+    extra.extend_from_slice(&1u16.to_le_bytes());
+    extra.extend_from_slice(&(values.len() as u16 * 8).to_le_bytes());
+    for value in values {
+        extra.extend_from_slice(&value.to_le_bytes());
+    }
+    extra
+}
+
+/*
+A stored entry whose sizes and header offset only fit the zip64 extra fields,
+followed by a zip64 end of central directory record and locator.
+*/
+fn zip64_package() -> Vec<u8> {
+    let crc = crc32(DATA);
+    let size = DATA.len() as u64;
+    let mut package = Vec::new();
+
+    let local_extra = zip64_extra(&[size, size]);
+    package.extend_from_slice(&0x04034b50u32.to_le_bytes());
+    package.extend_from_slice(&45u16.to_le_bytes());
+    package.extend_from_slice(&[0; 8]);
+    package.extend_from_slice(&crc.to_le_bytes());
+    package.extend_from_slice(&u32::MAX.to_le_bytes());
+    package.extend_from_slice(&u32::MAX.to_le_bytes());
+    package.extend_from_slice(&(NAME.len() as u16).to_le_bytes());
+    package.extend_from_slice(&(local_extra.len() as u16).to_le_bytes());
+    package.extend_from_slice(NAME);
+    package.extend_from_slice(&local_extra);
+    package.extend_from_slice(DATA);
+
+    let directory_offset = package.len() as u64;
+    let directory_extra = zip64_extra(&[size, size, 0]);
+    package.extend_from_slice(&0x02014b50u32.to_le_bytes());
+    package.extend_from_slice(&45u16.to_le_bytes());
+    package.extend_from_slice(&45u16.to_le_bytes());
+    package.extend_from_slice(&[0; 8]);
+    package.extend_from_slice(&crc.to_le_bytes());
+    package.extend_from_slice(&u32::MAX.to_le_bytes());
+    package.extend_from_slice(&u32::MAX.to_le_bytes());
+    package.extend_from_slice(&(NAME.len() as u16).to_le_bytes());
+    package.extend_from_slice(&(directory_extra.len() as u16).to_le_bytes());
+    package.extend_from_slice(&[0; 10]);
+    package.extend_from_slice(&u32::MAX.to_le_bytes());
+    package.extend_from_slice(NAME);
+    package.extend_from_slice(&directory_extra);
+    let directory_size = package.len() as u64 - directory_offset;
+
+    let record_offset = package.len() as u64;
+    package.extend_from_slice(&0x06064b50u32.to_le_bytes());
+    package.extend_from_slice(&44u64.to_le_bytes());
+    package.extend_from_slice(&45u16.to_le_bytes());
+    package.extend_from_slice(&45u16.to_le_bytes());
+    package.extend_from_slice(&[0; 8]);
+    package.extend_from_slice(&1u64.to_le_bytes());
+    package.extend_from_slice(&1u64.to_le_bytes());
+    package.extend_from_slice(&directory_size.to_le_bytes());
+    package.extend_from_slice(&directory_offset.to_le_bytes());
+
+    package.extend_from_slice(&0x07064b50u32.to_le_bytes());
+    package.extend_from_slice(&0u32.to_le_bytes());
+    package.extend_from_slice(&record_offset.to_le_bytes());
+    package.extend_from_slice(&1u32.to_le_bytes());
+
+    package.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    package.extend_from_slice(&[0; 4]);
+    package.extend_from_slice(&u16::MAX.to_le_bytes());
+    package.extend_from_slice(&u16::MAX.to_le_bytes());
+    package.extend_from_slice(&u32::MAX.to_le_bytes());
+    package.extend_from_slice(&u32::MAX.to_le_bytes());
+    package.extend_from_slice(&0u16.to_le_bytes());
+    package
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb88320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[test]
+fn central_directory() {
+    let package = zip64_package();
+    let (offset, size) = find_central_directory(&package, 0).unwrap();
+    let directory = &package[offset as usize..(offset + size) as usize];
+    let entries = parse_central_directory(directory).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].name.as_bytes(), NAME);
+    assert_eq!(entries[0].header_start, 0);
+    assert_eq!(entries[0].compressed_size, DATA.len() as u64);
+}
+
+// the tail of a large package starts somewhere after its first byte
+#[test]
+fn central_directory_in_tail() {
+    let package = zip64_package();
+    let tail_start = 20;
+    let (offset, _) = find_central_directory(&package[tail_start..], tail_start as u64).unwrap();
+    assert_eq!(
+        offset,
+        (30 + NAME.len() + 20 + DATA.len()) as u64,
+        "offsets are relative to the package"
+    );
+}
+
+#[test]
+fn local_header() {
+    let entry = read_local_entry(&zip64_package(), 0).unwrap().unwrap();
+    assert_eq!(entry.compressed_size, DATA.len() as u64);
+    let start = entry.data_offset() as usize;
+    assert_eq!(&zip64_package()[start..start + DATA.len()], DATA);
+}
+
+#[test]
+fn package() {
+    let package = SceneLayerPackage::from_bytes(zip64_package()).unwrap();
+    assert_eq!(package.get("metadata.json").unwrap(), DATA);
+    assert!(package
+        .get("nodepages/0.json.gz")
+        .unwrap_err()
+        .is_not_found());
+}